@@ -0,0 +1,423 @@
+//! Admin subcommands operating directly on the database
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use std::io::Write;
+
+use super::{
+    AuditCommand, Command, DbCommand, DevicesCommand, KeysCommand, PairArgs, TransfersCommand,
+};
+use crate::config::Config;
+use crate::db::{AuditQuery, SortOrder, TransferQuery};
+use crate::qr::generate_qr_terminal;
+use crate::server::api::{create_pairing, validate_device_update};
+use crate::server::audit::{self, Actor, AuditKind, AUDIT_KINDS};
+use crate::server::discovery::reachable_addrs;
+use crate::server::presence::PresenceTracker;
+use crate::server::transfers::TRANSFER_STATUSES;
+use crate::server::upload::transfer_dir;
+use crate::server::{PairRequest, UpdateDeviceRequest};
+use crate::{AppState, Database, ServerCertificate};
+
+/// Open the configured database, creating its directory if needed
+///
+/// Migrations are not applied; see [`Database::init_schema`].
+pub async fn open_database(config: &Config) -> Result<Database> {
+    std::fs::create_dir_all(config.data_dir())?;
+    let url = format!("sqlite:{}?mode=rwc", config.database.path.display());
+    Database::new(&url)
+        .await
+        .with_context(|| format!("Failed to open database {}", config.database.path.display()))
+}
+
+/// Run an admin subcommand
+///
+/// # Arguments
+/// * `command` - Any subcommand except `serve`
+/// * `config` - Validated configuration
+/// * `out` - Where to print results
+pub async fn run(command: Command, config: Config, out: &mut dyn Write) -> Result<()> {
+    let db = open_database(&config).await?;
+
+    if let Command::Db(DbCommand::Migrate) = command {
+        return migrate(&db, out).await;
+    }
+    db.init_schema().await?;
+
+    match command {
+        Command::Serve => bail!("`serve` is not an admin command"),
+        Command::Devices(command) => devices(&db, &config, command, out).await,
+        Command::Transfers(command) => transfers(&db, &config, command, out).await,
+        Command::Db(command) => database(&db, command, out).await,
+        Command::Audit(command) => audit_log(&db, command, out).await,
+        Command::Keys(KeysCommand::ShowFingerprint) => {
+            let certificate = ServerCertificate::load_or_generate(config.data_dir())?;
+            writeln!(out, "{}", certificate.fingerprint())?;
+            Ok(())
+        }
+        Command::Pair(args) => {
+            if config.server.port == 0 {
+                bail!("`pair` needs a fixed server.port; the running server picks port 0 itself");
+            }
+            let certificate = ServerCertificate::load_or_generate(config.data_dir())?;
+            let addrs = reachable_addrs(config.http_addr());
+            let state = AppState::with_config(db, certificate, config);
+            state.discovery.set_local_addrs(addrs).await;
+            pair(&state, args, out).await
+        }
+    }
+}
+
+/// `db migrate`
+pub async fn migrate(db: &Database, out: &mut dyn Write) -> Result<()> {
+    let applied = db.migrate().await?;
+    let version = db.schema_version().await?;
+    if applied.is_empty() {
+        writeln!(out, "Schema is up to date (version {})", version)?;
+    } else {
+        for version in &applied {
+            writeln!(out, "Applied migration {}", version)?;
+        }
+        writeln!(out, "Schema is at version {}", version)?;
+    }
+    Ok(())
+}
+
+/// `devices list|rm|rename`
+pub async fn devices(
+    db: &Database,
+    config: &Config,
+    command: DevicesCommand,
+    out: &mut dyn Write,
+) -> Result<()> {
+    match command {
+        DevicesCommand::List { json } => {
+            let devices = db.get_devices().await?;
+            if json {
+                serde_json::to_writer_pretty(&mut *out, &devices)?;
+                writeln!(out)?;
+                return Ok(());
+            }
+
+            let tracker = PresenceTracker::new(config.presence_thresholds());
+            let now = Utc::now();
+            let rows = devices
+                .iter()
+                .map(|device| {
+                    let presence = tracker.presence(device.last_seen, false, now);
+                    vec![
+                        device.id.clone(),
+                        device
+                            .nickname
+                            .clone()
+                            .unwrap_or_else(|| device.name.clone()),
+                        device.device_type.to_string(),
+                        device.platform.clone().unwrap_or_default(),
+                        presence.to_string(),
+                        device.last_seen.map(format_time).unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            write_table(
+                out,
+                &["ID", "NAME", "TYPE", "PLATFORM", "PRESENCE", "LAST SEEN"],
+                rows,
+            )
+        }
+        DevicesCommand::Rm { id } => {
+            if !db.delete_device(&id).await? {
+                bail!("Device {} not found", id);
+            }
+            audit::record(db, AuditKind::DeviceRevoked.event(&Actor::Cli).device(&id)).await;
+            writeln!(out, "Removed device {}", id)?;
+            Ok(())
+        }
+        DevicesCommand::Rename { id, name } => {
+            let update = validate_device_update(UpdateDeviceRequest {
+                name: Some(name),
+                ..Default::default()
+            })?;
+            let device = db
+                .update_device(&id, &update)
+                .await?
+                .with_context(|| format!("Device {} not found", id))?;
+            audit::record(db, audit::device_updated(&Actor::Cli, &id, &update)).await;
+            writeln!(out, "Renamed device {} to {}", device.id, device.name)?;
+            Ok(())
+        }
+    }
+}
+
+/// `transfers list|purge`
+pub async fn transfers(
+    db: &Database,
+    config: &Config,
+    command: TransfersCommand,
+    out: &mut dyn Write,
+) -> Result<()> {
+    match command {
+        TransfersCommand::List {
+            device,
+            status,
+            limit,
+            json,
+        } => {
+            if let Some(status) = &status {
+                check_status(status)?;
+            }
+            let transfers = db
+                .list_transfers(&TransferQuery {
+                    device_id: device,
+                    status,
+                    order: SortOrder::Desc,
+                    limit,
+                    ..Default::default()
+                })
+                .await?;
+            if json {
+                serde_json::to_writer_pretty(&mut *out, &transfers)?;
+                writeln!(out)?;
+                return Ok(());
+            }
+
+            let rows = transfers
+                .into_iter()
+                .map(|transfer| {
+                    vec![
+                        transfer.id,
+                        transfer.device_id,
+                        transfer.file_name,
+                        transfer.file_size.to_string(),
+                        transfer.status,
+                        format_time(transfer.created_at),
+                    ]
+                })
+                .collect();
+            write_table(
+                out,
+                &["ID", "DEVICE", "FILE", "BYTES", "STATUS", "CREATED"],
+                rows,
+            )
+        }
+        TransfersCommand::Purge {
+            older_than_days,
+            statuses,
+        } => {
+            for status in &statuses {
+                check_status(status)?;
+            }
+            let before = Utc::now() - chrono::Duration::days(older_than_days as i64);
+            let ids = db.purge_transfers(&statuses, before).await?;
+
+            for id in &ids {
+                let dir = transfer_dir(&config.storage.upload_dir, id);
+                if dir.exists() {
+                    std::fs::remove_dir_all(&dir)
+                        .with_context(|| format!("Failed to remove {}", dir.display()))?;
+                }
+            }
+            writeln!(out, "Purged {} transfers", ids.len())?;
+            Ok(())
+        }
+    }
+}
+
+/// `db vacuum|backup`
+pub async fn database(db: &Database, command: DbCommand, out: &mut dyn Write) -> Result<()> {
+    match command {
+        DbCommand::Migrate => migrate(db, out).await,
+        DbCommand::Vacuum => {
+            db.vacuum().await?;
+            writeln!(out, "Database vacuumed")?;
+            Ok(())
+        }
+        DbCommand::Backup { path } => {
+            if path.exists() {
+                bail!("{} already exists", path.display());
+            }
+            db.backup_to(&path).await?;
+            writeln!(out, "Database backed up to {}", path.display())?;
+            Ok(())
+        }
+    }
+}
+
+/// `audit list|export|verify`
+pub async fn audit_log(db: &Database, command: AuditCommand, out: &mut dyn Write) -> Result<()> {
+    match command {
+        AuditCommand::List {
+            kind,
+            device,
+            limit,
+            json,
+        } => {
+            if let Some(kind) = &kind {
+                check_kind(kind)?;
+            }
+            let events = db
+                .list_audit_events(&AuditQuery {
+                    kind,
+                    device_id: device,
+                    order: SortOrder::Desc,
+                    limit,
+                    ..Default::default()
+                })
+                .await?;
+            if json {
+                serde_json::to_writer_pretty(&mut *out, &events)?;
+                writeln!(out)?;
+                return Ok(());
+            }
+
+            let rows = events
+                .into_iter()
+                .map(|event| {
+                    vec![
+                        event.seq.to_string(),
+                        format_time(event.occurred_at),
+                        event.kind,
+                        event.actor,
+                        event.device_id.unwrap_or_default(),
+                        event.transfer_id.unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            write_table(
+                out,
+                &["SEQ", "TIME", "KIND", "ACTOR", "DEVICE", "TRANSFER"],
+                rows,
+            )
+        }
+        AuditCommand::Export {
+            output,
+            kind,
+            device,
+        } => {
+            if let Some(kind) = &kind {
+                check_kind(kind)?;
+            }
+            let query = AuditQuery {
+                kind,
+                device_id: device,
+                ..Default::default()
+            };
+            match output {
+                None => {
+                    audit::export(db, &query, out).await?;
+                }
+                Some(path) => {
+                    let file = std::fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(&path)
+                        .with_context(|| format!("Failed to create {}", path.display()))?;
+                    let mut writer = std::io::BufWriter::new(file);
+                    let count = audit::export(db, &query, &mut writer).await?;
+                    writer.flush()?;
+                    writeln!(out, "Exported {} events to {}", count, path.display())?;
+                }
+            }
+            Ok(())
+        }
+        AuditCommand::Verify => {
+            let status = db.verify_audit_chain().await?;
+            if let Some(seq) = status.first_invalid {
+                bail!(
+                    "Audit log chain is broken at event {} ({} events checked)",
+                    seq,
+                    status.events
+                );
+            }
+            writeln!(out, "Audit log intact: {} events", status.events)?;
+            if let Some(head) = status.head {
+                writeln!(out, "Head: {}", head)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// `pair`: register a device and print its pairing QR code
+pub async fn pair(state: &AppState, args: PairArgs, out: &mut dyn Write) -> Result<()> {
+    let options = args.qr_options();
+    let (device, pairing) = create_pairing(
+        state,
+        PairRequest {
+            device_name: args.name,
+            device_type: args.device_type,
+            platform: None,
+            os_version: None,
+            app_version: None,
+        },
+        &Actor::Cli,
+    )
+    .await?;
+    let uri = pairing.to_uri();
+
+    writeln!(out, "{}", generate_qr_terminal(&uri, options)?)?;
+    writeln!(out, "Scan with the BridgeX app to pair \"{}\"", device.name)?;
+    writeln!(out, "Device ID:   {}", device.id)?;
+    writeln!(out, "Fingerprint: {}", pairing.server_fingerprint)?;
+    writeln!(out, "Expires:     {}", format_time(pairing.expires_at))?;
+    writeln!(out, "URI:         {}", uri)?;
+    if pairing.addresses.iter().all(|addr| addr.ip().is_loopback()) {
+        writeln!(
+            out,
+            "Warning: the server only listens on loopback; use --host 0.0.0.0 so devices can reach it"
+        )?;
+    }
+    Ok(())
+}
+
+fn check_status(status: &str) -> Result<()> {
+    if !TRANSFER_STATUSES.contains(&status) {
+        bail!(
+            "Unknown status {:?}, expected one of {}",
+            status,
+            TRANSFER_STATUSES.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn check_kind(kind: &str) -> Result<()> {
+    if !AUDIT_KINDS.iter().any(|k| k.as_str() == kind) {
+        let kinds: Vec<&str> = AUDIT_KINDS.iter().map(|k| k.as_str()).collect();
+        bail!(
+            "Unknown event kind {:?}, expected one of {}",
+            kind,
+            kinds.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Print rows as left-aligned columns
+fn write_table(out: &mut dyn Write, header: &[&str], rows: Vec<Vec<String>>) -> Result<()> {
+    if rows.is_empty() {
+        writeln!(out, "Nothing to show")?;
+        return Ok(());
+    }
+
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        writeln!(out, "{}", line.join("  ").trim_end())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,375 @@
+//! Command-line interface of `bridgex-server`
+//!
+//! Without a subcommand the server starts, as before. The admin subcommands
+//! open the same database and certificate directly, so a headless install
+//! can be managed over SSH while the server is stopped or running.
+
+pub mod admin;
+
+use clap::{Args, Parser, Subcommand};
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use crate::config::{AdminPort, Config, ConfigError, LogFormat, TlsMode};
+use crate::db::models::DeviceType;
+use crate::qr::TerminalQrOptions;
+
+/// BridgeX backend server
+#[derive(Debug, Parser)]
+#[command(name = "bridgex-server", version, about)]
+pub struct Cli {
+    /// Config file [default: ./bridgex.toml if present]
+    #[arg(long, env = "BRIDGEX_CONFIG", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Address to listen on
+    #[arg(long, global = true)]
+    pub host: Option<IpAddr>,
+
+    /// Device API port
+    #[arg(long, global = true)]
+    pub port: Option<u16>,
+
+    /// Admin API port, on loopback, or off
+    #[arg(long, global = true)]
+    pub admin_port: Option<AdminPort>,
+
+    /// Unix socket serving the admin API
+    #[arg(long, global = true)]
+    pub admin_socket: Option<PathBuf>,
+
+    /// File to write the bound ports to once listening
+    #[arg(long, global = true)]
+    pub port_file: Option<PathBuf>,
+
+    /// Direct TCP transfer channel port
+    #[arg(long, global = true)]
+    pub tcp_port: Option<u16>,
+
+    /// QUIC transfer endpoint port
+    #[arg(long, global = true)]
+    pub quic_port: Option<u16>,
+
+    /// Serve the HTTP API over TLS: auto (unless on loopback), on or off
+    #[arg(long, global = true)]
+    pub tls: Option<TlsMode>,
+
+    /// SQLite database file
+    #[arg(long, global = true)]
+    pub db_path: Option<PathBuf>,
+
+    /// Directory for received files
+    #[arg(long, global = true)]
+    pub upload_dir: Option<PathBuf>,
+
+    /// Print a pairing QR code once the server is listening
+    #[arg(long, global = true)]
+    pub pairing_qr: bool,
+
+    /// Log line format: text or json
+    #[arg(long, global = true)]
+    pub log_format: Option<LogFormat>,
+
+    /// Print the effective configuration as TOML and exit
+    #[arg(long)]
+    pub print_config: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Subcommands
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the server (the default)
+    Serve,
+
+    /// Manage paired devices
+    #[command(subcommand)]
+    Devices(DevicesCommand),
+
+    /// Inspect and clean up transfer history
+    #[command(subcommand)]
+    Transfers(TransfersCommand),
+
+    /// Pair a new device by printing a QR code to the terminal
+    Pair(PairArgs),
+
+    /// Database maintenance
+    #[command(subcommand)]
+    Db(DbCommand),
+
+    /// Server identity
+    #[command(subcommand)]
+    Keys(KeysCommand),
+
+    /// Query, export and verify the audit log
+    #[command(subcommand)]
+    Audit(AuditCommand),
+}
+
+/// `devices` subcommands
+#[derive(Debug, Subcommand)]
+pub enum DevicesCommand {
+    /// List paired devices
+    List {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Unpair a device
+    Rm { id: String },
+
+    /// Rename a device
+    Rename { id: String, name: String },
+}
+
+/// `transfers` subcommands
+#[derive(Debug, Subcommand)]
+pub enum TransfersCommand {
+    /// List transfers, newest first
+    List {
+        /// Only transfers of this device
+        #[arg(long)]
+        device: Option<String>,
+
+        /// Only transfers in this status
+        #[arg(long)]
+        status: Option<String>,
+
+        /// Maximum number of transfers to show
+        #[arg(long, default_value_t = 50)]
+        limit: u32,
+
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Delete old transfers and their received files
+    Purge {
+        /// Only transfers created more than this many days ago
+        #[arg(long, default_value_t = 30)]
+        older_than_days: u32,
+
+        /// Statuses to purge (repeatable)
+        #[arg(long = "status", default_values_t = ["completed".to_string(), "failed".to_string()])]
+        statuses: Vec<String>,
+    },
+}
+
+/// Name given to devices paired from the terminal
+pub const DEFAULT_PAIR_NAME: &str = "New device";
+
+/// Arguments of `pair`
+#[derive(Debug, Args)]
+pub struct PairArgs {
+    /// Name of the device being paired
+    #[arg(long, default_value = DEFAULT_PAIR_NAME)]
+    pub name: String,
+
+    /// Kind of the device being paired
+    #[arg(long = "type", default_value_t = DeviceType::Mobile)]
+    pub device_type: DeviceType,
+
+    /// Draw the QR code for a light terminal background
+    #[arg(long)]
+    pub invert: bool,
+
+    /// Border around the QR code, in modules
+    #[arg(long, default_value_t = TerminalQrOptions::default().quiet_zone)]
+    pub quiet_zone: u32,
+}
+
+impl PairArgs {
+    /// Arguments for the QR code printed at startup
+    pub fn startup(config: &Config) -> Self {
+        Self {
+            name: DEFAULT_PAIR_NAME.to_string(),
+            device_type: DeviceType::Mobile,
+            invert: config.pairing.qr_invert,
+            quiet_zone: TerminalQrOptions::default().quiet_zone,
+        }
+    }
+
+    /// Rendering options of the QR code
+    pub fn qr_options(&self) -> TerminalQrOptions {
+        TerminalQrOptions {
+            invert: self.invert,
+            quiet_zone: self.quiet_zone,
+        }
+    }
+}
+
+/// `db` subcommands
+#[derive(Debug, Subcommand)]
+pub enum DbCommand {
+    /// Apply pending schema migrations
+    Migrate,
+
+    /// Reclaim unused space in the database file
+    Vacuum,
+
+    /// Write a consistent copy of the database
+    Backup {
+        /// Destination file, which must not exist
+        path: PathBuf,
+    },
+}
+
+/// `keys` subcommands
+#[derive(Debug, Subcommand)]
+pub enum KeysCommand {
+    /// Print the certificate fingerprint devices pin during pairing
+    ShowFingerprint,
+}
+
+/// `audit` subcommands
+#[derive(Debug, Subcommand)]
+pub enum AuditCommand {
+    /// List audit events, newest first
+    List {
+        /// Only events of this kind, e.g. device.paired
+        #[arg(long)]
+        kind: Option<String>,
+
+        /// Only events about this device
+        #[arg(long)]
+        device: Option<String>,
+
+        /// Maximum number of events to show
+        #[arg(long, default_value_t = 50)]
+        limit: u32,
+
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Write the audit log as JSON lines, oldest first
+    Export {
+        /// Destination file, which must not exist [default: stdout]
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Only events of this kind
+        #[arg(long)]
+        kind: Option<String>,
+
+        /// Only events about this device
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Check the hash chain of the audit log
+    Verify,
+}
+
+impl Cli {
+    /// Load the layered configuration and validate it
+    pub fn load_config(&self) -> Result<Config, ConfigError> {
+        let mut config = Config::load(self.config.as_deref())?;
+        self.apply(&mut config);
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Apply command-line flags, which take precedence over the file and environment
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(host) = self.host {
+            config.server.host = host;
+        }
+        if let Some(port) = self.port {
+            config.server.port = port;
+        }
+        if let Some(port) = self.admin_port {
+            config.server.admin_port = port;
+        }
+        if let Some(path) = &self.admin_socket {
+            config.server.admin_socket = Some(path.clone());
+        }
+        if let Some(path) = &self.port_file {
+            config.server.port_file = Some(path.clone());
+        }
+        if let Some(port) = self.tcp_port {
+            config.server.tcp_port = port;
+        }
+        if let Some(port) = self.quic_port {
+            config.server.quic_port = port;
+        }
+        if let Some(tls) = self.tls {
+            config.server.tls = tls;
+        }
+        if let Some(path) = &self.db_path {
+            config.database.path = path.clone();
+        }
+        if let Some(dir) = &self.upload_dir {
+            config.storage.upload_dir = dir.clone();
+        }
+        if self.pairing_qr {
+            config.pairing.qr_on_startup = true;
+        }
+        if let Some(format) = self.log_format {
+            config.logging.format = format;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subcommands() {
+        let cli = Cli::try_parse_from(["bridgex-server"]).unwrap();
+        assert!(cli.command.is_none());
+
+        let cli =
+            Cli::try_parse_from(["bridgex-server", "devices", "rename", "abc", "Phone"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Devices(DevicesCommand::Rename { ref id, ref name }))
+                if id == "abc" && name == "Phone"
+        ));
+
+        let cli = Cli::try_parse_from(["bridgex-server", "transfers", "purge"]).unwrap();
+        match cli.command {
+            Some(Command::Transfers(TransfersCommand::Purge {
+                older_than_days,
+                statuses,
+            })) => {
+                assert_eq!(older_than_days, 30);
+                assert_eq!(statuses, vec!["completed", "failed"]);
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+
+        assert!(Cli::try_parse_from(["bridgex-server", "pair", "--type", "toaster"]).is_err());
+
+        let cli = Cli::try_parse_from(["bridgex-server", "audit", "export", "-o", "audit.ndjson"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Audit(AuditCommand::Export { output: Some(ref path), .. }))
+                if path == std::path::Path::new("audit.ndjson")
+        ));
+    }
+
+    #[test]
+    fn test_global_flags_after_subcommand() {
+        let cli = Cli::try_parse_from([
+            "bridgex-server",
+            "serve",
+            "--port",
+            "9000",
+            "--log-format",
+            "json",
+        ])
+        .unwrap();
+        let mut config = Config::default();
+        cli.apply(&mut config);
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.logging.format, LogFormat::Json);
+    }
+}
@@ -0,0 +1,1080 @@
+//! Server configuration
+//!
+//! Settings are layered: built-in defaults, then an optional TOML file, then
+//! `BRIDGEX_*` environment variables, then command-line flags. The result is
+//! validated once at startup and shared with handlers through
+//! [`AppState`](crate::AppState), so nothing reads the environment later.
+//!
+//! ```toml
+//! [server]
+//! host = "127.0.0.1"
+//! port = 8080
+//!
+//! [pairing]
+//! expiry_secs = 300
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::server::presence::PresenceThresholds;
+
+/// Config file read when none is given explicitly, if it exists
+pub const DEFAULT_CONFIG_FILE: &str = "bridgex.toml";
+
+/// Errors that stop the configuration from loading
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to read config file {path}: {source}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("invalid config file {path}: {message}")]
+    Parse { path: PathBuf, message: String },
+
+    #[error("invalid value {value:?} for {var}: {reason}")]
+    Env {
+        var: &'static str,
+        value: String,
+        reason: String,
+    },
+
+    #[error("invalid setting `{field}`: {reason}")]
+    Invalid { field: &'static str, reason: String },
+}
+
+/// Complete server configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub storage: StorageConfig,
+    pub pairing: PairingConfig,
+    pub presence: PresenceConfig,
+    pub discovery: DiscoveryConfig,
+    pub cors: CorsConfig,
+    pub logging: LoggingConfig,
+    pub rate_limit: RateLimitConfig,
+}
+
+/// Listening addresses
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: IpAddr,
+    /// Device API port, on `host`
+    pub port: u16,
+    /// Admin API port, always on loopback, or `"off"` to serve the admin
+    /// API on the Unix socket only
+    pub admin_port: AdminPort,
+    /// Unix socket also serving the admin API, by default
+    /// `bridgex/admin.sock` in the user runtime directory
+    pub admin_socket: Option<PathBuf>,
+    /// File the bound ports are written to once listening
+    pub port_file: Option<PathBuf>,
+    /// Direct TCP transfer channel port
+    pub tcp_port: u16,
+    /// QUIC transfer endpoint port (UDP)
+    pub quic_port: u16,
+    /// How long active uploads may finish after a shutdown signal
+    pub shutdown_grace_secs: u64,
+    /// Serve the HTTP API over TLS
+    pub tls: TlsMode,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8080,
+            admin_port: AdminPort::Port(8083),
+            admin_socket: None,
+            port_file: None,
+            tcp_port: 8081,
+            quic_port: 8082,
+            shutdown_grace_secs: 30,
+            tls: TlsMode::Auto,
+        }
+    }
+}
+
+/// Port of the admin API on loopback
+///
+/// The loopback port is open to every local user, so it can be turned off
+/// where the Unix socket is enough.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "AdminPortValue", into = "AdminPortValue")]
+pub enum AdminPort {
+    Port(u16),
+    Off,
+}
+
+impl AdminPort {
+    /// The port, unless turned off
+    pub fn port(self) -> Option<u16> {
+        match self {
+            AdminPort::Port(port) => Some(port),
+            AdminPort::Off => None,
+        }
+    }
+}
+
+impl fmt::Display for AdminPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminPort::Port(port) => write!(f, "{}", port),
+            AdminPort::Off => f.write_str("off"),
+        }
+    }
+}
+
+impl std::str::FromStr for AdminPort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(AdminPort::Off),
+            _ => s
+                .parse()
+                .map(AdminPort::Port)
+                .map_err(|_| format!("invalid admin port '{}', expected a port or off", s)),
+        }
+    }
+}
+
+/// `admin_port` as written in the config file: a number or `"off"`
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum AdminPortValue {
+    Port(u16),
+    Name(String),
+}
+
+impl TryFrom<AdminPortValue> for AdminPort {
+    type Error = String;
+
+    fn try_from(value: AdminPortValue) -> Result<Self, Self::Error> {
+        match value {
+            AdminPortValue::Port(port) => Ok(AdminPort::Port(port)),
+            AdminPortValue::Name(name) => name.parse(),
+        }
+    }
+}
+
+impl From<AdminPort> for AdminPortValue {
+    fn from(port: AdminPort) -> Self {
+        match port {
+            AdminPort::Port(port) => AdminPortValue::Port(port),
+            AdminPort::Off => AdminPortValue::Name("off".to_string()),
+        }
+    }
+}
+
+/// Whether the HTTP API is served over TLS
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsMode {
+    /// TLS unless bound to a loopback address
+    #[default]
+    Auto,
+    On,
+    Off,
+}
+
+impl fmt::Display for TlsMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TlsMode::Auto => "auto",
+            TlsMode::On => "on",
+            TlsMode::Off => "off",
+        })
+    }
+}
+
+impl std::str::FromStr for TlsMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(TlsMode::Auto),
+            "on" => Ok(TlsMode::On),
+            "off" => Ok(TlsMode::Off),
+            _ => Err(format!(
+                "unknown TLS mode '{}', expected auto, on or off",
+                s
+            )),
+        }
+    }
+}
+
+/// SQLite database location
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub path: PathBuf,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("./data/bridge.db"),
+        }
+    }
+}
+
+/// Where received files are written
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Directory for in-progress and assembled uploads
+    pub upload_dir: PathBuf,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            upload_dir: PathBuf::from("./data/uploads"),
+        }
+    }
+}
+
+/// Pairing QR codes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PairingConfig {
+    /// How long a pairing code stays valid
+    pub expiry_secs: u64,
+    /// Print a fresh pairing QR code to stdout when the server starts
+    pub qr_on_startup: bool,
+    /// Draw the terminal QR code for light backgrounds
+    pub qr_invert: bool,
+}
+
+impl Default for PairingConfig {
+    fn default() -> Self {
+        Self {
+            expiry_secs: 300,
+            qr_on_startup: false,
+            qr_invert: false,
+        }
+    }
+}
+
+/// Presence thresholds, in seconds since the last heartbeat
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PresenceConfig {
+    pub online_secs: u64,
+    pub idle_secs: u64,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        let thresholds = PresenceThresholds::default();
+        Self {
+            online_secs: thresholds.online.num_seconds() as u64,
+            idle_secs: thresholds.idle.num_seconds() as u64,
+        }
+    }
+}
+
+/// mDNS advertisement on the LAN
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    pub enabled: bool,
+    /// Name shown to peers discovering this server
+    pub device_name: String,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            device_name: "BridgeX".to_string(),
+        }
+    }
+}
+
+/// Cross-origin access to the HTTP API
+///
+/// The desktop app's webview origins are always allowed, see
+/// [`cors`](crate::server::cors).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Additional web origins such as `http://localhost:3000`
+    pub allowed_origins: Vec<String>,
+    /// Grant Private Network Access preflights from allowed origins
+    pub allow_private_network: bool,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allow_private_network: true,
+        }
+    }
+}
+
+/// Log line format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        })
+    }
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format '{}', expected text or json", s)),
+        }
+    }
+}
+
+/// Server logs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Filter directives like `bridgex_backend=debug`; `RUST_LOG` takes precedence
+    pub level: String,
+    pub format: LogFormat,
+    /// Also write daily-rotated log files to `dir`
+    pub file: bool,
+    /// Directory of the log files; `<data dir>/logs` when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+    /// Rotated log files to keep
+    pub max_files: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "bridgex_server=info,bridgex_backend=info,tower_http=info".to_string(),
+            format: LogFormat::Text,
+            file: false,
+            dir: None,
+            max_files: 7,
+        }
+    }
+}
+
+/// Token bucket of a group of routes: `burst` requests at once, refilled at
+/// `per_minute`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateBudget {
+    pub per_minute: u32,
+    pub burst: u32,
+}
+
+/// Request rate limits and lockout after failed authentications
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Leave clients on the loopback interface, such as the desktop app,
+    /// unlimited except for pairing
+    pub exempt_loopback: bool,
+    /// `POST /api/v1/pair`, per client IP, loopback included
+    pub pair: RateBudget,
+    /// Chunk uploads, per client IP and per signing device
+    pub upload: RateBudget,
+    /// Every other route, per client IP and per signing device
+    pub api: RateBudget,
+    /// Failed authentications from one IP within `failure_window_secs`
+    /// before it is locked out
+    pub max_failures: u32,
+    pub failure_window_secs: u64,
+    pub lockout_secs: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            exempt_loopback: true,
+            pair: RateBudget {
+                per_minute: 10,
+                burst: 5,
+            },
+            upload: RateBudget {
+                per_minute: 6000,
+                burst: 300,
+            },
+            api: RateBudget {
+                per_minute: 600,
+                burst: 60,
+            },
+            max_failures: 5,
+            failure_window_secs: 600,
+            lockout_secs: 900,
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration file and apply environment overrides
+    ///
+    /// # Arguments
+    /// * `path` - Config file to read; if `None`, [`DEFAULT_CONFIG_FILE`] is
+    ///   read when present and defaults are used otherwise
+    ///
+    /// # Returns
+    /// The configuration, not yet validated so that command-line flags can
+    /// still be applied
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut config = match path {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => Self::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    /// Read a TOML config file; missing settings keep their defaults
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&text).map_err(|e| {
+            let message = match e.span() {
+                Some(span) => format!(
+                    "line {}: {}",
+                    text[..span.start].matches('\n').count() + 1,
+                    e.message()
+                ),
+                None => e.message().to_string(),
+            };
+            ConfigError::Parse {
+                path: path.to_path_buf(),
+                message,
+            }
+        })
+    }
+
+    /// Override settings from `BRIDGEX_*` variables
+    ///
+    /// # Arguments
+    /// * `var` - Looks up a variable, e.g. `|name| std::env::var(name).ok()`
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        let get = |name: &'static str| var(name).map(|value| (name, value));
+
+        if let Some((name, value)) = get("BRIDGEX_HOST") {
+            self.server.host = parse_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_PORT") {
+            self.server.port = parse_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_ADMIN_PORT") {
+            self.server.admin_port = parse_env(name, value)?;
+        }
+        if let Some((_, value)) = get("BRIDGEX_ADMIN_SOCKET") {
+            self.server.admin_socket = Some(PathBuf::from(value));
+        }
+        if let Some((_, value)) = get("BRIDGEX_PORT_FILE") {
+            self.server.port_file = Some(PathBuf::from(value));
+        }
+        if let Some((name, value)) = get("BRIDGEX_TCP_PORT") {
+            self.server.tcp_port = parse_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_QUIC_PORT") {
+            self.server.quic_port = parse_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_SHUTDOWN_GRACE_SECS") {
+            self.server.shutdown_grace_secs = parse_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_TLS") {
+            self.server.tls = parse_env(name, value)?;
+        }
+        if let Some((_, value)) = get("BRIDGEX_DB_PATH") {
+            self.database.path = PathBuf::from(value);
+        }
+        if let Some((_, value)) = get("BRIDGEX_UPLOAD_DIR") {
+            self.storage.upload_dir = PathBuf::from(value);
+        }
+        if let Some((name, value)) = get("BRIDGEX_PAIRING_EXPIRY_SECS") {
+            self.pairing.expiry_secs = parse_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_PRESENCE_ONLINE_SECS") {
+            self.presence.online_secs = parse_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_PRESENCE_IDLE_SECS") {
+            self.presence.idle_secs = parse_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_PAIRING_QR") {
+            self.pairing.qr_on_startup = parse_bool_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_PAIRING_QR_INVERT") {
+            self.pairing.qr_invert = parse_bool_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_AUTO_DISCOVERY") {
+            self.discovery.enabled = parse_bool_env(name, value)?;
+        }
+        if let Some((_, value)) = get("BRIDGEX_DEVICE_NAME") {
+            self.discovery.device_name = value;
+        }
+        if let Some((_, value)) = get("BRIDGEX_LOG_LEVEL") {
+            self.logging.level = value;
+        }
+        if let Some((name, value)) = get("BRIDGEX_LOG_FORMAT") {
+            self.logging.format = parse_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_LOG_FILE") {
+            // Earlier releases took the path of a log file here
+            match parse_bool_env(name, value.clone()) {
+                Ok(file) => self.logging.file = file,
+                Err(_) => {
+                    self.logging.file = true;
+                    self.logging.dir = Some(legacy_log_dir(PathBuf::from(value)));
+                }
+            }
+        }
+        if let Some((name, value)) = get("BRIDGEX_LOG_MAX_FILES") {
+            self.logging.max_files = parse_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_RATE_LIMIT") {
+            self.rate_limit.enabled = parse_bool_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_MAX_AUTH_FAILURES") {
+            self.rate_limit.max_failures = parse_env(name, value)?;
+        }
+        if let Some((name, value)) = get("BRIDGEX_LOCKOUT_SECS") {
+            self.rate_limit.lockout_secs = parse_env(name, value)?;
+        }
+        if let Some((_, value)) = get("BRIDGEX_CORS_ORIGINS") {
+            self.cors.allowed_origins = value
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        if let Some((name, value)) = get("BRIDGEX_CORS_PRIVATE_NETWORK") {
+            self.cors.allow_private_network = parse_bool_env(name, value)?;
+        }
+        Ok(())
+    }
+
+    /// Check that the settings are usable together
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        // Port 0 lets the OS pick a free port, so zeros never collide
+        let collides = |a: u16, b: u16| a != 0 && a == b;
+        if collides(self.server.tcp_port, self.server.port) {
+            return Err(invalid("server.tcp_port", "must differ from the HTTP port"));
+        }
+        if let Some(admin_port) = self.server.admin_port.port() {
+            if collides(admin_port, self.server.port) || collides(admin_port, self.server.tcp_port) {
+                return Err(invalid(
+                    "server.admin_port",
+                    "must differ from the HTTP and TCP ports",
+                ));
+            }
+        }
+        if matches!(&self.server.admin_socket, Some(path) if path.as_os_str().is_empty()) {
+            return Err(invalid("server.admin_socket", "must not be empty"));
+        }
+        if matches!(&self.server.port_file, Some(path) if path.as_os_str().is_empty()) {
+            return Err(invalid("server.port_file", "must not be empty"));
+        }
+        if self.server.shutdown_grace_secs > 3600 {
+            return Err(invalid(
+                "server.shutdown_grace_secs",
+                "must be at most 3600",
+            ));
+        }
+
+        if self.database.path.as_os_str().is_empty() {
+            return Err(invalid("database.path", "must not be empty"));
+        }
+        if self.storage.upload_dir.as_os_str().is_empty() {
+            return Err(invalid("storage.upload_dir", "must not be empty"));
+        }
+
+        if !(30..=86_400).contains(&self.pairing.expiry_secs) {
+            return Err(invalid(
+                "pairing.expiry_secs",
+                "must be between 30 and 86400",
+            ));
+        }
+
+        if self.presence.online_secs == 0 {
+            return Err(invalid("presence.online_secs", "must be positive"));
+        }
+        if self.presence.idle_secs < self.presence.online_secs {
+            return Err(invalid(
+                "presence.idle_secs",
+                "must not be less than presence.online_secs",
+            ));
+        }
+
+        if self.discovery.device_name.trim().is_empty() {
+            return Err(invalid("discovery.device_name", "must not be empty"));
+        }
+
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.logging.level) {
+            return Err(ConfigError::Invalid {
+                field: "logging.level",
+                reason: e.to_string(),
+            });
+        }
+        if self.logging.max_files == 0 {
+            return Err(invalid("logging.max_files", "must be positive"));
+        }
+
+        let budgets = [
+            ("rate_limit.pair", self.rate_limit.pair),
+            ("rate_limit.upload", self.rate_limit.upload),
+            ("rate_limit.api", self.rate_limit.api),
+        ];
+        for (field, budget) in budgets {
+            if budget.per_minute == 0 || budget.burst == 0 {
+                return Err(invalid(field, "per_minute and burst must be positive"));
+            }
+        }
+        if self.rate_limit.max_failures == 0 {
+            return Err(invalid("rate_limit.max_failures", "must be positive"));
+        }
+        if self.rate_limit.failure_window_secs == 0 {
+            return Err(invalid(
+                "rate_limit.failure_window_secs",
+                "must be positive",
+            ));
+        }
+        if self.rate_limit.lockout_secs == 0 {
+            return Err(invalid("rate_limit.lockout_secs", "must be positive"));
+        }
+
+        for origin in &self.cors.allowed_origins {
+            if origin == "*" {
+                return Err(invalid(
+                    "cors.allowed_origins",
+                    "\"*\" would let any website call the API; list the origins instead",
+                ));
+            }
+            if !is_origin(origin) {
+                return Err(ConfigError::Invalid {
+                    field: "cors.allowed_origins",
+                    reason: format!("{:?} is not an origin like http://host:port", origin),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Render the configuration as TOML
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("config is always serializable")
+    }
+
+    /// HTTP API address
+    pub fn http_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.server.host, self.server.port)
+    }
+
+    /// Admin API address, on the loopback address of the `host` family;
+    /// `None` when the admin port is off
+    pub fn admin_addr(&self) -> Option<SocketAddr> {
+        let loopback = match self.server.host {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        };
+        let port = self.server.admin_port.port()?;
+        Some(SocketAddr::new(loopback, port))
+    }
+
+    /// Path of the admin API's Unix socket
+    ///
+    /// `server.admin_socket`, or `bridgex/admin.sock` under
+    /// `$XDG_RUNTIME_DIR`. Without a runtime directory it falls back to
+    /// `bridgex-<uid>/admin.sock` in the temporary directory, which all
+    /// users share.
+    pub fn admin_socket_path(&self) -> PathBuf {
+        if let Some(path) = &self.server.admin_socket {
+            return path.clone();
+        }
+        let dir = match std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+        {
+            Some(runtime_dir) => runtime_dir.join("bridgex"),
+            None => std::env::temp_dir().join(temp_socket_dir()),
+        };
+        dir.join("admin.sock")
+    }
+
+    /// Whether the HTTP API is served over TLS
+    pub fn tls_enabled(&self) -> bool {
+        match self.server.tls {
+            TlsMode::Auto => !self.server.host.is_loopback(),
+            TlsMode::On => true,
+            TlsMode::Off => false,
+        }
+    }
+
+    /// URL scheme of the HTTP API
+    pub fn http_scheme(&self) -> &'static str {
+        if self.tls_enabled() {
+            "https"
+        } else {
+            "http"
+        }
+    }
+
+    /// Direct TCP transfer channel address
+    pub fn tcp_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.server.host, self.server.tcp_port)
+    }
+
+    /// QUIC transfer endpoint address
+    pub fn quic_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.server.host, self.server.quic_port)
+    }
+
+    /// Drain period granted to active uploads on shutdown
+    pub fn shutdown_grace(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.server.shutdown_grace_secs)
+    }
+
+    /// Directory holding the database, also used for the server certificate
+    pub fn data_dir(&self) -> &Path {
+        self.database
+            .path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."))
+    }
+
+    /// Directory of rotated log files
+    pub fn log_dir(&self) -> PathBuf {
+        match &self.logging.dir {
+            Some(dir) => dir.clone(),
+            None => self.data_dir().join("logs"),
+        }
+    }
+
+    /// How long a pairing code stays valid
+    pub fn pairing_expiry(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.pairing.expiry_secs as i64)
+    }
+
+    /// How long an IP is refused after too many failed authentications
+    pub fn lockout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.rate_limit.lockout_secs)
+    }
+
+    /// Presence thresholds
+    pub fn presence_thresholds(&self) -> PresenceThresholds {
+        PresenceThresholds {
+            online: chrono::Duration::seconds(self.presence.online_secs as i64),
+            idle: chrono::Duration::seconds(self.presence.idle_secs as i64),
+        }
+    }
+}
+
+fn invalid(field: &'static str, reason: &str) -> ConfigError {
+    ConfigError::Invalid {
+        field,
+        reason: reason.to_string(),
+    }
+}
+
+fn parse_env<T>(var: &'static str, value: String) -> Result<T, ConfigError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value.parse().map_err(|e: T::Err| ConfigError::Env {
+        var,
+        reason: e.to_string(),
+        value,
+    })
+}
+
+/// Per-user name of the admin socket directory in the temporary directory
+fn temp_socket_dir() -> String {
+    #[cfg(unix)]
+    {
+        // SAFETY: geteuid has no preconditions and cannot fail
+        format!("bridgex-{}", unsafe { libc::geteuid() })
+    }
+    #[cfg(not(unix))]
+    {
+        "bridgex".to_string()
+    }
+}
+
+/// Log directory for a `BRIDGEX_LOG_FILE` path
+///
+/// A path ending in `.log`, like the `./logs/bridge.log` of earlier
+/// releases, names a file, and its directory is used.
+fn legacy_log_dir(path: PathBuf) -> PathBuf {
+    if path.extension().is_some_and(|extension| extension == "log") {
+        match path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::from("."),
+        }
+    } else {
+        path
+    }
+}
+
+fn parse_bool_env(var: &'static str, value: String) -> Result<bool, ConfigError> {
+    match value.as_str() {
+        "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        _ => Err(ConfigError::Env {
+            var,
+            value,
+            reason: "expected true, false, 1 or 0".to_string(),
+        }),
+    }
+}
+
+/// Whether `value` is a bare `scheme://host[:port]` origin
+fn is_origin(value: &str) -> bool {
+    match url::Url::parse(value) {
+        Ok(url) => {
+            matches!(url.scheme(), "http" | "https" | "tauri")
+                && url.host_str().is_some()
+                && matches!(url.path(), "/" | "")
+                && !value.ends_with('/')
+                && url.query().is_none()
+                && url.username().is_empty()
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_defaults_are_valid() {
+        let config = Config::default();
+        config.validate().unwrap();
+        assert_eq!(config.http_addr(), "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(config.data_dir(), Path::new("./data"));
+        assert!(!config.tls_enabled());
+        assert_eq!(config.pairing_expiry(), chrono::Duration::minutes(5));
+        assert_eq!(config.presence_thresholds(), PresenceThresholds::default());
+    }
+
+    #[test]
+    fn test_partial_file_keeps_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+            [server]
+            port = 9000
+
+            [cors]
+            allowed_origins = ["http://localhost:3000"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.server.tcp_port, 8081);
+        assert_eq!(config.storage, StorageConfig::default());
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("[server]\nprot = 9000\n").is_err());
+        assert!(toml::from_str::<Config>("[sever]\nport = 9000\n").is_err());
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let mut config = Config::default();
+        config
+            .apply_env(env(&[
+                ("BRIDGEX_HOST", "0.0.0.0"),
+                ("BRIDGEX_PORT", "9000"),
+                ("BRIDGEX_AUTO_DISCOVERY", "0"),
+                ("BRIDGEX_PAIRING_QR", "true"),
+                ("BRIDGEX_LOG_FORMAT", "json"),
+                ("BRIDGEX_LOCKOUT_SECS", "60"),
+                ("BRIDGEX_CORS_ORIGINS", "http://a.test, http://b.test:8080"),
+                ("BRIDGEX_ADMIN_SOCKET", "/run/user/1000/bx.sock"),
+            ]))
+            .unwrap();
+
+        assert_eq!(config.http_addr(), "0.0.0.0:9000".parse().unwrap());
+        assert_eq!(config.admin_addr(), Some("127.0.0.1:8083".parse().unwrap()));
+        assert_eq!(
+            config.admin_socket_path(),
+            PathBuf::from("/run/user/1000/bx.sock")
+        );
+        assert!(!config.discovery.enabled);
+        assert!(config.pairing.qr_on_startup);
+        assert_eq!(config.logging.format, LogFormat::Json);
+        assert!(config.tls_enabled());
+        assert_eq!(config.lockout(), std::time::Duration::from_secs(60));
+        assert_eq!(
+            config.cors.allowed_origins,
+            vec!["http://a.test", "http://b.test:8080"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_env_is_an_error() {
+        let mut config = Config::default();
+        let error = config
+            .apply_env(env(&[("BRIDGEX_PORT", "eighty")]))
+            .unwrap_err();
+        assert!(error.to_string().contains("BRIDGEX_PORT"));
+
+        let error = config
+            .apply_env(env(&[("BRIDGEX_AUTO_DISCOVERY", "maybe")]))
+            .unwrap_err();
+        assert!(matches!(error, ConfigError::Env { .. }));
+    }
+
+    #[test]
+    fn test_log_file_accepts_a_path() {
+        let mut config = Config::default();
+        assert_eq!(config.log_dir(), PathBuf::from("./data/logs"));
+
+        config
+            .apply_env(env(&[("BRIDGEX_LOG_FILE", "./logs/bridge.log")]))
+            .unwrap();
+        assert!(config.logging.file);
+        assert_eq!(config.log_dir(), PathBuf::from("./logs"));
+
+        config
+            .apply_env(env(&[("BRIDGEX_LOG_FILE", "/var/log/bridgex")]))
+            .unwrap();
+        assert_eq!(config.log_dir(), PathBuf::from("/var/log/bridgex"));
+
+        config
+            .apply_env(env(&[("BRIDGEX_LOG_FILE", "false")]))
+            .unwrap();
+        assert!(!config.logging.file);
+    }
+
+    #[test]
+    fn test_port_zero_is_valid() {
+        let mut config = Config::default();
+        config.server.port = 0;
+        config.server.admin_port = AdminPort::Port(0);
+        config.server.tcp_port = 0;
+        config.server.quic_port = 0;
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn test_validation() {
+        let invalid = |f: fn(&mut Config)| {
+            let mut config = Config::default();
+            f(&mut config);
+            match config.validate() {
+                Err(ConfigError::Invalid { field, .. }) => field,
+                other => panic!("expected a validation error, got {:?}", other),
+            }
+        };
+
+        assert_eq!(invalid(|c| c.server.tcp_port = 8080), "server.tcp_port");
+        assert_eq!(
+            invalid(|c| c.server.admin_port = AdminPort::Port(8080)),
+            "server.admin_port"
+        );
+        assert_eq!(
+            invalid(|c| c.server.admin_socket = Some(PathBuf::new())),
+            "server.admin_socket"
+        );
+        assert_eq!(
+            invalid(|c| c.server.port_file = Some(PathBuf::new())),
+            "server.port_file"
+        );
+        assert_eq!(
+            invalid(|c| c.logging.level = "bridgex_backend=loud".to_string()),
+            "logging.level"
+        );
+        assert_eq!(invalid(|c| c.logging.max_files = 0), "logging.max_files");
+        assert_eq!(
+            invalid(|c| c.server.shutdown_grace_secs = 86_400),
+            "server.shutdown_grace_secs"
+        );
+        assert_eq!(
+            invalid(|c| c.pairing.expiry_secs = 5),
+            "pairing.expiry_secs"
+        );
+        assert_eq!(invalid(|c| c.presence.idle_secs = 10), "presence.idle_secs");
+        assert_eq!(
+            invalid(|c| c.rate_limit.pair.per_minute = 0),
+            "rate_limit.pair"
+        );
+        assert_eq!(
+            invalid(|c| c.rate_limit.max_failures = 0),
+            "rate_limit.max_failures"
+        );
+        assert_eq!(
+            invalid(|c| c.discovery.device_name = " ".to_string()),
+            "discovery.device_name"
+        );
+        assert_eq!(
+            invalid(|c| c.cors.allowed_origins = vec!["localhost:3000".to_string()]),
+            "cors.allowed_origins"
+        );
+        assert_eq!(
+            invalid(|c| c.cors.allowed_origins = vec!["http://a.test/path".to_string()]),
+            "cors.allowed_origins"
+        );
+        assert_eq!(
+            invalid(|c| c.cors.allowed_origins = vec!["*".to_string()]),
+            "cors.allowed_origins"
+        );
+    }
+
+    #[test]
+    fn test_printed_config_round_trips() {
+        let mut config = Config::default();
+        config.server.port = 9000;
+        config.discovery.device_name = "Office".to_string();
+
+        let parsed: Config = toml::from_str(&config.to_toml()).unwrap();
+        assert_eq!(parsed, config);
+    }
+}
@@ -0,0 +1,263 @@
+//! Server certificate management
+//!
+//! The server presents a self-signed certificate on its encrypted transports
+//! (HTTPS and QUIC). Its key pair is the server's identity: peers do not
+//! validate the certificate against a CA but pin its SHA-256 fingerprint,
+//! which they learn during pairing.
+
+use anyhow::{Context, Result};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, SignatureScheme, SupportedProtocolVersion};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Subject name embedded in the self-signed certificate
+pub const CERT_SUBJECT: &str = "bridgex.local";
+
+const CERT_FILE: &str = "server_cert.der";
+const KEY_FILE: &str = "server_key.der";
+
+/// Self-signed server certificate and its private key (DER encoded)
+#[derive(Debug, Clone)]
+pub struct ServerCertificate {
+    pub cert_der: Vec<u8>,
+    pub key_der: Vec<u8>,
+}
+
+impl ServerCertificate {
+    /// Generate a fresh self-signed certificate
+    pub fn generate() -> Result<Self> {
+        let certified = rcgen::generate_simple_self_signed(vec![CERT_SUBJECT.to_string()])
+            .context("Failed to generate server certificate")?;
+        Ok(Self {
+            cert_der: certified.cert.der().to_vec(),
+            key_der: certified.key_pair.serialize_der(),
+        })
+    }
+
+    /// Load the certificate stored in `dir`, generating and saving one if missing
+    ///
+    /// The certificate must survive restarts, otherwise every paired device
+    /// would see a fingerprint mismatch.
+    pub fn load_or_generate(dir: &Path) -> Result<Self> {
+        let cert_path = dir.join(CERT_FILE);
+        let key_path = dir.join(KEY_FILE);
+
+        if cert_path.exists() && key_path.exists() {
+            return Ok(Self {
+                cert_der: fs::read(&cert_path)?,
+                key_der: fs::read(&key_path)?,
+            });
+        }
+
+        let certificate = Self::generate()?;
+        fs::create_dir_all(dir)?;
+        if let Err(e) = write_private_key(&key_path, &certificate.key_der) {
+            // Another process generated the key first; use its pair
+            if e.kind() == io::ErrorKind::AlreadyExists && cert_path.exists() {
+                return Ok(Self {
+                    cert_der: fs::read(&cert_path)?,
+                    key_der: fs::read(&key_path)?,
+                });
+            }
+            return Err(e).with_context(|| {
+                format!(
+                    "Failed to write {} (a leftover key without {} must be removed)",
+                    key_path.display(),
+                    CERT_FILE
+                )
+            });
+        }
+        fs::write(&cert_path, &certificate.cert_der)?;
+        Ok(certificate)
+    }
+
+    /// SHA-256 fingerprint of the certificate, hex encoded
+    pub fn fingerprint(&self) -> String {
+        certificate_fingerprint(&self.cert_der)
+    }
+
+    /// rustls server configuration presenting this certificate
+    ///
+    /// # Arguments
+    /// * `versions` - TLS versions to offer
+    /// * `alpn` - Application protocols, most preferred first
+    pub fn server_config(
+        &self,
+        versions: &[&'static SupportedProtocolVersion],
+        alpn: &[&[u8]],
+    ) -> Result<rustls::ServerConfig> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_protocol_versions(versions)?
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from(self.cert_der.clone())],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(self.key_der.clone())),
+            )
+            .context("Invalid server certificate")?;
+        config.alpn_protocols = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+        Ok(config)
+    }
+}
+
+/// Create `path` holding `key_der`, readable by the owner only
+///
+/// Fails if the file exists, so a key written concurrently is never
+/// replaced.
+fn write_private_key(path: &Path, key_der: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(key_der)?;
+    file.sync_all()
+}
+
+/// rustls client configuration trusting only the certificate with `fingerprint`
+///
+/// # Arguments
+/// * `fingerprint` - Server certificate fingerprint received at pairing
+/// * `versions` - TLS versions to offer
+/// * `alpn` - Application protocols, most preferred first
+pub fn pinned_client_config(
+    fingerprint: &str,
+    versions: &[&'static SupportedProtocolVersion],
+    alpn: &[&[u8]],
+) -> Result<rustls::ClientConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_protocol_versions(versions)?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier::new(fingerprint)))
+        .with_no_client_auth();
+    config.alpn_protocols = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+    Ok(config)
+}
+
+/// SHA-256 fingerprint of a DER certificate, hex encoded
+pub fn certificate_fingerprint(cert_der: &[u8]) -> String {
+    hex::encode(Sha256::digest(cert_der))
+}
+
+/// Certificate verifier accepting only a certificate with a pinned fingerprint
+///
+/// Used instead of WebPKI validation: the server certificate is self-signed
+/// and its fingerprint is exchanged during pairing.
+#[derive(Debug)]
+pub struct PinnedCertVerifier {
+    fingerprint: String,
+    provider: Arc<CryptoProvider>,
+}
+
+impl PinnedCertVerifier {
+    /// Create a verifier pinned to a hex-encoded SHA-256 fingerprint
+    pub fn new(fingerprint: &str) -> Self {
+        Self {
+            fingerprint: fingerprint.to_ascii_lowercase(),
+            provider: Arc::new(rustls::crypto::ring::default_provider()),
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if certificate_fingerprint(end_entity) == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_certificate() {
+        let certificate = ServerCertificate::generate().unwrap();
+        assert!(!certificate.cert_der.is_empty());
+        assert_eq!(certificate.fingerprint().len(), 64);
+    }
+
+    #[test]
+    fn test_load_or_generate_is_stable() {
+        let dir = std::env::temp_dir().join(format!("bridgex-cert-{}", uuid::Uuid::new_v4()));
+
+        let first = ServerCertificate::load_or_generate(&dir).unwrap();
+        let second = ServerCertificate::load_or_generate(&dir).unwrap();
+        assert_eq!(first.fingerprint(), second.fingerprint());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_private_key_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("bridgex-cert-{}", uuid::Uuid::new_v4()));
+        ServerCertificate::load_or_generate(&dir).unwrap();
+
+        let mode = fs::metadata(dir.join(KEY_FILE))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
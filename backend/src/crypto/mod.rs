@@ -0,0 +1,6 @@
+//! Cryptography module
+//!
+//! Key generation, key exchange, session key derivation, and server certificates
+
+pub mod cert;
+pub mod keys;
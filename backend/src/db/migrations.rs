@@ -0,0 +1,148 @@
+//! Versioned schema migrations
+//!
+//! Migrations are applied in order and recorded in the `schema_version`
+//! table, so columns added in later releases reach existing installs. A
+//! database migrated by a newer release is refused rather than written to
+//! with an outdated understanding of its schema.
+
+use anyhow::{Context, Result};
+use sqlx::{Pool, Sqlite};
+use thiserror::Error;
+
+/// A single up-migration
+#[derive(Debug)]
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// All migrations, in the order they must be applied
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sql: include_str!("migrations/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        description: "device platform and versions",
+        sql: include_str!("migrations/0002_device_details.sql"),
+    },
+    Migration {
+        version: 3,
+        description: "device labels",
+        sql: include_str!("migrations/0003_device_labels.sql"),
+    },
+    Migration {
+        version: 4,
+        description: "interrupted transfer status",
+        sql: include_str!("migrations/0004_interrupted_transfers.sql"),
+    },
+    Migration {
+        version: 5,
+        description: "audit log",
+        sql: include_str!("migrations/0005_audit_events.sql"),
+    },
+    Migration {
+        version: 6,
+        description: "pairing tokens",
+        sql: include_str!("migrations/0006_pairing_tokens.sql"),
+    },
+];
+
+/// Schema errors that stop the server from starting
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MigrationError {
+    #[error("database schema version {found} is newer than the supported version {supported}; upgrade BridgeX to open it")]
+    NewerSchema { found: u32, supported: u32 },
+}
+
+/// Latest schema version known to this build
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Current schema version, 0 for an empty or v0.1 database
+pub async fn current_version(pool: &Pool<Sqlite>) -> Result<u32> {
+    ensure_version_table(pool).await?;
+    let version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+        .fetch_one(pool)
+        .await?;
+    Ok(version.unwrap_or(0) as u32)
+}
+
+/// Apply every pending migration
+///
+/// # Returns
+/// Versions applied, in order (empty if the schema was up to date)
+pub async fn run(pool: &Pool<Sqlite>) -> Result<Vec<u32>> {
+    let current = current_version(pool).await?;
+    let supported = latest_version();
+    if current > supported {
+        return Err(MigrationError::NewerSchema {
+            found: current,
+            supported,
+        }
+        .into());
+    }
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let mut tx = pool.begin().await?;
+        sqlx::query(migration.sql)
+            .execute(&mut *tx)
+            .await
+            .with_context(|| {
+                format!(
+                    "Migration {} ({}) failed",
+                    migration.version, migration.description
+                )
+            })?;
+        sqlx::query(
+            "INSERT INTO schema_version (version, description, applied_at) VALUES (?, ?, ?)",
+        )
+        .bind(migration.version)
+        .bind(migration.description)
+        .bind(chrono::Utc::now())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        tracing::info!(
+            "Applied migration {}: {}",
+            migration.version,
+            migration.description
+        );
+        applied.push(migration.version);
+    }
+
+    Ok(applied)
+}
+
+async fn ensure_version_table(pool: &Pool<Sqlite>) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY NOT NULL,
+            description TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        let versions: Vec<u32> = MIGRATIONS.iter().map(|m| m.version).collect();
+        let expected: Vec<u32> = (1..=MIGRATIONS.len() as u32).collect();
+        assert_eq!(versions, expected);
+    }
+}
@@ -0,0 +1,48 @@
+-- BridgeX initial schema (v0.1)
+--
+-- Uses IF NOT EXISTS so databases created by v0.1, which predate the
+-- schema_version table, adopt this migration without changes.
+
+-- Devices table
+CREATE TABLE IF NOT EXISTS devices (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    type TEXT NOT NULL CHECK(type IN ('desktop', 'mobile', 'tablet', 'unknown')),
+    public_key BLOB NOT NULL,
+    paired_at TEXT NOT NULL,
+    last_seen TEXT,
+    created_at TEXT DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_devices_paired_at ON devices(paired_at);
+CREATE INDEX IF NOT EXISTS idx_devices_last_seen ON devices(last_seen);
+
+-- Transfers table
+CREATE TABLE IF NOT EXISTS transfers (
+    id TEXT PRIMARY KEY NOT NULL,
+    device_id TEXT NOT NULL,
+    file_name TEXT NOT NULL,
+    file_size INTEGER NOT NULL,
+    file_hash TEXT NOT NULL,
+    status TEXT NOT NULL CHECK(status IN ('pending', 'uploading', 'completed', 'failed')),
+    created_at TEXT NOT NULL,
+    completed_at TEXT,
+    FOREIGN KEY (device_id) REFERENCES devices(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_transfers_device_id ON transfers(device_id);
+CREATE INDEX IF NOT EXISTS idx_transfers_status ON transfers(status);
+CREATE INDEX IF NOT EXISTS idx_transfers_created_at ON transfers(created_at);
+
+-- Sessions table (for future use)
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY NOT NULL,
+    device_id TEXT NOT NULL,
+    session_key BLOB NOT NULL,
+    expires_at TEXT NOT NULL,
+    created_at TEXT DEFAULT (datetime('now')),
+    FOREIGN KEY (device_id) REFERENCES devices(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_device_id ON sessions(device_id);
+CREATE INDEX IF NOT EXISTS idx_sessions_expires_at ON sessions(expires_at);
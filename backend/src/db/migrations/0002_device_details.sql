@@ -0,0 +1,5 @@
+-- Platform and version details reported by devices at pairing
+
+ALTER TABLE devices ADD COLUMN platform TEXT;
+ALTER TABLE devices ADD COLUMN os_version TEXT;
+ALTER TABLE devices ADD COLUMN app_version TEXT;
@@ -0,0 +1,9 @@
+-- User-editable labels that tell devices apart
+
+ALTER TABLE devices ADD COLUMN nickname TEXT;
+ALTER TABLE devices ADD COLUMN icon TEXT;
+ALTER TABLE devices ADD COLUMN color TEXT;
+ALTER TABLE devices ADD COLUMN tags TEXT NOT NULL DEFAULT '[]';  -- JSON array of strings
+ALTER TABLE devices ADD COLUMN favorite INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_devices_favorite ON devices(favorite);
@@ -0,0 +1,26 @@
+-- Allow the `interrupted` status for transfers cut off by a shutdown
+--
+-- SQLite cannot alter a CHECK constraint, so the table is rebuilt.
+
+CREATE TABLE transfers_new (
+    id TEXT PRIMARY KEY NOT NULL,
+    device_id TEXT NOT NULL,
+    file_name TEXT NOT NULL,
+    file_size INTEGER NOT NULL,
+    file_hash TEXT NOT NULL,
+    status TEXT NOT NULL
+        CHECK(status IN ('pending', 'uploading', 'interrupted', 'completed', 'failed')),
+    created_at TEXT NOT NULL,
+    completed_at TEXT,
+    FOREIGN KEY (device_id) REFERENCES devices(id) ON DELETE CASCADE
+);
+
+INSERT INTO transfers_new (id, device_id, file_name, file_size, file_hash, status, created_at, completed_at)
+SELECT id, device_id, file_name, file_size, file_hash, status, created_at, completed_at FROM transfers;
+
+DROP TABLE transfers;
+ALTER TABLE transfers_new RENAME TO transfers;
+
+CREATE INDEX IF NOT EXISTS idx_transfers_device_id ON transfers(device_id);
+CREATE INDEX IF NOT EXISTS idx_transfers_status ON transfers(status);
+CREATE INDEX IF NOT EXISTS idx_transfers_created_at ON transfers(created_at);
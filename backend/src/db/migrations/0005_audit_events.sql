@@ -0,0 +1,33 @@
+-- Append-only audit log
+--
+-- Each event stores the hash of the previous one, so edited, inserted or
+-- removed rows break the chain. Device and transfer ids are not foreign keys:
+-- events outlive the rows they describe.
+
+CREATE TABLE IF NOT EXISTS audit_events (
+    seq INTEGER PRIMARY KEY NOT NULL,
+    occurred_at TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    device_id TEXT,
+    transfer_id TEXT,
+    details TEXT NOT NULL DEFAULT '{}',  -- JSON object
+    prev_hash TEXT NOT NULL,
+    hash TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_events_kind ON audit_events(kind);
+CREATE INDEX IF NOT EXISTS idx_audit_events_device ON audit_events(device_id);
+CREATE INDEX IF NOT EXISTS idx_audit_events_occurred ON audit_events(occurred_at);
+
+CREATE TRIGGER IF NOT EXISTS audit_events_no_update
+BEFORE UPDATE ON audit_events
+BEGIN
+    SELECT RAISE(ABORT, 'audit_events is append-only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_events_no_delete
+BEFORE DELETE ON audit_events
+BEGIN
+    SELECT RAISE(ABORT, 'audit_events is append-only');
+END;
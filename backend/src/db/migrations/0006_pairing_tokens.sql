@@ -0,0 +1,6 @@
+-- Pairing token of each device: the hash of the token in its QR code, the
+-- end of the pairing window, and when the device first authenticated with it
+
+ALTER TABLE devices ADD COLUMN token_hash BLOB;
+ALTER TABLE devices ADD COLUMN pairing_expires_at TEXT;
+ALTER TABLE devices ADD COLUMN confirmed_at TEXT;
@@ -2,26 +2,189 @@
 //!
 //! Handles SQLite database operations for devices, transfers, and sessions
 
+pub mod migrations;
 pub mod models;
 
 use anyhow::Result;
-use sqlx::{sqlite::SqlitePool, Pool, Sqlite};
+use chrono::{DateTime, SubsecRound, Utc};
+use prometheus::{HistogramOpts, HistogramTimer, HistogramVec};
+use serde::Serialize;
+use sqlx::{
+    sqlite::{SqliteConnection, SqlitePool},
+    Pool, QueryBuilder, Sqlite,
+};
+use tokio::sync::Mutex;
+
+/// Sort order of listings, by creation time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    /// Newest first
+    #[default]
+    Desc,
+}
+
+/// Filters and keyset pagination for [`Database::list_transfers`]
+#[derive(Debug, Clone, Default)]
+pub struct TransferQuery {
+    pub device_id: Option<String>,
+    pub status: Option<String>,
+    /// Created at or after
+    pub from: Option<DateTime<Utc>>,
+    /// Created strictly before
+    pub to: Option<DateTime<Utc>>,
+    /// Case-insensitive substring of the file name
+    pub name: Option<String>,
+    pub order: SortOrder,
+    /// Position of the last transfer of the previous page
+    pub after: Option<(DateTime<Utc>, String)>,
+    pub limit: u32,
+}
+
+/// Filters and pagination for [`Database::list_audit_events`]
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    pub kind: Option<String>,
+    pub device_id: Option<String>,
+    pub transfer_id: Option<String>,
+    /// Occurred at or after
+    pub from: Option<DateTime<Utc>>,
+    /// Occurred strictly before
+    pub to: Option<DateTime<Utc>>,
+    pub order: SortOrder,
+    /// `seq` of the last event of the previous page
+    pub after: Option<i64>,
+    pub limit: u32,
+}
+
+/// Outcome of [`Database::verify_audit_chain`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditChainStatus {
+    /// Number of events checked
+    pub events: u64,
+    /// Hash of the last event; compare with a previously exported value to
+    /// detect events removed from the end of the log
+    pub head: Option<String>,
+    /// `seq` of the first event whose hash or link does not match
+    pub first_invalid: Option<i64>,
+}
+
+impl AuditChainStatus {
+    /// Whether every event links to its predecessor with a matching hash
+    pub fn is_valid(&self) -> bool {
+        self.first_invalid.is_none()
+    }
+}
+
+/// Columns selected into [`models::AuditEvent`]
+const AUDIT_COLUMNS: &str =
+    "seq, occurred_at, kind, actor, device_id, transfer_id, details, prev_hash, hash";
+
+/// Columns selected into [`models::Device`]
+const DEVICE_COLUMNS: &str = "id, name, type, public_key, paired_at, last_seen, \
+     platform, os_version, app_version, nickname, icon, color, tags, favorite, \
+     token_hash, pairing_expires_at, confirmed_at";
+
+/// Records the latency of a query when dropped
+///
+/// The debug event is emitted inside the caller's span, so queries made by a
+/// handler carry its request id.
+struct QueryTimer {
+    operation: &'static str,
+    timer: Option<HistogramTimer>,
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            let seconds = timer.stop_and_record();
+            tracing::debug!(
+                operation = self.operation,
+                "Database query took {:.2}ms",
+                seconds * 1000.0
+            );
+        }
+    }
+}
 
 /// Database connection pool
 pub struct Database {
     pool: Pool<Sqlite>,
+    query_duration: HistogramVec,
+    /// Serializes appends so each event links to the one before it
+    audit_lock: Mutex<()>,
 }
 
 impl Database {
     /// Create a new database connection
     pub async fn new(database_url: &str) -> Result<Self> {
         let pool = SqlitePool::connect(database_url).await?;
-        Ok(Self { pool })
+        let query_duration = HistogramVec::new(
+            HistogramOpts::new("db_query_duration_seconds", "Database query latency").buckets(
+                vec![0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0],
+            ),
+            &["operation"],
+        )?;
+        Ok(Self {
+            pool,
+            query_duration,
+            audit_lock: Mutex::new(()),
+        })
+    }
+
+    /// Query latency histogram, labelled by operation
+    ///
+    /// Registered by [`crate::server::metrics::Metrics`].
+    pub fn query_duration(&self) -> &HistogramVec {
+        &self.query_duration
+    }
+
+    /// Time a query until the returned timer is dropped
+    fn timer(&self, operation: &'static str) -> QueryTimer {
+        QueryTimer {
+            operation,
+            timer: Some(
+                self.query_duration
+                    .with_label_values(&[operation])
+                    .start_timer(),
+            ),
+        }
     }
 
     /// Initialize database schema
+    ///
+    /// Applies pending migrations and fails if the database was created by a
+    /// newer release (see [`migrations::MigrationError`]).
     pub async fn init_schema(&self) -> Result<()> {
-        sqlx::query(include_str!("schema.sql"))
+        self.migrate().await?;
+        Ok(())
+    }
+
+    /// Apply pending migrations
+    ///
+    /// # Returns
+    /// Versions applied, in order
+    pub async fn migrate(&self) -> Result<Vec<u32>> {
+        migrations::run(&self.pool).await
+    }
+
+    /// Current schema version of the database
+    pub async fn schema_version(&self) -> Result<u32> {
+        migrations::current_version(&self.pool).await
+    }
+
+    /// Rebuild the database file, reclaiming unused space
+    pub async fn vacuum(&self) -> Result<()> {
+        sqlx::query("VACUUM").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Write a consistent copy of the database to `path`
+    ///
+    /// Safe while the server is running. Fails if `path` already exists.
+    pub async fn backup_to(&self, path: &std::path::Path) -> Result<()> {
+        sqlx::query("VACUUM INTO ?")
+            .bind(path.to_string_lossy().into_owned())
             .execute(&self.pool)
             .await?;
         Ok(())
@@ -29,57 +192,153 @@ impl Database {
 
     /// Save a device pairing
     pub async fn save_device(&self, device: &models::Device) -> Result<()> {
+        let _timer = self.timer("save_device");
         sqlx::query(
             r#"
-            INSERT INTO devices (id, name, type, public_key, paired_at, last_seen)
-            VALUES (?, ?, ?, ?, ?, ?)
+            INSERT INTO devices (id, name, type, public_key, paired_at, last_seen, platform, os_version, app_version,
+                                 nickname, icon, color, tags, favorite, token_hash, pairing_expires_at, confirmed_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             ON CONFLICT(id) DO UPDATE SET
-                last_seen = excluded.last_seen
+                last_seen = excluded.last_seen,
+                platform = excluded.platform,
+                os_version = excluded.os_version,
+                app_version = excluded.app_version
             "#,
         )
         .bind(&device.id)
         .bind(&device.name)
-        .bind(&device.device_type)
+        .bind(device.device_type)
         .bind(&device.public_key)
         .bind(device.paired_at)
         .bind(device.last_seen)
+        .bind(&device.platform)
+        .bind(&device.os_version)
+        .bind(&device.app_version)
+        .bind(&device.nickname)
+        .bind(&device.icon)
+        .bind(&device.color)
+        .bind(sqlx::types::Json(&device.tags))
+        .bind(device.favorite)
+        .bind(&device.token_hash)
+        .bind(device.pairing_expires_at)
+        .bind(device.confirmed_at)
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
-    /// Get all paired devices
+    /// Get all paired devices, favorites first
     pub async fn get_devices(&self) -> Result<Vec<models::Device>> {
-        let devices = sqlx::query_as::<_, models::Device>(
-            "SELECT id, name, type, public_key, paired_at, last_seen FROM devices ORDER BY paired_at DESC"
-        )
-        .fetch_all(&self.pool)
-        .await?;
+        let _timer = self.timer("get_devices");
+        let sql = format!(
+            "SELECT {} FROM devices ORDER BY favorite DESC, paired_at DESC",
+            DEVICE_COLUMNS
+        );
+        let devices = sqlx::query_as::<_, models::Device>(&sql)
+            .fetch_all(&self.pool)
+            .await?;
         Ok(devices)
     }
 
     /// Get device by ID
     pub async fn get_device(&self, id: &str) -> Result<Option<models::Device>> {
-        let device = sqlx::query_as::<_, models::Device>(
-            "SELECT id, name, type, public_key, paired_at, last_seen FROM devices WHERE id = ?"
-        )
-        .bind(id)
-        .fetch_optional(&self.pool)
-        .await?;
+        let _timer = self.timer("get_device");
+        let sql = format!("SELECT {} FROM devices WHERE id = ?", DEVICE_COLUMNS);
+        let device = sqlx::query_as::<_, models::Device>(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
         Ok(device)
     }
 
+    /// Apply user edits to a device
+    ///
+    /// # Returns
+    /// The updated device, or `None` if it is not paired
+    pub async fn update_device(
+        &self,
+        id: &str,
+        update: &models::DeviceUpdate,
+    ) -> Result<Option<models::Device>> {
+        let _timer = self.timer("update_device");
+        if !update.is_empty() {
+            let mut builder = QueryBuilder::<Sqlite>::new("UPDATE devices SET ");
+            let mut fields = builder.separated(", ");
+            if let Some(name) = &update.name {
+                fields.push("name = ").push_bind_unseparated(name.clone());
+            }
+            if let Some(nickname) = &update.nickname {
+                fields.push("nickname = ").push_bind_unseparated(nickname.clone());
+            }
+            if let Some(icon) = &update.icon {
+                fields.push("icon = ").push_bind_unseparated(icon.clone());
+            }
+            if let Some(color) = &update.color {
+                fields.push("color = ").push_bind_unseparated(color.clone());
+            }
+            if let Some(tags) = &update.tags {
+                fields
+                    .push("tags = ")
+                    .push_bind_unseparated(sqlx::types::Json(tags.clone()));
+            }
+            if let Some(favorite) = update.favorite {
+                fields.push("favorite = ").push_bind_unseparated(favorite);
+            }
+            builder.push(" WHERE id = ").push_bind(id.to_string());
+
+            let result = builder.build().execute(&self.pool).await?;
+            if result.rows_affected() == 0 {
+                return Ok(None);
+            }
+        }
+        self.get_device(id).await
+    }
+
+    /// Refresh a device's last_seen timestamp
+    ///
+    /// # Returns
+    /// `false` if the device is not paired
+    pub async fn touch_device(&self, id: &str, seen_at: DateTime<Utc>) -> Result<bool> {
+        let _timer = self.timer("touch_device");
+        let result = sqlx::query("UPDATE devices SET last_seen = ? WHERE id = ?")
+            .bind(seen_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record a device's first authentication with its pairing token
+    ///
+    /// # Returns
+    /// `false` if the device is not paired or was already confirmed
+    pub async fn confirm_device(&self, id: &str, confirmed_at: DateTime<Utc>) -> Result<bool> {
+        let _timer = self.timer("confirm_device");
+        let result =
+            sqlx::query("UPDATE devices SET confirmed_at = ? WHERE id = ? AND confirmed_at IS NULL")
+                .bind(confirmed_at)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Delete a device
-    pub async fn delete_device(&self, id: &str) -> Result<()> {
-        sqlx::query("DELETE FROM devices WHERE id = ?")
+    ///
+    /// # Returns
+    /// `false` if the device was not paired
+    pub async fn delete_device(&self, id: &str) -> Result<bool> {
+        let _timer = self.timer("delete_device");
+        let result = sqlx::query("DELETE FROM devices WHERE id = ?")
             .bind(id)
             .execute(&self.pool)
             .await?;
-        Ok(())
+        Ok(result.rows_affected() > 0)
     }
 
     /// Save a transfer record
     pub async fn save_transfer(&self, transfer: &models::Transfer) -> Result<()> {
+        let _timer = self.timer("save_transfer");
         sqlx::query(
             r#"
             INSERT INTO transfers (id, device_id, file_name, file_size, file_hash, status, created_at)
@@ -105,6 +364,7 @@ impl Database {
         status: &str,
         completed_at: Option<chrono::DateTime<chrono::Utc>>,
     ) -> Result<()> {
+        let _timer = self.timer("update_transfer_status");
         sqlx::query(
             r#"
             UPDATE transfers 
@@ -120,8 +380,150 @@ impl Database {
         Ok(())
     }
 
+    /// Get transfer by ID
+    pub async fn get_transfer(&self, id: &str) -> Result<Option<models::Transfer>> {
+        let _timer = self.timer("get_transfer");
+        let transfer = sqlx::query_as::<_, models::Transfer>(
+            r#"
+            SELECT id, device_id, file_name, file_size, file_hash, status, created_at, completed_at
+            FROM transfers
+            WHERE id = ?
+            "#
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(transfer)
+    }
+
+    /// List transfers matching a query, ordered by creation time then id
+    ///
+    /// Pages are keyset-paginated on `(created_at, id)`, which is stable
+    /// while new transfers are being added.
+    pub async fn list_transfers(&self, query: &TransferQuery) -> Result<Vec<models::Transfer>> {
+        let _timer = self.timer("list_transfers");
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            "SELECT id, device_id, file_name, file_size, file_hash, status, created_at, completed_at FROM transfers WHERE 1 = 1",
+        );
+
+        if let Some(device_id) = &query.device_id {
+            builder.push(" AND device_id = ").push_bind(device_id.clone());
+        }
+        if let Some(status) = &query.status {
+            builder.push(" AND status = ").push_bind(status.clone());
+        }
+        if let Some(from) = query.from {
+            builder.push(" AND created_at >= ").push_bind(from);
+        }
+        if let Some(to) = query.to {
+            builder.push(" AND created_at < ").push_bind(to);
+        }
+        if let Some(name) = &query.name {
+            let pattern = format!(
+                "%{}%",
+                name.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+            );
+            builder
+                .push(" AND file_name LIKE ")
+                .push_bind(pattern)
+                .push(" ESCAPE '\\'");
+        }
+
+        let (cmp, direction) = match query.order {
+            SortOrder::Asc => (">", "ASC"),
+            SortOrder::Desc => ("<", "DESC"),
+        };
+        if let Some((created_at, id)) = &query.after {
+            builder
+                .push(format!(" AND (created_at, id) {} (", cmp))
+                .push_bind(*created_at)
+                .push(", ")
+                .push_bind(id.clone())
+                .push(")");
+        }
+        builder
+            .push(format!(" ORDER BY created_at {0}, id {0} LIMIT ", direction))
+            .push_bind(query.limit as i64);
+
+        let transfers = builder
+            .build_query_as::<models::Transfer>()
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(transfers)
+    }
+
+    /// Delete transfers created before a cutoff
+    ///
+    /// # Arguments
+    /// * `statuses` - Statuses eligible for deletion
+    /// * `before` - Only transfers created strictly earlier are deleted
+    ///
+    /// # Returns
+    /// Ids of the deleted transfers
+    pub async fn purge_transfers(
+        &self,
+        statuses: &[String],
+        before: DateTime<Utc>,
+    ) -> Result<Vec<String>> {
+        let _timer = self.timer("purge_transfers");
+        if statuses.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut builder = QueryBuilder::<Sqlite>::new("DELETE FROM transfers WHERE created_at < ");
+        builder.push_bind(before).push(" AND status IN (");
+        let mut separated = builder.separated(", ");
+        for status in statuses {
+            separated.push_bind(status.clone());
+        }
+        builder.push(") RETURNING id");
+
+        let ids = builder
+            .build_query_scalar::<String>()
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(ids)
+    }
+
+    /// Mark every transfer still `uploading` as `interrupted`
+    ///
+    /// Run on shutdown and at startup, which also covers a previous run that
+    /// was killed. Received chunks are kept so the transfer can be resumed.
+    ///
+    /// # Returns
+    /// Number of transfers marked
+    pub async fn interrupt_uploading_transfers(&self) -> Result<u64> {
+        let _timer = self.timer("interrupt_uploading_transfers");
+        let result = sqlx::query(
+            "UPDATE transfers SET status = 'interrupted' WHERE status = 'uploading'",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Number of paired devices
+    pub async fn count_devices(&self) -> Result<i64> {
+        let _timer = self.timer("count_devices");
+        let count = sqlx::query_scalar("SELECT COUNT(*) FROM devices")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count)
+    }
+
+    /// Number of transfers in each status that has any
+    pub async fn count_transfers_by_status(&self) -> Result<Vec<(String, i64)>> {
+        let _timer = self.timer("count_transfers_by_status");
+        let counts =
+            sqlx::query_as("SELECT status, COUNT(*) FROM transfers GROUP BY status ORDER BY status")
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(counts)
+    }
+
     /// Get transfers for a device
     pub async fn get_device_transfers(&self, device_id: &str) -> Result<Vec<models::Transfer>> {
+        let _timer = self.timer("get_device_transfers");
         let transfers = sqlx::query_as::<_, models::Transfer>(
             r#"
             SELECT id, device_id, file_name, file_size, file_hash, status, created_at, completed_at
@@ -135,4 +537,175 @@ impl Database {
         .await?;
         Ok(transfers)
     }
+
+    /// Append an event to the audit log, chained to the previous one
+    pub async fn append_audit_event(
+        &self,
+        event: &models::NewAuditEvent,
+    ) -> Result<models::AuditEvent> {
+        let _timer = self.timer("append_audit_event");
+        let _lock = self.audit_lock.lock().await;
+        // Finish the transaction even if the caller goes away, so the
+        // connection never returns to the pool with it still open
+        let pool = self.pool.clone();
+        let event = event.clone();
+        tokio::spawn(async move { append_audit_event(&pool, &event).await }).await?
+    }
+
+    /// List audit events matching a query, ordered by `seq`
+    pub async fn list_audit_events(&self, query: &AuditQuery) -> Result<Vec<models::AuditEvent>> {
+        let _timer = self.timer("list_audit_events");
+        let mut builder = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {} FROM audit_events WHERE 1 = 1",
+            AUDIT_COLUMNS
+        ));
+
+        if let Some(kind) = &query.kind {
+            builder.push(" AND kind = ").push_bind(kind.clone());
+        }
+        if let Some(device_id) = &query.device_id {
+            builder.push(" AND device_id = ").push_bind(device_id.clone());
+        }
+        if let Some(transfer_id) = &query.transfer_id {
+            builder.push(" AND transfer_id = ").push_bind(transfer_id.clone());
+        }
+        if let Some(from) = query.from {
+            builder.push(" AND occurred_at >= ").push_bind(from);
+        }
+        if let Some(to) = query.to {
+            builder.push(" AND occurred_at < ").push_bind(to);
+        }
+
+        let (cmp, direction) = match query.order {
+            SortOrder::Asc => (">", "ASC"),
+            SortOrder::Desc => ("<", "DESC"),
+        };
+        if let Some(after) = query.after {
+            builder.push(format!(" AND seq {} ", cmp)).push_bind(after);
+        }
+        builder
+            .push(format!(" ORDER BY seq {} LIMIT ", direction))
+            .push_bind(query.limit as i64);
+
+        let events = builder
+            .build_query_as::<models::AuditEvent>()
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(events)
+    }
+
+    /// Check that every audit event links to its predecessor with a matching hash
+    pub async fn verify_audit_chain(&self) -> Result<AuditChainStatus> {
+        let _timer = self.timer("verify_audit_chain");
+        let sql = format!(
+            "SELECT {} FROM audit_events WHERE seq > ? ORDER BY seq LIMIT 500",
+            AUDIT_COLUMNS
+        );
+        let mut status = AuditChainStatus {
+            events: 0,
+            head: None,
+            first_invalid: None,
+        };
+        let mut last_seq = 0;
+        let mut prev_hash = models::AUDIT_GENESIS_HASH.to_string();
+
+        loop {
+            let page = sqlx::query_as::<_, models::AuditEvent>(&sql)
+                .bind(last_seq)
+                .fetch_all(&self.pool)
+                .await?;
+            if page.is_empty() {
+                break;
+            }
+            for event in page {
+                let intact = event.seq == last_seq + 1
+                    && event.prev_hash == prev_hash
+                    && event.compute_hash() == event.hash;
+                if !intact && status.first_invalid.is_none() {
+                    status.first_invalid = Some(event.seq);
+                }
+                status.events += 1;
+                last_seq = event.seq;
+                prev_hash = event.hash;
+            }
+        }
+
+        if status.events > 0 {
+            status.head = Some(prev_hash);
+        }
+        Ok(status)
+    }
+}
+
+/// Append an event after the current head of the audit log
+///
+/// The CLI appends to the same file from another process. A deferred
+/// transaction would let both read the same head and collide on `seq`;
+/// `BEGIN IMMEDIATE` takes the write lock before the read, waiting for the
+/// other writer to finish.
+async fn append_audit_event(
+    pool: &SqlitePool,
+    event: &models::NewAuditEvent,
+) -> Result<models::AuditEvent> {
+    let mut conn = pool.acquire().await?;
+    sqlx::query("BEGIN IMMEDIATE").execute(&mut *conn).await?;
+    let result = async {
+        let audit = insert_audit_event(&mut conn, event).await?;
+        sqlx::query("COMMIT").execute(&mut *conn).await?;
+        Ok(audit)
+    }
+    .await;
+    if result.is_err() && sqlx::query("ROLLBACK").execute(&mut *conn).await.is_err() {
+        // Don't hand a connection in an unknown state back to the pool
+        drop(conn.detach());
+    }
+    result
+}
+
+/// Insert an event chained to the current head, inside a write transaction
+async fn insert_audit_event(
+    conn: &mut SqliteConnection,
+    event: &models::NewAuditEvent,
+) -> Result<models::AuditEvent> {
+    let last: Option<(i64, String)> =
+        sqlx::query_as("SELECT seq, hash FROM audit_events ORDER BY seq DESC LIMIT 1")
+            .fetch_optional(&mut *conn)
+            .await?;
+    let (seq, prev_hash) = match last {
+        Some((seq, hash)) => (seq + 1, hash),
+        None => (1, models::AUDIT_GENESIS_HASH.to_string()),
+    };
+
+    let mut audit = models::AuditEvent {
+        seq,
+        // Hashed as RFC 3339 with microseconds, so drop the rest up front
+        occurred_at: Utc::now().trunc_subsecs(6),
+        kind: event.kind.clone(),
+        actor: event.actor.clone(),
+        device_id: event.device_id.clone(),
+        transfer_id: event.transfer_id.clone(),
+        details: event.details.clone(),
+        prev_hash,
+        hash: String::new(),
+    };
+    audit.hash = audit.compute_hash();
+
+    sqlx::query(
+        r#"
+        INSERT INTO audit_events (seq, occurred_at, kind, actor, device_id, transfer_id, details, prev_hash, hash)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(audit.seq)
+    .bind(audit.occurred_at)
+    .bind(&audit.kind)
+    .bind(&audit.actor)
+    .bind(&audit.device_id)
+    .bind(&audit.transfer_id)
+    .bind(sqlx::types::Json(&audit.details))
+    .bind(&audit.prev_hash)
+    .bind(&audit.hash)
+    .execute(&mut *conn)
+    .await?;
+    Ok(audit)
 }
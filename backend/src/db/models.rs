@@ -2,7 +2,61 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::FromRow;
+use std::fmt;
+use std::str::FromStr;
+
+/// Kind of a paired device
+///
+/// Stored as lowercase text, matching the `devices.type` CHECK constraint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum DeviceType {
+    Desktop,
+    Mobile,
+    Tablet,
+    #[default]
+    Unknown,
+}
+
+impl DeviceType {
+    /// Every device type, in display order
+    pub const ALL: [DeviceType; 4] = [
+        DeviceType::Desktop,
+        DeviceType::Mobile,
+        DeviceType::Tablet,
+        DeviceType::Unknown,
+    ];
+
+    /// Lowercase name as stored in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeviceType::Desktop => "desktop",
+            DeviceType::Mobile => "mobile",
+            DeviceType::Tablet => "tablet",
+            DeviceType::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DeviceType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DeviceType::ALL
+            .into_iter()
+            .find(|t| t.as_str() == s)
+            .ok_or_else(|| format!("unknown device type '{}'", s))
+    }
+}
 
 /// Device model
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -10,28 +64,110 @@ pub struct Device {
     pub id: String,
     pub name: String,
     #[sqlx(rename = "type")]
-    pub device_type: String,
+    pub device_type: DeviceType,
+    /// Public key handed out at pairing; never sent back in device lists
+    #[serde(skip_serializing, default)]
     pub public_key: Vec<u8>,
     pub paired_at: DateTime<Utc>,
     pub last_seen: Option<DateTime<Utc>>,
+    /// Operating system family, e.g. "android", "ios", "windows"
+    pub platform: Option<String>,
+    pub os_version: Option<String>,
+    /// BridgeX client version
+    pub app_version: Option<String>,
+    /// User-chosen label shown instead of the name
+    pub nickname: Option<String>,
+    /// Icon identifier, e.g. "phone" or "work-laptop"
+    pub icon: Option<String>,
+    /// Accent color as `#rrggbb`
+    pub color: Option<String>,
+    #[sqlx(json)]
+    pub tags: Vec<String>,
+    pub favorite: bool,
+    /// SHA-256 of the pairing token, the device's shared secret
+    #[serde(skip)]
+    pub token_hash: Option<Vec<u8>>,
+    /// End of the window for the first authentication with the token
+    pub pairing_expires_at: Option<DateTime<Utc>>,
+    /// When the device first authenticated with its pairing token
+    pub confirmed_at: Option<DateTime<Utc>>,
 }
 
 impl Device {
-    pub fn new(
-        id: String,
-        name: String,
-        device_type: String,
-        public_key: Vec<u8>,
-    ) -> Self {
+    pub fn new(id: String, name: String, device_type: DeviceType, public_key: Vec<u8>) -> Self {
+        let now = Utc::now();
         Self {
             id,
             name,
             device_type,
             public_key,
-            paired_at: Utc::now(),
-            last_seen: Some(Utc::now()),
+            paired_at: now,
+            last_seen: Some(now),
+            platform: None,
+            os_version: None,
+            app_version: None,
+            nickname: None,
+            icon: None,
+            color: None,
+            tags: Vec::new(),
+            favorite: false,
+            token_hash: None,
+            pairing_expires_at: None,
+            confirmed_at: None,
         }
     }
+
+    /// Attach the pairing token handed out in the device's QR code
+    ///
+    /// Its hash is kept, see [`token_hash`]. The device must first
+    /// authenticate with it before `expires_at`.
+    pub fn with_pairing_token(mut self, token: &[u8], expires_at: DateTime<Utc>) -> Self {
+        self.token_hash = Some(token_hash(token).to_vec());
+        self.pairing_expires_at = Some(expires_at);
+        self
+    }
+
+    /// Hash of the pairing token, if it may be used to authenticate at `now`
+    ///
+    /// A token is usable once confirmed by a first authentication, or before
+    /// the pairing window closes. Devices paired before tokens were stored
+    /// have none and must be paired again.
+    pub fn usable_token_hash(&self, now: DateTime<Utc>) -> Option<&[u8]> {
+        let token_hash = self.token_hash.as_deref()?;
+        let in_window = self
+            .pairing_expires_at
+            .is_some_and(|expires_at| now < expires_at);
+        (self.confirmed_at.is_some() || in_window).then_some(token_hash)
+    }
+}
+
+/// SHA-256 of a pairing token
+///
+/// Both sides key device authentication with it and the server stores it.
+/// It is therefore as good as the token: anyone who can read the database
+/// can authenticate as any device.
+pub fn token_hash(token: &[u8]) -> [u8; 32] {
+    Sha256::digest(token).into()
+}
+
+/// Changes to the user-editable fields of a device
+///
+/// `None` leaves a field unchanged; for nullable fields `Some(None)` clears it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceUpdate {
+    pub name: Option<String>,
+    pub nickname: Option<Option<String>>,
+    pub icon: Option<Option<String>>,
+    pub color: Option<Option<String>>,
+    pub tags: Option<Vec<String>>,
+    pub favorite: Option<bool>,
+}
+
+impl DeviceUpdate {
+    /// Whether the update changes nothing
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 /// Transfer model
@@ -67,3 +203,87 @@ impl Transfer {
         }
     }
 }
+
+/// `prev_hash` of the first audit event
+pub const AUDIT_GENESIS_HASH: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Entry of the append-only audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct AuditEvent {
+    /// Position in the log, starting at 1
+    pub seq: i64,
+    pub occurred_at: DateTime<Utc>,
+    /// Event type, e.g. `device.paired`
+    pub kind: String,
+    /// Who caused the event, e.g. `api`, `cli` or `device:<id>`
+    pub actor: String,
+    pub device_id: Option<String>,
+    pub transfer_id: Option<String>,
+    #[sqlx(json)]
+    pub details: serde_json::Value,
+    /// Hash of the previous event, [`AUDIT_GENESIS_HASH`] for the first one
+    pub prev_hash: String,
+    /// SHA-256 over `prev_hash` and every other field, hex encoded
+    pub hash: String,
+}
+
+impl AuditEvent {
+    /// Hash the event should have given its fields and `prev_hash`
+    pub fn compute_hash(&self) -> String {
+        let fields = serde_json::json!([
+            self.seq,
+            self.occurred_at.to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            self.kind,
+            self.actor,
+            self.device_id,
+            self.transfer_id,
+            self.details,
+        ]);
+        let mut hasher = Sha256::new();
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(fields.to_string().as_bytes());
+        hex::encode(hasher.finalize())
+    }
+}
+
+/// Audit event to append, see [`crate::db::Database::append_audit_event`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewAuditEvent {
+    pub kind: String,
+    pub actor: String,
+    pub device_id: Option<String>,
+    pub transfer_id: Option<String>,
+    pub details: serde_json::Value,
+}
+
+impl NewAuditEvent {
+    pub fn new(kind: impl Into<String>, actor: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            actor: actor.into(),
+            device_id: None,
+            transfer_id: None,
+            details: serde_json::json!({}),
+        }
+    }
+
+    /// Device the event is about
+    pub fn device(mut self, device_id: impl Into<String>) -> Self {
+        self.device_id = Some(device_id.into());
+        self
+    }
+
+    /// Transfer the event is about
+    pub fn transfer(mut self, transfer_id: impl Into<String>) -> Self {
+        self.transfer_id = Some(transfer_id.into());
+        self
+    }
+
+    /// Event-specific fields, as a JSON object
+    pub fn details(mut self, details: serde_json::Value) -> Self {
+        self.details = details;
+        self
+    }
+}
@@ -2,13 +2,67 @@
 //!
 //! Core functionality for P2P file transfer, device pairing, and secure communication.
 
+pub mod cli;
+pub mod config;
 pub mod crypto;
 pub mod db;
+pub mod logging;
+pub mod pairing;
 pub mod qr;
 pub mod server;
 pub mod util;
 
+use std::sync::Arc;
+
 /// Re-export commonly used types
+pub use config::Config;
+pub use crypto::cert::ServerCertificate;
 pub use crypto::keys::{generate_keypair, KeyPair};
 pub use db::Database;
-pub use qr::{generate_pairing_qr, generate_qr_data_url, generate_qr_svg};
+pub use pairing::{parse_pairing_uri, PairingPayload, PairingUriError};
+pub use qr::{generate_pairing_qr, generate_qr_data_url, generate_qr_svg, generate_qr_terminal};
+pub use server::discovery::Discovery;
+pub use server::p2p::ConnectionManager;
+pub use server::metrics::Metrics;
+pub use server::presence::{PresenceThresholds, PresenceTracker};
+pub use server::rate_limit::RateLimiter;
+pub use server::shutdown::Shutdown;
+pub use server::signaling::SignalingHub;
+
+/// Application state
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Arc<Database>,
+    pub connections: Arc<ConnectionManager>,
+    pub certificate: Arc<ServerCertificate>,
+    pub signaling: Arc<SignalingHub>,
+    pub discovery: Arc<Discovery>,
+    pub presence: Arc<PresenceTracker>,
+    pub config: Arc<Config>,
+    pub shutdown: Arc<Shutdown>,
+    pub metrics: Arc<Metrics>,
+    pub rate_limiter: Arc<RateLimiter>,
+}
+
+impl AppState {
+    /// Create the application state around a database and server certificate
+    pub fn new(db: Database, certificate: ServerCertificate) -> Self {
+        Self::with_config(db, certificate, Config::default())
+    }
+
+    /// Create the application state with a validated configuration
+    pub fn with_config(db: Database, certificate: ServerCertificate, config: Config) -> Self {
+        Self {
+            metrics: Arc::new(Metrics::new(&db)),
+            db: Arc::new(db),
+            connections: Arc::new(ConnectionManager::new()),
+            certificate: Arc::new(certificate),
+            signaling: Arc::new(SignalingHub::new()),
+            discovery: Arc::new(Discovery::new()),
+            presence: Arc::new(PresenceTracker::new(config.presence_thresholds())),
+            rate_limiter: Arc::new(RateLimiter::new(config.rate_limit.clone())),
+            config: Arc::new(config),
+            shutdown: Arc::new(Shutdown::new()),
+        }
+    }
+}
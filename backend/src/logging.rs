@@ -0,0 +1,267 @@
+//! Logging setup
+//!
+//! Logs go to stdout and optionally to daily-rotated files in the data
+//! directory, as text or one JSON object per line. Every HTTP request gets
+//! an `X-Request-Id`, kept from the client if it sent one, which is recorded
+//! on a span around the handler so that handler and database events carry
+//! it. Keys, tokens and similar secrets are redacted from every line before
+//! it is written.
+
+use axum::{
+    http::{HeaderName, Request},
+    Router,
+};
+use std::borrow::Cow;
+use std::io::{self, IsTerminal, Write};
+use tower_http::request_id::{
+    MakeRequestUuid, PropagateRequestIdLayer, RequestId, SetRequestIdLayer,
+};
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+use tracing::Level;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::{
+    layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer, Registry,
+};
+
+use crate::config::{Config, LogFormat};
+
+/// Header carrying the request correlation id
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Replacement for redacted values
+pub const REDACTED: &str = "[redacted]";
+
+/// Field and parameter names whose values never reach the logs
+const SENSITIVE_NAMES: &[&str] = &[
+    "key",
+    "public_key",
+    "session_key",
+    "token",
+    "secret",
+    "password",
+    "authorization",
+];
+
+/// Install the global subscriber for the server
+///
+/// `RUST_LOG` takes precedence over `logging.level`.
+///
+/// # Returns
+/// Guard flushing the log file on drop; keep it alive until exit
+pub fn init(config: &Config) -> anyhow::Result<Option<WorkerGuard>> {
+    let logging = &config.logging;
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&logging.level));
+
+    let ansi = io::stdout().is_terminal();
+    let mut layers = vec![fmt_layer(logging.format, Redacting(io::stdout), ansi)];
+    let guard = if logging.file {
+        let dir = config.log_dir();
+        std::fs::create_dir_all(&dir)?;
+        let appender = RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix("bridgex")
+            .filename_suffix("log")
+            .max_log_files(logging.max_files)
+            .build(&dir)?;
+        let (writer, guard) = tracing_appender::non_blocking(appender);
+        layers.push(fmt_layer(logging.format, Redacting(writer), false));
+        Some(guard)
+    } else {
+        None
+    };
+
+    tracing_subscriber::registry()
+        .with(layers.with_filter(filter))
+        .try_init()?;
+
+    if logging.file {
+        tracing::info!("Writing logs to {}", config.log_dir().display());
+    }
+    Ok(guard)
+}
+
+fn fmt_layer<W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    }
+}
+
+/// Tag every request with an `X-Request-Id` and trace it in a span carrying the id
+///
+/// The id is echoed in the response so clients can quote it in bug reports.
+pub fn trace_requests(router: Router) -> Router {
+    let header = HeaderName::from_static(REQUEST_ID_HEADER);
+    router
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(request_span)
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .layer(PropagateRequestIdLayer::new(header.clone()))
+        .layer(SetRequestIdLayer::new(header, MakeRequestUuid))
+}
+
+fn request_span<B>(request: &Request<B>) -> tracing::Span {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or("-");
+    tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        uri = %redact(&request.uri().to_string()),
+    )
+}
+
+/// Redact the values of sensitive fields and parameters in a log line
+///
+/// Covers `name=value` (query strings and text log fields, quoted or not)
+/// and `"name":"value"` (JSON), for names such as `key` and `token`.
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let bytes = text.as_bytes();
+    let mut redacted = String::new();
+    let mut copied = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if let Some((start, end)) = sensitive_value_at(bytes, i) {
+            redacted.push_str(&text[copied..start]);
+            redacted.push_str(REDACTED);
+            copied = end;
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+
+    if copied == 0 {
+        Cow::Borrowed(text)
+    } else {
+        redacted.push_str(&text[copied..]);
+        Cow::Owned(redacted)
+    }
+}
+
+/// Byte range of a sensitive value whose name starts at `i`
+fn sensitive_value_at(bytes: &[u8], i: usize) -> Option<(usize, usize)> {
+    if i > 0 && (bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_') {
+        return None;
+    }
+
+    let name = SENSITIVE_NAMES
+        .iter()
+        .find(|name| bytes[i..].starts_with(name.as_bytes()))?;
+    let after = i + name.len();
+
+    let (start, quoted) = if bytes[after..].starts_with(b"=\"") {
+        (after + 2, true)
+    } else if bytes[after..].starts_with(b"=") {
+        (after + 1, false)
+    } else if i > 0 && bytes[i - 1] == b'"' && bytes[after..].starts_with(b"\":\"") {
+        (after + 3, true)
+    } else {
+        return None;
+    };
+
+    let end = bytes[start..]
+        .iter()
+        .position(|&b| {
+            if quoted {
+                b == b'"'
+            } else {
+                b == b'&' || b == b'"' || b == b',' || b == b'}' || b.is_ascii_whitespace()
+            }
+        })
+        .map_or(bytes.len(), |len| start + len);
+
+    (end > start).then_some((start, end))
+}
+
+/// [`MakeWriter`] redacting secrets from everything written, see [`redact`]
+#[derive(Debug, Clone)]
+pub struct Redacting<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.0.make_writer())
+    }
+}
+
+/// Writer returned by [`Redacting`]
+///
+/// Log layers write each event in a single call, so values are never split
+/// across writes.
+#[derive(Debug)]
+pub struct RedactingWriter<W>(W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match std::str::from_utf8(buf) {
+            Ok(text) => self.0.write_all(redact(text).as_bytes())?,
+            Err(_) => self.0.write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_pairing_uri() {
+        let uri =
+            "Pairing URI: bridgex://pair?v=1&id=abc&key=S2V5&token=dG9r&addr=10.0.0.2:8080&fp=00ff";
+        assert_eq!(
+            redact(uri),
+            "Pairing URI: bridgex://pair?v=1&id=abc&key=[redacted]&token=[redacted]&addr=10.0.0.2:8080&fp=00ff"
+        );
+    }
+
+    #[test]
+    fn test_redact_fields() {
+        assert_eq!(
+            redact(r#"{"fields":{"token":"abc","device":"d1"},"public_key":"xyz"}"#),
+            r#"{"fields":{"token":"[redacted]","device":"d1"},"public_key":"[redacted]"}"#
+        );
+        assert_eq!(
+            redact(r#"session_key="a b" secret=s3cr3t, ok=1"#),
+            r#"session_key="[redacted]" secret=[redacted], ok=1"#
+        );
+    }
+
+    #[test]
+    fn test_redact_leaves_other_text_alone() {
+        let text = "monkey=1 keyboard=2 key: value token= é";
+        assert!(matches!(redact(text), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_redacting_writer() {
+        let mut writer = RedactingWriter(Vec::new());
+        writer.write_all(b"token=abc\n").unwrap();
+        assert_eq!(writer.0, b"token=[redacted]\n");
+    }
+}
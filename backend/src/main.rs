@@ -1,105 +1,266 @@
-use axum::{
-    extract::State,
-    routing::{delete, get, post},
-    Router,
-};
-use std::net::SocketAddr;
-use std::sync::Arc;
-use tower_http::cors::{Any, CorsLayer};
-use tower_http::trace::TraceLayer;
+use clap::Parser;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-mod crypto;
-mod db;
-mod qr;
-mod server;
-mod util;
-
-use db::Database;
-use server::{api, upload};
-
-/// Application state
-#[derive(Clone)]
-pub struct AppState {
-    pub db: Arc<Database>,
-}
+use bridgex_backend::cli::{admin, Cli, Command, PairArgs};
+use bridgex_backend::server::ports::BoundPorts;
+use bridgex_backend::{logging, server};
+use bridgex_backend::{AppState, Config, ServerCertificate};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load environment variables
     dotenv::dotenv().ok();
 
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "bridgex_backend=debug,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    let cli = Cli::parse();
+    let config = match cli.load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Configuration error: {}", e);
+            std::process::exit(2);
+        }
+    };
 
-    // Initialize database
-    let db_path = std::env::var("BRIDGEX_DB_PATH")
-        .unwrap_or_else(|_| "./data/bridge.db".to_string());
-    
-    // Create data directory if it doesn't exist
-    if let Some(parent) = std::path::Path::new(&db_path).parent() {
-        std::fs::create_dir_all(parent)?;
+    if cli.print_config {
+        print!("{}", config.to_toml());
+        return Ok(());
     }
 
-    let database_url = format!("sqlite:{}", db_path);
-    tracing::info!("Connecting to database: {}", database_url);
-    
-    let db = Database::new(&database_url).await?;
+    match cli.command {
+        None | Some(Command::Serve) => serve(config).await,
+        Some(command) => {
+            // Admin commands print results on stdout; keep logs to warnings on stderr
+            tracing_subscriber::registry()
+                .with(
+                    tracing_subscriber::EnvFilter::try_from_default_env()
+                        .unwrap_or_else(|_| "warn".into()),
+                )
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .with_writer(logging::Redacting(std::io::stderr)),
+                )
+                .init();
+
+            if let Err(e) = admin::run(command, config, &mut std::io::stdout()).await {
+                eprintln!("Error: {:#}", e);
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Run the server until it fails or receives a shutdown signal
+async fn serve(config: Config) -> Result<(), Box<dyn std::error::Error>> {
+    // Initialize tracing; the guard flushes the log file on exit
+    let _log_guard = logging::init(&config)?;
+
+    // Initialize database
+    tracing::info!("Connecting to database: {}", config.database.path.display());
+    let db = admin::open_database(&config).await?;
     db.init_schema().await?;
-    tracing::info!("Database initialized");
+    tracing::info!("Database initialized (schema version {})", db.schema_version().await?);
 
-    let state = AppState {
-        db: Arc::new(db),
-    };
+    // Uploads cut off by a previous run that was killed can be resumed
+    let interrupted = db.interrupt_uploading_transfers().await?;
+    if interrupted > 0 {
+        tracing::warn!("Marked {} transfers left uploading as interrupted", interrupted);
+    }
+    match server::audit::record_config(&db, &config).await {
+        Ok(true) => tracing::info!("Configuration changed since the last start"),
+        Ok(false) => {}
+        Err(e) => tracing::error!("Failed to audit the configuration: {}", e),
+    }
+
+    // Load the server certificate pinned by paired devices
+    let certificate = ServerCertificate::load_or_generate(config.data_dir())?;
+    tracing::info!("Server certificate fingerprint: {}", certificate.fingerprint());
+
+    let addr = config.http_addr();
+    let admin_addr = config.admin_addr();
+    let state = AppState::with_config(db, certificate, config);
 
-    // Build application routes
-    let app = Router::new()
-        .route("/api/v1/health", get(api::health))
-        .route("/api/v1/pair", post(api::pair))
-        .route("/api/v1/transfer/init", post(api::transfer_init))  // Fixed: was /transfer
-        .route("/api/v1/transfer/upload", post(upload::upload_chunk))  // Fixed: removed :id
-        .route("/api/v1/transfer/finalize", post(upload::finalize_transfer))  // Added
-        .route("/api/v1/transfer/:id/status", get(upload::get_upload_status))
-        .route("/api/v1/status", get(api::status))
-        .route("/api/v1/devices", get(api::list_devices))
-        .route("/api/v1/devices/:id", delete(api::delete_device))
-        .with_state(state)
-        .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any))
-        .layer(TraceLayer::new_for_http());
-
-    // Get host and port from environment or use defaults
-    let host = std::env::var("BRIDGEX_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
-    let port = std::env::var("BRIDGEX_PORT")
-        .unwrap_or_else(|_| "8080".to_string())
-        .parse::<u16>()
-        .unwrap_or(8080);
-
-    let addr = SocketAddr::from((
-        host.parse::<std::net::IpAddr>()?,
-        port,
-    ));
-
-    tracing::info!("BridgeX backend starting on http://{}", addr);
-    tracing::info!("API endpoints:");
+    // Build application routes: everything on loopback, the device subset on `host`
+    let admin_app = logging::trace_requests(server::router(state.clone()));
+    let device_app = logging::trace_requests(server::device_router(state.clone()));
+
+    tracing::info!("Device API endpoints (also served by the admin API):");
     tracing::info!("  GET    /api/v1/health               - Health check");
-    tracing::info!("  POST   /api/v1/pair                 - Device pairing");
     tracing::info!("  POST   /api/v1/transfer/init        - Initialize transfer");
     tracing::info!("  POST   /api/v1/transfer/upload      - Upload file chunk");
     tracing::info!("  POST   /api/v1/transfer/finalize    - Finalize transfer");
     tracing::info!("  GET    /api/v1/transfer/:id/status  - Get upload status");
+    tracing::info!("  POST   /api/v1/devices/:id/heartbeat - Device heartbeat");
+    tracing::info!("  POST   /api/v1/signaling/offer      - Open WebRTC signaling session");
+    tracing::info!("  *      /api/v1/signaling/:id/...    - Answer and ICE candidate exchange");
+    tracing::info!("Admin API endpoints:");
+    tracing::info!("  GET    /metrics                     - Prometheus metrics");
+    tracing::info!("  POST   /api/v1/pair                 - Device pairing");
+    tracing::info!("  GET    /api/v1/transfers            - Transfer history");
+    tracing::info!("  GET    /api/v1/transfers/:id        - Transfer details");
     tracing::info!("  GET    /api/v1/status               - Server status");
     tracing::info!("  GET    /api/v1/devices              - List devices");
+    tracing::info!("  PATCH  /api/v1/devices/:id          - Rename or label device");
     tracing::info!("  DELETE /api/v1/devices/:id          - Delete device");
+    tracing::info!("  *      /api/v1/audit/...            - Audit log, export and verification");
+    tracing::info!("  GET    /api/v1/discovery/peers      - Peers discovered on the LAN");
+
+    // Start direct TCP transfer channel
+    let tcp_addr = state.config.tcp_addr();
+    let tcp_listener = tokio::net::TcpListener::bind(tcp_addr).await?;
+    let tcp_addr = tcp_listener.local_addr()?;
+    tracing::info!("TCP transfer channel listening on {}", tcp_addr);
+    tokio::spawn(server::tcp::serve(tcp_listener, state.clone()));
+
+    // Start QUIC transfer endpoint
+    let quic_addr = state.config.quic_addr();
+    let quic_endpoint = server::quic::server_endpoint(quic_addr, &state.certificate)?;
+    let quic_addr = quic_endpoint.local_addr()?;
+    tracing::info!("QUIC transfer endpoint listening on udp://{}", quic_addr);
+    tokio::spawn(server::quic::serve(quic_endpoint, state.clone()));
 
-    // Start server
+    // Start servers
+    let scheme = state.config.http_scheme();
+    let admin_listener = match admin_addr {
+        Some(admin_addr) => {
+            let admin_listener = tokio::net::TcpListener::bind(admin_addr).await?;
+            let admin_addr = admin_listener.local_addr()?;
+            tracing::info!("BridgeX admin API listening on {}://{}", scheme, admin_addr);
+            Some((admin_listener, admin_addr))
+        }
+        None => None,
+    };
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    let addr = listener.local_addr()?;
+    tracing::info!("BridgeX device API listening on {}://{}", scheme, addr);
+    #[cfg(unix)]
+    let admin_socket = {
+        let path = state.config.admin_socket_path();
+        let socket = server::admin_socket::bind(&path)
+            .map_err(|e| format!("Failed to create admin socket {}: {}", path.display(), e))?;
+        tracing::info!("BridgeX admin API listening on unix:{}", path.display());
+        (socket, path)
+    };
+
+    // Publish reachable addresses for pairing and advertise on the LAN
+    let reachable = server::discovery::reachable_addrs(addr);
+    state.discovery.set_local_addrs(reachable.clone()).await;
+
+    if state.config.pairing.qr_on_startup {
+        let args = PairArgs::startup(&state.config);
+        if let Err(e) = admin::pair(&state, args, &mut std::io::stdout()).await {
+            tracing::warn!("Failed to print pairing QR code: {:#}", e);
+        }
+    }
+
+    let discovery = &state.config.discovery;
+    let _mdns = if discovery.enabled && !addr.ip().is_loopback() && !reachable.is_empty() {
+        match server::discovery::start(
+            state.discovery.clone(),
+            &discovery.device_name,
+            &state.certificate.fingerprint(),
+            &reachable,
+        ) {
+            Ok(daemon) => Some(daemon),
+            Err(e) => {
+                tracing::warn!("mDNS discovery unavailable: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // Stop on SIGTERM or Ctrl+C
+    tokio::spawn({
+        let shutdown = state.shutdown.clone();
+        async move {
+            server::shutdown::signal().await;
+            shutdown.trigger();
+        }
+    });
+
+    // Tell a supervising process where to connect, e.g. after binding port 0
+    let ports = BoundPorts {
+        port: addr.port(),
+        admin_port: admin_listener.as_ref().map(|(_, addr)| addr.port()),
+        tcp_port: tcp_addr.port(),
+        quic_port: quic_addr.port(),
+        #[cfg(unix)]
+        admin_socket: Some(admin_socket.1.clone()),
+        #[cfg(not(unix))]
+        admin_socket: None,
+        pid: std::process::id(),
+    };
+    if let Some(path) = &state.config.server.port_file {
+        ports
+            .write_file(path)
+            .map_err(|e| format!("Failed to write port file {}: {}", path.display(), e))?;
+    }
+    ports.announce()?;
+
+    if !state.config.tls_enabled() && !addr.ip().is_loopback() {
+        tracing::warn!("TLS is off: API traffic crosses the network in cleartext");
+    }
+    let mut http = tokio::spawn({
+        let state = state.clone();
+        async move {
+            #[cfg(unix)]
+            let socket = server::admin_socket::serve(
+                admin_socket.0,
+                admin_socket.1,
+                admin_app.clone(),
+                state.shutdown.clone(),
+            );
+            #[cfg(not(unix))]
+            let socket = std::future::ready(Ok(()));
+            let admin = async {
+                match admin_listener {
+                    Some((listener, _)) => server::serve(listener, admin_app, state.clone()).await,
+                    None => Ok(()),
+                }
+            };
+            tokio::try_join!(
+                admin,
+                server::serve(listener, device_app, state.clone()),
+                socket,
+            )
+        }
+    });
+
+    tokio::select! {
+        biased;
+        _ = state.shutdown.triggered() => {}
+        result = &mut http => {
+            // The HTTP servers only return on their own when one fails
+            tracing::error!("HTTP server stopped unexpectedly");
+            state.shutdown.trigger();
+            server::shutdown::drain(&state, state.config.shutdown_grace()).await?;
+            remove_port_file(&state.config);
+            result??;
+            return Ok(());
+        }
+    }
 
+    // Requests in flight finish within the drain period along with transfers
+    let grace = state.config.shutdown_grace();
+    tracing::info!("Shutting down, draining for up to {}s", grace.as_secs());
+    let started = tokio::time::Instant::now();
+    if tokio::time::timeout(grace, &mut http).await.is_err() {
+        tracing::warn!("HTTP connections still open after the drain period");
+        http.abort();
+    }
+    server::shutdown::drain(&state, grace.saturating_sub(started.elapsed())).await?;
+    remove_port_file(&state.config);
+
+    tracing::info!("BridgeX backend stopped");
     Ok(())
 }
+
+/// Remove the port file so nobody connects to a stopped server
+fn remove_port_file(config: &Config) {
+    if let Some(path) = &config.server.port_file {
+        if let Err(e) = std::fs::remove_file(path) {
+            tracing::warn!("Failed to remove port file {}: {}", path.display(), e);
+        }
+    }
+}
@@ -0,0 +1,407 @@
+//! Pairing URI format
+//!
+//! The pairing QR code carries a versioned `bridgex://pair?...` URI:
+//!
+//! ```text
+//! bridgex://pair?v=1&id={device_id}&key={public_key}&token={token}
+//!     &addr={host:port}&addr=...&exp={unix_seconds}&fp={fingerprint}[&tls=1]
+//! ```
+//!
+//! `token` is the device's secret: the server stores only its hash and the
+//! device proves it holds the token when opening a transfer session. The
+//! first such session must happen before `exp`, after which an unused token
+//! is refused.
+//!
+//! `tls=1` means the HTTP API at the addresses speaks HTTPS with the
+//! certificate `fp`; clients pin it instead of validating against a CA.
+//! Without it the API is plain HTTP.
+//!
+//! Binary fields (`key`, `token`) use unpadded URL-safe base64 and every
+//! value is form-urlencoded, so the URI survives scanners and deep-link
+//! handlers unchanged. Parsing is strict: unknown, duplicate or malformed
+//! parameters are rejected rather than guessed at.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, TimeZone, Utc};
+use std::net::SocketAddr;
+use thiserror::Error;
+use url::Url;
+
+/// URI scheme of pairing links
+pub const PAIRING_SCHEME: &str = "bridgex";
+
+/// Current pairing payload version
+pub const PAIRING_VERSION: u32 = 1;
+
+/// Length of an X25519 public key
+pub const PUBLIC_KEY_LEN: usize = 32;
+
+/// Minimum length of the pairing token
+pub const MIN_TOKEN_LEN: usize = 16;
+
+/// Maximum length of a device id
+pub const MAX_DEVICE_ID_LEN: usize = 64;
+
+/// Maximum number of advertised addresses
+pub const MAX_ADDRESSES: usize = 16;
+
+/// Errors returned when a pairing URI fails validation
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PairingUriError {
+    #[error("not a valid URI: {0}")]
+    InvalidUri(String),
+
+    #[error("expected bridgex://pair, got {0}")]
+    NotPairingUri(String),
+
+    #[error("unsupported pairing version {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("missing parameter `{0}`")]
+    MissingField(&'static str),
+
+    #[error("duplicate parameter `{0}`")]
+    DuplicateField(&'static str),
+
+    #[error("unknown parameter `{0}`")]
+    UnknownField(String),
+
+    #[error("invalid parameter `{field}`: {reason}")]
+    InvalidField {
+        field: &'static str,
+        reason: &'static str,
+    },
+}
+
+/// Everything a client needs to pair with this server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PairingPayload {
+    pub version: u32,
+    pub device_id: String,
+    pub public_key: [u8; PUBLIC_KEY_LEN],
+    /// Secret proving the client scanned this code
+    ///
+    /// The server keeps only its hash. The device authenticates with it on
+    /// the transfer channels, the first time before `expires_at`.
+    pub token: Vec<u8>,
+    /// Addresses the server is reachable on, in order of preference
+    pub addresses: Vec<SocketAddr>,
+    pub expires_at: DateTime<Utc>,
+    /// Hex SHA-256 fingerprint of the server certificate
+    pub server_fingerprint: String,
+    /// Whether the HTTP API is served over TLS
+    pub tls: bool,
+}
+
+impl PairingPayload {
+    /// Create a payload of the current version
+    pub fn new(
+        device_id: String,
+        public_key: [u8; PUBLIC_KEY_LEN],
+        token: Vec<u8>,
+        addresses: Vec<SocketAddr>,
+        expires_at: DateTime<Utc>,
+        server_fingerprint: String,
+    ) -> Self {
+        Self {
+            version: PAIRING_VERSION,
+            device_id,
+            public_key,
+            token,
+            addresses,
+            // The URI carries whole seconds
+            expires_at: Utc
+                .timestamp_opt(expires_at.timestamp(), 0)
+                .single()
+                .unwrap_or(expires_at),
+            server_fingerprint,
+            tls: false,
+        }
+    }
+
+    /// Mark the HTTP API as served over TLS
+    pub fn with_tls(mut self, tls: bool) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Base URL of the HTTP API at `addr`
+    pub fn api_url(&self, addr: SocketAddr) -> String {
+        let scheme = if self.tls { "https" } else { "http" };
+        format!("{}://{}", scheme, addr)
+    }
+
+    /// Whether the pairing window has closed
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Encode the payload as a `bridgex://pair` URI
+    pub fn to_uri(&self) -> String {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        query.append_pair("v", &self.version.to_string());
+        query.append_pair("id", &self.device_id);
+        query.append_pair("key", &URL_SAFE_NO_PAD.encode(self.public_key));
+        query.append_pair("token", &URL_SAFE_NO_PAD.encode(&self.token));
+        for addr in &self.addresses {
+            query.append_pair("addr", &addr.to_string());
+        }
+        query.append_pair("exp", &self.expires_at.timestamp().to_string());
+        query.append_pair("fp", &self.server_fingerprint);
+        if self.tls {
+            query.append_pair("tls", "1");
+        }
+
+        format!("{}://pair?{}", PAIRING_SCHEME, query.finish())
+    }
+}
+
+/// Parse and validate a pairing URI
+///
+/// # Arguments
+/// * `uri` - URI scanned from the pairing QR code
+///
+/// # Returns
+/// The decoded payload, or the first validation error found
+pub fn parse_pairing_uri(uri: &str) -> Result<PairingPayload, PairingUriError> {
+    let url = Url::parse(uri).map_err(|e| PairingUriError::InvalidUri(e.to_string()))?;
+    if url.scheme() != PAIRING_SCHEME || url.host_str() != Some("pair") {
+        return Err(PairingUriError::NotPairingUri(format!(
+            "{}://{}",
+            url.scheme(),
+            url.host_str().unwrap_or_default()
+        )));
+    }
+
+    let mut version = None;
+    let mut device_id = None;
+    let mut public_key = None;
+    let mut token = None;
+    let mut addresses = Vec::new();
+    let mut expires_at = None;
+    let mut server_fingerprint = None;
+    let mut tls = None;
+
+    for (name, value) in url.query_pairs() {
+        match name.as_ref() {
+            "v" => set_once(&mut version, "v", parse_version(&value)?)?,
+            "id" => set_once(&mut device_id, "id", parse_device_id(&value)?)?,
+            "key" => set_once(&mut public_key, "key", parse_public_key(&value)?)?,
+            "token" => set_once(&mut token, "token", parse_token(&value)?)?,
+            "addr" => {
+                if addresses.len() == MAX_ADDRESSES {
+                    return Err(invalid("addr", "too many addresses"));
+                }
+                addresses.push(
+                    value
+                        .parse::<SocketAddr>()
+                        .map_err(|_| invalid("addr", "expected host:port"))?,
+                );
+            }
+            "exp" => set_once(&mut expires_at, "exp", parse_expiry(&value)?)?,
+            "fp" => set_once(&mut server_fingerprint, "fp", parse_fingerprint(&value)?)?,
+            "tls" => set_once(&mut tls, "tls", parse_flag("tls", &value)?)?,
+            other => return Err(PairingUriError::UnknownField(other.to_string())),
+        }
+    }
+
+    Ok(PairingPayload {
+        version: version.ok_or(PairingUriError::MissingField("v"))?,
+        device_id: device_id.ok_or(PairingUriError::MissingField("id"))?,
+        public_key: public_key.ok_or(PairingUriError::MissingField("key"))?,
+        token: token.ok_or(PairingUriError::MissingField("token"))?,
+        addresses,
+        expires_at: expires_at.ok_or(PairingUriError::MissingField("exp"))?,
+        server_fingerprint: server_fingerprint.ok_or(PairingUriError::MissingField("fp"))?,
+        tls: tls.unwrap_or(false),
+    })
+}
+
+fn invalid(field: &'static str, reason: &'static str) -> PairingUriError {
+    PairingUriError::InvalidField { field, reason }
+}
+
+fn set_once<T>(slot: &mut Option<T>, field: &'static str, value: T) -> Result<(), PairingUriError> {
+    if slot.replace(value).is_some() {
+        return Err(PairingUriError::DuplicateField(field));
+    }
+    Ok(())
+}
+
+fn parse_version(value: &str) -> Result<u32, PairingUriError> {
+    let version: u32 = value
+        .parse()
+        .map_err(|_| invalid("v", "expected an integer"))?;
+    if version != PAIRING_VERSION {
+        return Err(PairingUriError::UnsupportedVersion(version));
+    }
+    Ok(version)
+}
+
+fn parse_device_id(value: &str) -> Result<String, PairingUriError> {
+    if value.is_empty() || value.len() > MAX_DEVICE_ID_LEN {
+        return Err(invalid("id", "must be 1 to 64 characters"));
+    }
+    if !value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(invalid(
+            "id",
+            "only letters, digits, '-' and '_' are allowed",
+        ));
+    }
+    Ok(value.to_string())
+}
+
+fn parse_public_key(value: &str) -> Result<[u8; PUBLIC_KEY_LEN], PairingUriError> {
+    URL_SAFE_NO_PAD
+        .decode(value)
+        .map_err(|_| invalid("key", "expected URL-safe base64"))?
+        .try_into()
+        .map_err(|_| invalid("key", "expected a 32-byte key"))
+}
+
+fn parse_token(value: &str) -> Result<Vec<u8>, PairingUriError> {
+    let token = URL_SAFE_NO_PAD
+        .decode(value)
+        .map_err(|_| invalid("token", "expected URL-safe base64"))?;
+    if token.len() < MIN_TOKEN_LEN {
+        return Err(invalid("token", "too short"));
+    }
+    Ok(token)
+}
+
+fn parse_expiry(value: &str) -> Result<DateTime<Utc>, PairingUriError> {
+    let seconds: i64 = value
+        .parse()
+        .map_err(|_| invalid("exp", "expected unix seconds"))?;
+    Utc.timestamp_opt(seconds, 0)
+        .single()
+        .filter(|_| seconds > 0)
+        .ok_or(invalid("exp", "out of range"))
+}
+
+fn parse_fingerprint(value: &str) -> Result<String, PairingUriError> {
+    if value.len() != 64 || !value.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')) {
+        return Err(invalid("fp", "expected 64 lowercase hex characters"));
+    }
+    Ok(value.to_string())
+}
+
+fn parse_flag(field: &'static str, value: &str) -> Result<bool, PairingUriError> {
+    match value {
+        "1" => Ok(true),
+        "0" => Ok(false),
+        _ => Err(invalid(field, "expected 0 or 1")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload() -> PairingPayload {
+        PairingPayload::new(
+            "7f9c2ba4-e88f-4a1d-9d3c-0123456789ab".to_string(),
+            [0xfb; PUBLIC_KEY_LEN],
+            vec![0xff; MIN_TOKEN_LEN],
+            vec![
+                "192.168.1.5:8080".parse().unwrap(),
+                "[fe80::1]:8080".parse().unwrap(),
+            ],
+            Utc.timestamp_opt(1_900_000_000, 0).unwrap(),
+            "ab".repeat(32),
+        )
+    }
+
+    #[test]
+    fn test_uri_is_url_safe() {
+        let uri = payload().to_uri();
+        assert!(uri.starts_with("bridgex://pair?v=1&id="));
+        // 0xfb bytes encode to '+' and '/' in standard base64
+        let query = uri.split_once('?').unwrap().1;
+        assert!(!query.contains('+') && !query.contains('/') && !query.contains('['));
+        assert_eq!(parse_pairing_uri(&uri).unwrap(), payload());
+    }
+
+    #[test]
+    fn test_rejects_other_uris() {
+        assert!(matches!(
+            parse_pairing_uri("not a uri"),
+            Err(PairingUriError::InvalidUri(_))
+        ));
+        assert!(matches!(
+            parse_pairing_uri("https://pair?v=1"),
+            Err(PairingUriError::NotPairingUri(_))
+        ));
+        assert!(matches!(
+            parse_pairing_uri("bridgex://unpair?v=1"),
+            Err(PairingUriError::NotPairingUri(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_invalid_fields() {
+        let uri = payload().to_uri();
+
+        assert_eq!(
+            parse_pairing_uri(&uri.replace("v=1", "v=2")),
+            Err(PairingUriError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            parse_pairing_uri(&format!("{}&v=1", uri)),
+            Err(PairingUriError::DuplicateField("v"))
+        );
+        assert_eq!(
+            parse_pairing_uri(&format!("{}&extra=1", uri)),
+            Err(PairingUriError::UnknownField("extra".to_string()))
+        );
+        assert_eq!(
+            parse_pairing_uri(&uri.replace(&"ab".repeat(32), "abc")),
+            Err(invalid("fp", "expected 64 lowercase hex characters"))
+        );
+        assert_eq!(
+            parse_pairing_uri(&uri.replace("exp=1900000000", "exp=soon")),
+            Err(invalid("exp", "expected unix seconds"))
+        );
+    }
+
+    #[test]
+    fn test_rejects_missing_fields() {
+        let uri = payload().to_uri();
+        let without_token: Vec<&str> = uri
+            .split('&')
+            .filter(|p| !p.starts_with("token="))
+            .collect();
+        assert_eq!(
+            parse_pairing_uri(&without_token.join("&")),
+            Err(PairingUriError::MissingField("token"))
+        );
+    }
+
+    #[test]
+    fn test_tls_flag() {
+        let payload = payload().with_tls(true);
+        let uri = payload.to_uri();
+        assert!(uri.ends_with("&tls=1"));
+        assert_eq!(parse_pairing_uri(&uri).unwrap(), payload);
+        assert_eq!(
+            payload.api_url(payload.addresses[1]),
+            "https://[fe80::1]:8080"
+        );
+
+        assert!(!payload.clone().with_tls(false).to_uri().contains("tls="));
+        assert_eq!(
+            parse_pairing_uri(&uri.replace("tls=1", "tls=yes")),
+            Err(invalid("tls", "expected 0 or 1"))
+        );
+    }
+
+    #[test]
+    fn test_expiry() {
+        let payload = payload();
+        assert!(!payload.is_expired(payload.expires_at - chrono::Duration::seconds(1)));
+        assert!(payload.is_expired(payload.expires_at));
+    }
+}
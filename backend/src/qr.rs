@@ -2,7 +2,9 @@
 
 use anyhow::Result;
 use base64::{engine::general_purpose, Engine as _};
-use qrcode::{QrCode, render::svg};
+use qrcode::{Color, QrCode, render::svg};
+
+use crate::pairing::PairingPayload;
 
 /// Generate QR code as SVG string
 ///
@@ -50,22 +52,85 @@ pub fn generate_qr_data_url(data: &str) -> Result<String> {
     Ok(format!("data:image/png;base64,{}", base64_data))
 }
 
+/// Options of [`generate_qr_terminal`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalQrOptions {
+    /// Draw dark modules instead of light ones, for light terminal backgrounds
+    pub invert: bool,
+    /// Width of the light border around the code, in modules
+    pub quiet_zone: u32,
+}
+
+impl Default for TerminalQrOptions {
+    /// Suits the usual dark terminal; most scanners accept a 2-module border
+    fn default() -> Self {
+        Self {
+            invert: false,
+            quiet_zone: 2,
+        }
+    }
+}
+
+/// Generate QR code as Unicode half-block text for terminals
+///
+/// Each character covers two rows of modules using `▀`, `▄`, `█` and
+/// space, so the code stays roughly square and compact. The terminal
+/// background shows through for modules that are not drawn.
+///
+/// # Arguments
+/// * `data` - Data to encode in QR code
+/// * `options` - Colors and border width
+///
+/// # Returns
+/// Lines of text separated by `\n`, without a trailing newline
+pub fn generate_qr_terminal(data: &str, options: TerminalQrOptions) -> Result<String> {
+    let code = QrCode::new(data.as_bytes())?;
+    let width = code.width();
+    let colors = code.to_colors();
+    let quiet = options.quiet_zone as usize;
+    let size = width + 2 * quiet;
+
+    // Whether the module at (x, y) of the bordered code is drawn
+    let drawn = |x: usize, y: usize| {
+        let light = x < quiet
+            || y < quiet
+            || x >= quiet + width
+            || y >= quiet + width
+            || colors[(y - quiet) * width + (x - quiet)] == Color::Light;
+        light != options.invert
+    };
+
+    let lines: Vec<String> = (0..size)
+        .step_by(2)
+        .map(|y| {
+            (0..size)
+                .map(|x| {
+                    let top = drawn(x, y);
+                    let bottom = y + 1 < size && drawn(x, y + 1);
+                    match (top, bottom) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
 /// Generate pairing QR code data URI
 ///
-/// Format: bridgex://pair?id={device_id}&key={public_key_base64}
+/// The QR code encodes the versioned pairing URI, see [`crate::pairing`].
 ///
 /// # Arguments
-/// * `device_id` - Unique device identifier
-/// * `public_key` - Public key bytes
+/// * `payload` - Pairing payload to encode
 ///
 /// # Returns
 /// QR code data URI and the encoded string
-pub fn generate_pairing_qr(
-    device_id: &str,
-    public_key: &[u8],
-) -> Result<(String, String)> {
-    let key_b64 = general_purpose::STANDARD.encode(public_key);
-    let pairing_uri = format!("bridgex://pair?id={}&key={}", device_id, key_b64);
+pub fn generate_pairing_qr(payload: &PairingPayload) -> Result<(String, String)> {
+    let pairing_uri = payload.to_uri();
     let qr_data_url = generate_qr_data_url(&pairing_uri)?;
     Ok((qr_data_url, pairing_uri))
 }
@@ -89,12 +154,92 @@ mod tests {
 
     #[test]
     fn test_generate_pairing_qr() {
-        let device_id = "test-device-123";
-        let public_key = vec![1, 2, 3, 4, 5];
-        let (qr_url, pairing_uri) = generate_pairing_qr(device_id, &public_key).unwrap();
-        
+        let payload = PairingPayload::new(
+            "test-device-123".to_string(),
+            [7; 32],
+            vec![9; 32],
+            vec![
+                "192.168.1.5:8080".parse().unwrap(),
+                "[fe80::1]:8080".parse().unwrap(),
+            ],
+            chrono::Utc::now() + chrono::Duration::minutes(5),
+            "ab".repeat(32),
+        );
+        let (qr_url, pairing_uri) = generate_pairing_qr(&payload).unwrap();
+
         assert!(qr_url.starts_with("data:image/png;base64,"));
-        assert!(pairing_uri.contains("bridgex://pair"));
-        assert!(pairing_uri.contains(device_id));
+        assert!(pairing_uri.starts_with("bridgex://pair?v=1&id=test-device-123"));
+        assert_eq!(crate::pairing::parse_pairing_uri(&pairing_uri).unwrap(), payload);
+    }
+
+    /// Decode half-block text back into rows of drawn modules
+    fn modules(text: &str) -> Vec<Vec<bool>> {
+        let mut rows = Vec::new();
+        for line in text.lines() {
+            let (top, bottom): (Vec<bool>, Vec<bool>) = line
+                .chars()
+                .map(|c| match c {
+                    '█' => (true, true),
+                    '▀' => (true, false),
+                    '▄' => (false, true),
+                    ' ' => (false, false),
+                    other => panic!("unexpected character {:?}", other),
+                })
+                .unzip();
+            rows.push(top);
+            rows.push(bottom);
+        }
+        rows
+    }
+
+    #[test]
+    fn test_generate_qr_terminal() {
+        let code = QrCode::new(b"test data").unwrap();
+        let width = code.width();
+        let colors = code.to_colors();
+
+        for quiet_zone in [0, 1, 4] {
+            let options = TerminalQrOptions {
+                invert: false,
+                quiet_zone,
+            };
+            let text = generate_qr_terminal("test data", options).unwrap();
+            let size = width + 2 * quiet_zone as usize;
+            assert_eq!(text.lines().count(), size.div_ceil(2));
+            assert!(text.lines().all(|line| line.chars().count() == size));
+
+            // Light modules and the border are drawn
+            let rows = modules(&text);
+            let q = quiet_zone as usize;
+            for y in 0..size {
+                for x in 0..size {
+                    let inside = (q..q + width).contains(&x) && (q..q + width).contains(&y);
+                    let light = !inside || colors[(y - q) * width + (x - q)] == Color::Light;
+                    assert_eq!(rows[y][x], light, "module ({}, {})", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_qr_terminal_inverted() {
+        let normal = generate_qr_terminal("test data", TerminalQrOptions::default()).unwrap();
+        let inverted = generate_qr_terminal(
+            "test data",
+            TerminalQrOptions {
+                invert: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let (normal, inverted) = (modules(&normal), modules(&inverted));
+        // The padding row below an odd-sized code is never drawn
+        let size = normal[0].len();
+        for y in 0..size {
+            for x in 0..size {
+                assert_ne!(normal[y][x], inverted[y][x]);
+            }
+        }
     }
 }
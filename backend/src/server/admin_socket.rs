@@ -0,0 +1,145 @@
+//! Admin API on a Unix domain socket
+//!
+//! The desktop app reaches the admin API through a socket rather than the
+//! loopback port: there is no port to collide with, and only the user
+//! running the server may connect. The socket
+//! ([`Config::admin_socket_path`](crate::Config::admin_socket_path)) is
+//! created with mode `0600` in a directory owned by that user with no
+//! access for anyone else, and removed on shutdown. Requests on it are
+//! plain HTTP/1.1; the file permissions take the place of TLS.
+
+use axum::{extract::ConnectInfo, http::Request, Router};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper_util::rt::TokioIo;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use std::fs::{DirBuilder, Permissions};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UnixListener;
+use tower::ServiceExt;
+
+use crate::Shutdown;
+
+/// Permissions of the socket: read and write for the owner only
+const SOCKET_MODE: u32 = 0o600;
+
+/// Permissions of a socket directory created by the server
+const DIR_MODE: u32 = 0o700;
+
+/// Pause after a failed accept, e.g. when out of file descriptors
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+
+/// Create the admin socket at `path`
+///
+/// Creates the parent directory if missing, replaces a stale socket left by
+/// a server that was killed, and refuses to start when another server is
+/// still listening, when `path` is not a socket, or when the directory is
+/// owned by another user or open to other users. Otherwise another user
+/// could create the directory first and swap the socket for their own.
+pub fn bind(path: &Path) -> io::Result<UnixListener> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        if !dir.exists() {
+            DirBuilder::new()
+                .recursive(true)
+                .mode(DIR_MODE)
+                .create(dir)?;
+        }
+        let metadata = dir.metadata()?;
+        // SAFETY: geteuid has no preconditions and cannot fail
+        if metadata.uid() != unsafe { libc::geteuid() } {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is owned by another user", dir.display()),
+            ));
+        }
+        if metadata.permissions().mode() & 0o077 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "{} must only be accessible by its owner (mode 0700)",
+                    dir.display()
+                ),
+            ));
+        }
+    }
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if !metadata.file_type().is_socket() => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        Ok(_) => {
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is in use by another server", path.display()),
+                ));
+            }
+            std::fs::remove_file(path)?;
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, Permissions::from_mode(SOCKET_MODE))?;
+    Ok(listener)
+}
+
+/// Serve `app` on the socket at `path` until shutdown starts
+///
+/// Completes once the open connections have closed, then removes the
+/// socket. Handlers see peers as a loopback `ConnectInfo<SocketAddr>`, so
+/// they are rate limited like other local clients.
+pub async fn serve(
+    listener: UnixListener,
+    path: PathBuf,
+    app: Router,
+    shutdown: Arc<Shutdown>,
+) -> io::Result<()> {
+    let peer = SocketAddr::from((Ipv4Addr::LOCALHOST, 0));
+    let graceful = GracefulShutdown::new();
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!("Failed to accept admin socket connection: {}", e);
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            },
+            _ = shutdown.triggered() => break,
+        };
+
+        let service = app
+            .clone()
+            .map_request(move |mut request: Request<Incoming>| {
+                request.extensions_mut().insert(ConnectInfo(peer));
+                request
+            });
+        let connection = http1::Builder::new()
+            .serve_connection(TokioIo::new(stream), TowerToHyperService::new(service));
+        let connection = graceful.watch(connection);
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::debug!("Admin socket connection failed: {}", e);
+            }
+        });
+    }
+
+    drop(listener);
+    if let Err(e) = std::fs::remove_file(&path) {
+        tracing::warn!("Failed to remove {}: {}", path.display(), e);
+    }
+    graceful.shutdown().await;
+    Ok(())
+}
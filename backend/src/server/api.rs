@@ -3,15 +3,25 @@
 use axum::{
     extract::{Path, State},
     http::StatusCode,
-    response::{IntoResponse, Response},
+    response::IntoResponse,
     Json,
 };
+use base64::{engine::general_purpose, Engine as _};
+use rand::RngCore;
 use serde_json::json;
 use uuid::Uuid;
 
-use super::{PairRequest, PairResponse, TransferRequest, TransferResponse};
+use super::audit::{self, Actor, AuditKind};
+use super::auth::DeviceAuth;
+use super::error::{ApiJson, AppError};
+use super::metrics::refresh as refresh_metrics;
+use super::presence::{with_presence, PresenceCounts};
+use super::transfers::TRANSFER_STATUSES;
+use super::upload;
+use super::{PairRequest, PairResponse, TransferRequest, TransferResponse, UpdateDeviceRequest};
 use crate::crypto::keys::generate_keypair;
-use crate::db::models::{Device, Transfer};
+use crate::db::models::{Device, DeviceUpdate, Transfer};
+use crate::pairing::PairingPayload;
 use crate::qr::generate_pairing_qr;
 use crate::AppState;
 
@@ -28,45 +38,108 @@ pub async fn health() -> impl IntoResponse {
 /// Device pairing endpoint
 pub async fn pair(
     State(state): State<AppState>,
-    Json(payload): Json<PairRequest>,
+    ApiJson(payload): ApiJson<PairRequest>,
 ) -> Result<Json<PairResponse>, AppError> {
-    tracing::info!("Pairing request from device: {}", payload.device_name);
+    state.metrics.pairing_attempts.inc();
+    let (device, pairing) = create_pairing(&state, payload, &Actor::Api)
+        .await
+        .inspect_err(|_| state.metrics.pairing_failures.inc())?;
+    let (qr_data_url, pairing_uri) = generate_pairing_qr(&pairing)
+        .map_err(|e| AppError::Internal(e.context("QR generation failed")))?;
+
+    // The URI carries the pairing token, so only its public parts are logged
+    tracing::debug!(
+        "Pairing QR code for device {} expires at {}",
+        device.id,
+        pairing.expires_at
+    );
+
+    Ok(Json(PairResponse {
+        public_key: general_purpose::STANDARD.encode(&device.public_key),
+        device_id: device.id,
+        server_fingerprint: state.certificate.fingerprint(),
+        qr_data: qr_data_url,
+        pairing_uri,
+        expires_at: pairing.expires_at,
+    }))
+}
+
+/// Register a device and build the pairing payload for its QR code
+///
+/// Shared by the pairing endpoint and the `pair` command, which pass
+/// themselves as the `actor` recorded in the audit log.
+///
+/// # Returns
+/// The saved device and the pairing payload
+pub async fn create_pairing(
+    state: &AppState,
+    request: PairRequest,
+    actor: &Actor,
+) -> Result<(Device, PairingPayload), AppError> {
+    let device_name = request.device_name.trim();
+    if device_name.is_empty() || device_name.chars().count() > MAX_NAME_LEN {
+        return Err(AppError::validation(format!(
+            "`device_name` must be 1 to {} characters",
+            MAX_NAME_LEN
+        )));
+    }
+
+    tracing::info!(
+        "Pairing request from {} device: {}",
+        request.device_type,
+        device_name
+    );
 
     let keypair = generate_keypair();
     let device_id = Uuid::new_v4().to_string();
 
-    // Generate QR code with pairing information
-    let (qr_data_url, pairing_uri) = generate_pairing_qr(&device_id, &keypair.public_key)
-        .map_err(|e| anyhow::anyhow!("QR generation failed: {}", e))?;
+    let expires_at = chrono::Utc::now() + state.config.pairing_expiry();
 
-    // Save device to database
-    let device = Device::new(
+    // Secret proving the client scanned this code
+    let mut token = vec![0u8; 32];
+    rand::thread_rng().fill_bytes(&mut token);
+    let pairing = PairingPayload::new(
         device_id.clone(),
-        payload.device_name,
-        "desktop".to_string(),
-        keypair.public_key.to_vec(),
-    );
-    
-    state.db.save_device(&device).await
-        .map_err(|e| anyhow::anyhow!("Database error: {}", e))?;
+        keypair.public_key,
+        token.clone(),
+        state.discovery.local_addrs().await,
+        expires_at,
+        state.certificate.fingerprint(),
+    )
+    .with_tls(state.config.tls_enabled());
 
-    let expires_at = chrono::Utc::now() + chrono::Duration::minutes(5);
+    // Save device to database
+    let mut device = Device::new(
+        device_id,
+        device_name.to_string(),
+        request.device_type,
+        keypair.public_key.to_vec(),
+    )
+    .with_pairing_token(&token, pairing.expires_at);
+    device.platform = request.platform;
+    device.os_version = request.os_version;
+    device.app_version = request.app_version;
 
-    tracing::info!("Generated pairing for device_id: {}", device_id);
-    tracing::debug!("Pairing URI: {}", pairing_uri);
+    state.db.save_device(&device).await?;
+    audit::record(
+        &state.db,
+        AuditKind::DevicePaired.event(actor).device(&device.id).details(json!({
+            "name": device.name,
+            "type": device.device_type,
+            "platform": device.platform,
+        })),
+    )
+    .await;
 
-    Ok(Json(PairResponse {
-        device_id,
-        public_key: base64::encode(&keypair.public_key),
-        qr_data: qr_data_url,
-        expires_at,
-    }))
+    tracing::info!("Generated pairing for device_id: {}", device.id);
+    Ok((device, pairing))
 }
 
 /// Initialize file transfer
 pub async fn transfer_init(
     State(state): State<AppState>,
-    Json(payload): Json<TransferRequest>,
+    auth: DeviceAuth,
+    ApiJson(payload): ApiJson<TransferRequest>,
 ) -> Result<Json<TransferResponse>, AppError> {
     tracing::info!(
         "Transfer request: {} ({} bytes) to device {}",
@@ -75,12 +148,16 @@ pub async fn transfer_init(
         payload.device_id
     );
 
+    if upload::parse_file_hash(&payload.file_hash).is_none() {
+        return Err(AppError::validation(
+            "`file_hash` must be `sha256:` followed by 64 hex digits",
+        ));
+    }
+
+    auth.authorize(&payload.device_id)?;
     // Verify device exists
-    let device = state.db.get_device(&payload.device_id).await
-        .map_err(|e| anyhow::anyhow!("Database error: {}", e))?;
-    
-    if device.is_none() {
-        return Err(AppError(anyhow::anyhow!("Device not found")));
+    if state.db.get_device(&payload.device_id).await?.is_none() {
+        return Err(AppError::not_found("Device"));
     }
 
     let transfer_id = Uuid::new_v4().to_string();
@@ -95,8 +172,19 @@ pub async fn transfer_init(
         payload.file_hash,
     );
     
-    state.db.save_transfer(&transfer).await
-        .map_err(|e| anyhow::anyhow!("Database error: {}", e))?;
+    state.db.save_transfer(&transfer).await?;
+    audit::record(
+        &state.db,
+        AuditKind::TransferStarted
+            .event(&Actor::Device(transfer.device_id.clone()))
+            .device(&transfer.device_id)
+            .transfer(&transfer.id)
+            .details(json!({
+                "file_name": transfer.file_name,
+                "file_size": transfer.file_size,
+            })),
+    )
+    .await;
 
     Ok(Json(TransferResponse {
         transfer_id,
@@ -106,24 +194,165 @@ pub async fn transfer_init(
 }
 
 /// Server status endpoint
+///
+/// Counts come from the same registry as `/metrics`.
 pub async fn status(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
-    let devices = state.db.get_devices().await
-        .map_err(|e| anyhow::anyhow!("Database error: {}", e))?;
-    
+    refresh_metrics(&state).await?;
+    let devices = state.db.get_devices().await?;
+    let devices = with_presence(&state, devices).await;
+
+    let metrics = &state.metrics;
+    let transfers: serde_json::Map<String, serde_json::Value> = TRANSFER_STATUSES
+        .iter()
+        .map(|status| {
+            let count = metrics.transfers.with_label_values(&[status]).get();
+            (status.to_string(), json!(count))
+        })
+        .collect();
+
     Ok(Json(json!({
-        "uptime": "running",
-        "active_connections": 0,
-        "pending_transfers": 0,
-        "paired_devices": devices.len(),
+        "uptime_secs": metrics.uptime().as_secs(),
+        "started_at": metrics.started_at(),
+        "active_connections": metrics.active_connections.get(),
+        "active_transfers": metrics.active_transfers.get(),
+        "pending_transfers": metrics.transfers.with_label_values(&["pending"]).get(),
+        "transfers": transfers,
+        "transfers_completed": metrics.transfers_completed(),
+        "bytes_received": metrics.bytes_received_total(),
+        "chunks_received": metrics.chunks_received_total(),
+        "paired_devices": metrics.paired_devices.get(),
+        "presence": PresenceCounts::from_devices(&devices),
     })))
 }
 
 /// List all paired devices
 pub async fn list_devices(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
-    let devices = state.db.get_devices().await
-        .map_err(|e| anyhow::anyhow!("Database error: {}", e))?;
+    let devices = state.db.get_devices().await?;
     
-    Ok(Json(devices))
+    Ok(Json(with_presence(&state, devices).await))
+}
+
+/// Longest accepted device name
+pub const MAX_NAME_LEN: usize = 64;
+
+/// Longest accepted nickname, icon identifier or tag
+pub const MAX_LABEL_LEN: usize = 32;
+
+/// Most tags a device can carry
+pub const MAX_TAGS: usize = 16;
+
+/// Validate a device edit request
+///
+/// Text is trimmed; an empty nickname, icon or color clears the field.
+///
+/// # Returns
+/// The changes to apply, or a validation error naming the first invalid field
+pub fn validate_device_update(request: UpdateDeviceRequest) -> Result<DeviceUpdate, AppError> {
+    fn label(
+        value: Option<Option<String>>,
+        field: &str,
+    ) -> Result<Option<Option<String>>, AppError> {
+        Ok(match value {
+            Some(Some(text)) => {
+                let text = text.trim();
+                if text.chars().count() > MAX_LABEL_LEN {
+                    return Err(AppError::validation(format!(
+                        "`{}` must be at most {} characters",
+                        field, MAX_LABEL_LEN
+                    )));
+                }
+                Some((!text.is_empty()).then(|| text.to_string()))
+            }
+            other => other,
+        })
+    }
+
+    let name = match request.name {
+        Some(name) => {
+            let name = name.trim();
+            if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+                return Err(AppError::validation(format!(
+                    "`name` must be 1 to {} characters",
+                    MAX_NAME_LEN
+                )));
+            }
+            Some(name.to_string())
+        }
+        None => None,
+    };
+
+    let nickname = label(request.nickname, "nickname")?;
+
+    let icon = label(request.icon, "icon")?;
+    if let Some(Some(icon)) = &icon {
+        if !icon.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+            return Err(AppError::validation("`icon` may only contain a-z, 0-9 and '-'"));
+        }
+    }
+
+    let color = label(request.color, "color")?;
+    if let Some(Some(color)) = &color {
+        let hex = color.strip_prefix('#').unwrap_or_default();
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(AppError::validation("`color` must be formatted as #rrggbb"));
+        }
+    }
+    let color = color.map(|c| c.map(|c| c.to_ascii_lowercase()));
+
+    let tags = match request.tags {
+        Some(tags) => {
+            if tags.len() > MAX_TAGS {
+                return Err(AppError::validation(format!(
+                    "at most {} tags are allowed",
+                    MAX_TAGS
+                )));
+            }
+            let mut cleaned: Vec<String> = Vec::with_capacity(tags.len());
+            for tag in tags {
+                let tag = tag.trim();
+                if tag.is_empty() || tag.chars().count() > MAX_LABEL_LEN {
+                    return Err(AppError::validation(format!(
+                        "tags must be 1 to {} characters",
+                        MAX_LABEL_LEN
+                    )));
+                }
+                if cleaned.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                    return Err(AppError::validation(format!("duplicate tag `{}`", tag)));
+                }
+                cleaned.push(tag.to_string());
+            }
+            Some(cleaned)
+        }
+        None => None,
+    };
+
+    Ok(DeviceUpdate {
+        name,
+        nickname,
+        icon,
+        color,
+        tags,
+        favorite: request.favorite,
+    })
+}
+
+/// Rename a device or edit its labels
+pub async fn update_device(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    ApiJson(payload): ApiJson<UpdateDeviceRequest>,
+) -> Result<Json<Device>, AppError> {
+    let update = validate_device_update(payload)?;
+
+    let device = state
+        .db
+        .update_device(&device_id, &update)
+        .await?
+        .ok_or_else(|| AppError::not_found("Device"))?;
+    audit::record(&state.db, audit::device_updated(&Actor::Api, &device_id, &update)).await;
+
+    tracing::info!("Updated device {}", device_id);
+    Ok(Json(device))
 }
 
 /// Delete a device
@@ -133,34 +362,14 @@ pub async fn delete_device(
 ) -> Result<impl IntoResponse, AppError> {
     tracing::info!("Deleting device: {}", device_id);
     
-    state.db.delete_device(&device_id).await
-        .map_err(|e| anyhow::anyhow!("Database error: {}", e))?;
-    
-    Ok(StatusCode::NO_CONTENT)
-}
-
-/// Application error type
-#[derive(Debug)]
-pub struct AppError(anyhow::Error);
-
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        tracing::error!("API error: {:?}", self.0);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "error": self.0.to_string(),
-            })),
-        )
-            .into_response()
+    if !state.db.delete_device(&device_id).await? {
+        return Err(AppError::not_found("Device"));
     }
-}
+    audit::record(
+        &state.db,
+        AuditKind::DeviceRevoked.event(&Actor::Api).device(&device_id),
+    )
+    .await;
 
-impl<E> From<E> for AppError
-where
-    E: Into<anyhow::Error>,
-{
-    fn from(err: E) -> Self {
-        Self(err.into())
-    }
+    Ok(StatusCode::NO_CONTENT)
 }
@@ -0,0 +1,427 @@
+//! Audit log
+//!
+//! Pairing, verification, revocation, session creation, transfers and
+//! configuration changes append an event to the `audit_events` table. Events
+//! are never updated or deleted, and each one carries the hash of the
+//! previous event, so tampering with the table breaks the chain reported by
+//! `GET /api/v1/audit/verify` and `bridgex-server audit verify`.
+//!
+//! Recording is best effort: a failed append is logged and does not fail the
+//! operation being audited.
+
+use axum::{extract::State, http::header, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Write;
+
+use super::error::{ApiQuery, AppError};
+use super::metrics::Transport;
+use super::transfers::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::config::Config;
+use crate::db::models::{AuditEvent, DeviceUpdate, NewAuditEvent};
+use crate::db::{AuditChainStatus, AuditQuery, Database, SortOrder};
+use crate::AppState;
+
+/// Type of an audit event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditKind {
+    /// A device was registered through pairing
+    DevicePaired,
+    /// A device authenticated on a direct transport
+    DeviceVerified,
+    /// A direct transport `Hello` named a device but failed authentication
+    VerificationFailed,
+    /// A device was renamed or relabelled
+    DeviceUpdated,
+    /// A device was unpaired
+    DeviceRevoked,
+    /// A signaling session was opened
+    SessionCreated,
+    TransferStarted,
+    TransferCompleted,
+    /// A transfer session ended with an error; the transfer can be resumed
+    TransferFailed,
+    /// The server started with a configuration different from the last one
+    ConfigChanged,
+}
+
+/// Every kind, for validating filters
+pub const AUDIT_KINDS: &[AuditKind] = &[
+    AuditKind::DevicePaired,
+    AuditKind::DeviceVerified,
+    AuditKind::VerificationFailed,
+    AuditKind::DeviceUpdated,
+    AuditKind::DeviceRevoked,
+    AuditKind::SessionCreated,
+    AuditKind::TransferStarted,
+    AuditKind::TransferCompleted,
+    AuditKind::TransferFailed,
+    AuditKind::ConfigChanged,
+];
+
+impl AuditKind {
+    /// Value stored in `audit_events.kind`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditKind::DevicePaired => "device.paired",
+            AuditKind::DeviceVerified => "device.verified",
+            AuditKind::VerificationFailed => "device.verification_failed",
+            AuditKind::DeviceUpdated => "device.updated",
+            AuditKind::DeviceRevoked => "device.revoked",
+            AuditKind::SessionCreated => "session.created",
+            AuditKind::TransferStarted => "transfer.started",
+            AuditKind::TransferCompleted => "transfer.completed",
+            AuditKind::TransferFailed => "transfer.failed",
+            AuditKind::ConfigChanged => "config.changed",
+        }
+    }
+
+    /// Start an event of this kind caused by `actor`
+    pub fn event(self, actor: &Actor) -> NewAuditEvent {
+        NewAuditEvent::new(self.as_str(), actor.to_string())
+    }
+}
+
+impl fmt::Display for AuditKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Who caused an audit event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Actor {
+    /// A request to the HTTP API
+    Api,
+    /// An admin subcommand of `bridgex-server`
+    Cli,
+    /// The server itself
+    Server,
+    /// A paired device, authenticated or claimed
+    Device(String),
+}
+
+impl fmt::Display for Actor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Actor::Api => f.write_str("api"),
+            Actor::Cli => f.write_str("cli"),
+            Actor::Server => f.write_str("server"),
+            Actor::Device(id) => write!(f, "device:{}", id),
+        }
+    }
+}
+
+/// Append an event, logging instead of failing if it cannot be written
+pub async fn record(db: &Database, event: NewAuditEvent) {
+    if let Err(e) = db.append_audit_event(&event).await {
+        tracing::error!("Failed to record audit event {}: {}", event.kind, e);
+    }
+}
+
+/// `device.updated` event listing the fields an update sets
+pub fn device_updated(actor: &Actor, device_id: &str, update: &DeviceUpdate) -> NewAuditEvent {
+    let mut changes = serde_json::Map::new();
+    if let Some(name) = &update.name {
+        changes.insert("name".to_string(), name.clone().into());
+    }
+    if let Some(nickname) = &update.nickname {
+        changes.insert("nickname".to_string(), nickname.clone().into());
+    }
+    if let Some(icon) = &update.icon {
+        changes.insert("icon".to_string(), icon.clone().into());
+    }
+    if let Some(color) = &update.color {
+        changes.insert("color".to_string(), color.clone().into());
+    }
+    if let Some(tags) = &update.tags {
+        changes.insert("tags".to_string(), tags.clone().into());
+    }
+    if let Some(favorite) = update.favorite {
+        changes.insert("favorite".to_string(), favorite.into());
+    }
+    AuditKind::DeviceUpdated
+        .event(actor)
+        .device(device_id)
+        .details(serde_json::Value::Object(changes))
+}
+
+/// `transfer.completed` or `transfer.failed` event ending a transfer session
+///
+/// # Arguments
+/// * `outcome` - Total bytes assembled, or why the session failed
+pub fn transfer_outcome(
+    device_id: &str,
+    transfer_id: &str,
+    transport: Transport,
+    outcome: Result<u64, String>,
+) -> NewAuditEvent {
+    let (kind, details) = match outcome {
+        Ok(total_bytes) => (
+            AuditKind::TransferCompleted,
+            serde_json::json!({ "transport": transport.as_str(), "total_bytes": total_bytes }),
+        ),
+        Err(reason) => (
+            AuditKind::TransferFailed,
+            serde_json::json!({ "transport": transport.as_str(), "reason": reason }),
+        ),
+    };
+    kind.event(&Actor::Device(device_id.to_string()))
+        .device(device_id)
+        .transfer(transfer_id)
+        .details(details)
+}
+
+/// Record `config.changed` if `config` differs from the last recorded one
+///
+/// # Returns
+/// Whether an event was recorded
+pub async fn record_config(db: &Database, config: &Config) -> anyhow::Result<bool> {
+    let current = serde_json::to_value(config)?;
+    let last = db
+        .list_audit_events(&AuditQuery {
+            kind: Some(AuditKind::ConfigChanged.to_string()),
+            order: SortOrder::Desc,
+            limit: 1,
+            ..Default::default()
+        })
+        .await?;
+    let previous = last.first().and_then(|event| event.details.get("config"));
+    if previous == Some(&current) {
+        return Ok(false);
+    }
+
+    let event = AuditKind::ConfigChanged
+        .event(&Actor::Server)
+        .details(serde_json::json!({ "config": current }));
+    db.append_audit_event(&event).await?;
+    Ok(true)
+}
+
+/// Write every event matching `query` as JSON lines, oldest first
+///
+/// `order`, `after` and `limit` of the query are ignored.
+///
+/// # Returns
+/// Number of events written
+pub async fn export<W>(db: &Database, query: &AuditQuery, out: &mut W) -> anyhow::Result<u64>
+where
+    W: Write + ?Sized,
+{
+    let mut query = AuditQuery {
+        order: SortOrder::Asc,
+        after: None,
+        limit: 500,
+        ..query.clone()
+    };
+    let mut written = 0;
+    loop {
+        let events = db.list_audit_events(&query).await?;
+        for event in &events {
+            serde_json::to_writer(&mut *out, event)?;
+            writeln!(out)?;
+        }
+        written += events.len() as u64;
+        match events.last() {
+            Some(last) if events.len() == query.limit as usize => query.after = Some(last.seq),
+            _ => return Ok(written),
+        }
+    }
+}
+
+/// Query parameters of the audit endpoints
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditParams {
+    pub kind: Option<String>,
+    pub device_id: Option<String>,
+    pub transfer_id: Option<String>,
+    /// RFC 3339 lower bound on `occurred_at`, inclusive
+    pub from: Option<DateTime<Utc>>,
+    /// RFC 3339 upper bound on `occurred_at`, exclusive
+    pub to: Option<DateTime<Utc>>,
+    /// `asc` or `desc` (default)
+    pub sort: Option<String>,
+    pub limit: Option<u32>,
+    pub cursor: Option<String>,
+}
+
+impl AuditParams {
+    /// Validate the parameters into a database query
+    pub fn into_query(self) -> Result<AuditQuery, AppError> {
+        if let Some(kind) = &self.kind {
+            if !AUDIT_KINDS.iter().any(|k| k.as_str() == kind) {
+                return Err(AppError::validation("Unknown event kind"));
+            }
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from >= to {
+                return Err(AppError::validation("`from` must be before `to`"));
+            }
+        }
+
+        let order = match self.sort.as_deref() {
+            None | Some("desc") => SortOrder::Desc,
+            Some("asc") => SortOrder::Asc,
+            Some(_) => return Err(AppError::validation("`sort` must be `asc` or `desc`")),
+        };
+        let limit = match self.limit {
+            None => DEFAULT_PAGE_SIZE,
+            Some(limit @ 1..=MAX_PAGE_SIZE) => limit,
+            Some(_) => return Err(AppError::validation("`limit` must be between 1 and 200")),
+        };
+        let after = match self.cursor.as_deref() {
+            None => None,
+            Some(cursor) => Some(
+                cursor
+                    .parse()
+                    .map_err(|_| AppError::validation("Invalid cursor"))?,
+            ),
+        };
+
+        Ok(AuditQuery {
+            kind: self.kind,
+            device_id: self.device_id,
+            transfer_id: self.transfer_id,
+            from: self.from,
+            to: self.to,
+            order,
+            after,
+            limit,
+        })
+    }
+}
+
+/// A page of audit events
+#[derive(Debug, Serialize)]
+pub struct AuditPage {
+    pub events: Vec<AuditEvent>,
+    /// Cursor of the next page, absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Result of `GET /api/v1/audit/verify`
+#[derive(Debug, Serialize)]
+pub struct AuditVerification {
+    pub valid: bool,
+    #[serde(flatten)]
+    pub status: AuditChainStatus,
+}
+
+/// `GET /api/v1/audit`: list audit events, newest first by default
+pub async fn list_events(
+    State(state): State<AppState>,
+    ApiQuery(params): ApiQuery<AuditParams>,
+) -> Result<Json<AuditPage>, AppError> {
+    let mut query = params.into_query()?;
+    let limit = query.limit as usize;
+
+    // Fetch one extra row to learn whether another page follows
+    query.limit += 1;
+    let mut events = state.db.list_audit_events(&query).await?;
+
+    let next_cursor = if events.len() > limit {
+        events.truncate(limit);
+        events.last().map(|event| event.seq.to_string())
+    } else {
+        None
+    };
+
+    Ok(Json(AuditPage {
+        events,
+        next_cursor,
+    }))
+}
+
+/// `GET /api/v1/audit/export`: download matching events as JSON lines
+pub async fn export_events(
+    State(state): State<AppState>,
+    ApiQuery(params): ApiQuery<AuditParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let query = params.into_query()?;
+    let mut body = Vec::new();
+    export(&state.db, &query, &mut body).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"bridgex-audit.ndjson\"",
+            ),
+        ],
+        body,
+    ))
+}
+
+/// `GET /api/v1/audit/verify`: check the hash chain
+pub async fn verify_chain(
+    State(state): State<AppState>,
+) -> Result<Json<AuditVerification>, AppError> {
+    let status = state.db.verify_audit_chain().await?;
+    if !status.is_valid() {
+        tracing::warn!(
+            "Audit log chain broken at event {}",
+            status.first_invalid.unwrap_or_default()
+        );
+    }
+    Ok(Json(AuditVerification {
+        valid: status.is_valid(),
+        status,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_actor_display() {
+        assert_eq!(Actor::Api.to_string(), "api");
+        assert_eq!(Actor::Device("d1".to_string()).to_string(), "device:d1");
+    }
+
+    #[test]
+    fn test_params_validation() {
+        let query = AuditParams {
+            kind: Some("device.paired".to_string()),
+            cursor: Some("42".to_string()),
+            ..Default::default()
+        }
+        .into_query()
+        .unwrap();
+        assert_eq!(query.after, Some(42));
+        assert_eq!(query.limit, DEFAULT_PAGE_SIZE);
+
+        for params in [
+            AuditParams {
+                kind: Some("device.exploded".to_string()),
+                ..Default::default()
+            },
+            AuditParams {
+                cursor: Some("abc".to_string()),
+                ..Default::default()
+            },
+            AuditParams {
+                limit: Some(0),
+                ..Default::default()
+            },
+        ] {
+            assert!(params.into_query().is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_config_only_on_change() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        db.init_schema().await.unwrap();
+        let mut config = Config::default();
+
+        assert!(record_config(&db, &config).await.unwrap());
+        assert!(!record_config(&db, &config).await.unwrap());
+
+        config.server.port += 1;
+        assert!(record_config(&db, &config).await.unwrap());
+        assert_eq!(db.verify_audit_chain().await.unwrap().events, 2);
+    }
+}
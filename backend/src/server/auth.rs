@@ -0,0 +1,257 @@
+//! Device authentication on the HTTP API
+//!
+//! Paired devices sign each request with their pairing token:
+//!
+//! ```text
+//! Authorization: Device <device_id>:<unix seconds>:<tag>
+//! ```
+//!
+//! `tag` is the hex of [`request_tag`], keyed like the transfer channels'
+//! `Hello` with the hash of the token (see
+//! [`token_hash`](crate::db::models::token_hash)). It covers the time,
+//! the method and the path, so the token itself never crosses the network
+//! and a captured header only replays against the same route within
+//! [`MAX_CLOCK_SKEW_SECS`].
+//!
+//! Verified requests draw from the device's rate limit budget.
+//!
+//! The admin API serves the same device routes to the local user, who may
+//! act for any device; its router marks requests with [`AdminApi`].
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, Method},
+};
+use chrono::{DateTime, Utc};
+
+use super::audit::{self, Actor, AuditKind};
+use super::error::AppError;
+use super::rate_limit::{self, RouteClass};
+use crate::crypto::keys::derive_session_key;
+use crate::db::models::{token_hash, Device};
+use crate::AppState;
+
+/// `Authorization` scheme of signed device requests
+pub const AUTH_SCHEME: &str = "Device";
+
+/// How far a request's timestamp may be from the server clock
+pub const MAX_CLOCK_SKEW_SECS: i64 = 300;
+
+/// HKDF info prefix of request tags
+const REQUEST_AUTH_INFO: &[u8] = b"bridgex-http-request";
+
+/// Request extension marking the admin API, whose callers are trusted
+#[derive(Debug, Clone, Copy)]
+pub struct AdminApi;
+
+/// Caller of a device route
+#[derive(Debug, Clone)]
+pub enum DeviceAuth {
+    /// The local user through the admin API
+    Admin,
+    /// A paired device that signed the request with its token
+    Device(Box<Device>),
+}
+
+impl DeviceAuth {
+    /// Authenticated device, `None` for the admin API
+    pub fn device_id(&self) -> Option<&str> {
+        match self {
+            DeviceAuth::Admin => None,
+            DeviceAuth::Device(device) => Some(&device.id),
+        }
+    }
+
+    /// Check that the caller may act for `device_id`
+    pub fn authorize(&self, device_id: &str) -> Result<(), AppError> {
+        match self.device_id() {
+            Some(own) if own != device_id => Err(AppError::Forbidden(
+                "Not allowed to act for another device".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for DeviceAuth {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        if parts.extensions.get::<AdminApi>().is_some() {
+            return Ok(DeviceAuth::Admin);
+        }
+        let credentials = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_authorization)
+            .ok_or_else(|| AppError::Unauthorized("Device authentication required".to_string()))?;
+        let device = verify(state, &parts.method, parts.uri.path(), &credentials).await?;
+        rate_limit::admit_device(
+            state,
+            &device.id,
+            RouteClass::of(&parts.method, parts.uri.path()),
+        )?;
+        Ok(DeviceAuth::Device(Box::new(device)))
+    }
+}
+
+/// Fields of a `Device` authorization header
+#[derive(Debug, PartialEq, Eq)]
+struct Credentials {
+    device_id: String,
+    timestamp: i64,
+    tag: [u8; 32],
+}
+
+fn parse_authorization(value: &str) -> Option<Credentials> {
+    let (scheme, credentials) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case(AUTH_SCHEME) {
+        return None;
+    }
+    let mut fields = credentials.trim().rsplitn(3, ':');
+    let tag = hex::decode(fields.next()?).ok()?.try_into().ok()?;
+    let timestamp = fields.next()?.parse().ok()?;
+    let device_id = fields.next().filter(|id| !id.is_empty())?.to_string();
+    Some(Credentials {
+        device_id,
+        timestamp,
+        tag,
+    })
+}
+
+/// Check a request's credentials against the device's token
+async fn verify(
+    state: &AppState,
+    method: &Method,
+    path: &str,
+    credentials: &Credentials,
+) -> Result<Device, AppError> {
+    let unauthorized = || AppError::Unauthorized("Device authentication failed".to_string());
+    let now = Utc::now();
+    if now.timestamp().abs_diff(credentials.timestamp) > MAX_CLOCK_SKEW_SECS as u64 {
+        return Err(AppError::Unauthorized(
+            "Request timestamp too far from server time".to_string(),
+        ));
+    }
+    let device = state
+        .db
+        .get_device(&credentials.device_id)
+        .await?
+        .ok_or_else(unauthorized)?;
+
+    let expected = device
+        .usable_token_hash(now)
+        .and_then(|token_hash| <&[u8; 32]>::try_from(token_hash).ok())
+        .map(|token_hash| {
+            request_tag(
+                token_hash,
+                credentials.timestamp,
+                &device.id,
+                method,
+                path,
+            )
+        });
+    if !expected.is_some_and(|expected| super::tcp::tags_match(&expected, &credentials.tag)) {
+        audit::record(
+            &state.db,
+            AuditKind::VerificationFailed
+                .event(&Actor::Device(device.id.clone()))
+                .device(&device.id)
+                .details(serde_json::json!({ "transport": "http" })),
+        )
+        .await;
+        return Err(unauthorized());
+    }
+    if device.confirmed_at.is_none() {
+        state.db.confirm_device(&device.id, now).await?;
+    }
+    Ok(device)
+}
+
+/// Compute the tag signing a request of `device_id` at `timestamp`
+pub fn request_tag(
+    token_hash: &[u8; 32],
+    timestamp: i64,
+    device_id: &str,
+    method: &Method,
+    path: &str,
+) -> [u8; 32] {
+    let mut info = REQUEST_AUTH_INFO.to_vec();
+    info.extend_from_slice(&timestamp.to_be_bytes());
+    for field in [device_id, method.as_str(), path] {
+        info.extend_from_slice(&(field.len() as u64).to_be_bytes());
+        info.extend_from_slice(field.as_bytes());
+    }
+    derive_session_key(token_hash, &info)
+}
+
+/// `Authorization` header value signing a request with a pairing token
+pub fn authorization(
+    device_id: &str,
+    token: &[u8],
+    method: &Method,
+    path: &str,
+    at: DateTime<Utc>,
+) -> String {
+    let timestamp = at.timestamp();
+    let tag = request_tag(&token_hash(token), timestamp, device_id, method, path);
+    format!(
+        "{} {}:{}:{}",
+        AUTH_SCHEME,
+        device_id,
+        timestamp,
+        hex::encode(tag)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authorization_round_trips() {
+        let at = Utc::now();
+        let value = authorization("device-1", b"token", &Method::POST, "/api/v1/x", at);
+        let credentials = parse_authorization(&value).unwrap();
+
+        assert_eq!(credentials.device_id, "device-1");
+        assert_eq!(credentials.timestamp, at.timestamp());
+        let expected = request_tag(
+            &token_hash(b"token"),
+            at.timestamp(),
+            "device-1",
+            &Method::POST,
+            "/api/v1/x",
+        );
+        assert_eq!(credentials.tag, expected);
+    }
+
+    #[test]
+    fn test_tag_covers_method_and_path() {
+        let key = token_hash(b"token");
+        let tag = |method: &Method, path: &str| request_tag(&key, 0, "device-1", method, path);
+
+        assert_ne!(tag(&Method::POST, "/a"), tag(&Method::GET, "/a"));
+        assert_ne!(tag(&Method::POST, "/a"), tag(&Method::POST, "/b"));
+    }
+
+    #[test]
+    fn test_malformed_headers_are_ignored() {
+        for value in [
+            "Bearer abc",
+            "Device device-1:0",
+            "Device :0:00",
+            "Device device-1:soon:00",
+            "Device device-1:0:zz",
+            "Device device-1:-9223372036854775808:00",
+        ] {
+            assert_eq!(parse_authorization(value), None, "{}", value);
+        }
+    }
+}
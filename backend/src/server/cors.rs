@@ -0,0 +1,227 @@
+//! Cross-origin policy
+//!
+//! Browsers attach an `Origin` header to cross-origin requests. Only the
+//! desktop app's webview ([`TAURI_ORIGINS`]) and the origins listed in
+//! `cors.allowed_origins` may call the API; anything else is refused with
+//! `403 forbidden` before it reaches a handler. Refusing outright, rather
+//! than only leaving out `Access-Control-Allow-Origin`, matters because
+//! "simple" requests such as a form POST skip the preflight and would
+//! otherwise still run. Requests without an `Origin`, like those of the
+//! mobile app, the CLI or curl, are not affected.
+//!
+//! Chrome's Private Network Access sends
+//! `Access-Control-Request-Private-Network: true` on preflights from web
+//! pages to private addresses. It is granted to allowed origins when
+//! `cors.allow_private_network` is set.
+//!
+//! The admin API also checks the `Host` header ([`HostPolicy`]). A page
+//! can point its own domain at 127.0.0.1 (DNS rebinding) and then call the
+//! loopback port same-origin, without any `Origin` to refuse. Its requests
+//! still name that domain as the host, so only `localhost`, the loopback
+//! addresses and `server.host` are accepted.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, AllowPrivateNetwork, CorsLayer};
+
+use super::error::AppError;
+use crate::config::{Config, CorsConfig};
+use crate::logging::REQUEST_ID_HEADER;
+
+/// Origins of the desktop app's webview on macOS/Linux and Windows
+pub const TAURI_ORIGINS: [&str; 3] = [
+    "tauri://localhost",
+    "http://tauri.localhost",
+    "https://tauri.localhost",
+];
+
+/// How long browsers may cache a preflight
+const PREFLIGHT_MAX_AGE: Duration = Duration::from_secs(600);
+
+/// The set of origins allowed to call the API
+#[derive(Debug, Clone)]
+pub struct OriginPolicy {
+    origins: Arc<HashSet<HeaderValue>>,
+    allow_private_network: bool,
+}
+
+impl OriginPolicy {
+    pub fn new(config: &CorsConfig) -> Self {
+        let origins = TAURI_ORIGINS
+            .iter()
+            .map(|origin| origin.to_string())
+            .chain(config.allowed_origins.iter().cloned())
+            // Validated by `Config::validate`
+            .filter_map(|origin| HeaderValue::try_from(origin).ok())
+            .collect();
+        Self {
+            origins: Arc::new(origins),
+            allow_private_network: config.allow_private_network,
+        }
+    }
+
+    /// Whether requests carrying this `Origin` header may proceed
+    pub fn allows(&self, origin: &HeaderValue) -> bool {
+        self.origins.contains(origin)
+    }
+
+    /// CORS layer answering preflights and adding the response headers
+    pub fn layer(&self) -> CorsLayer {
+        let origins = self.clone();
+        let layer = CorsLayer::new()
+            .allow_origin(AllowOrigin::predicate(move |origin, _| {
+                origins.allows(origin)
+            }))
+            .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
+            .allow_headers([
+                header::CONTENT_TYPE,
+                header::AUTHORIZATION,
+                HeaderName::from_static(REQUEST_ID_HEADER),
+            ])
+            .expose_headers([
+                header::RETRY_AFTER,
+                HeaderName::from_static(REQUEST_ID_HEADER),
+            ])
+            .max_age(PREFLIGHT_MAX_AGE);
+        if self.allow_private_network {
+            let origins = self.clone();
+            layer.allow_private_network(AllowPrivateNetwork::predicate(move |origin, _| {
+                origins.allows(origin)
+            }))
+        } else {
+            layer
+        }
+    }
+}
+
+/// Middleware refusing requests from origins outside the policy
+pub async fn check_origin(
+    State(policy): State<OriginPolicy>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(origin) = request.headers().get(header::ORIGIN) {
+        if !policy.allows(origin) {
+            tracing::warn!(
+                "Refused {} {} from origin {:?}",
+                request.method(),
+                request.uri().path(),
+                origin
+            );
+            return AppError::Forbidden("Origin not allowed".to_string()).into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Host names the admin API answers to
+#[derive(Debug, Clone)]
+pub struct HostPolicy {
+    hosts: Arc<HashSet<String>>,
+}
+
+impl HostPolicy {
+    pub fn new(config: &Config) -> Self {
+        let bind_host = match config.server.host {
+            IpAddr::V4(ip) => ip.to_string(),
+            IpAddr::V6(ip) => format!("[{}]", ip),
+        };
+        let hosts = ["localhost", "127.0.0.1", "[::1]"]
+            .iter()
+            .map(|host| host.to_string())
+            .chain([bind_host])
+            .collect();
+        Self {
+            hosts: Arc::new(hosts),
+        }
+    }
+
+    /// Whether a `Host` header value, with or without a port, is allowed
+    pub fn allows(&self, host: &str) -> bool {
+        let name = match host.rsplit_once(':') {
+            // A colon inside brackets belongs to an IPv6 address
+            Some((name, port)) if !port.contains(']') => name,
+            _ => host,
+        };
+        self.hosts.contains(&name.to_ascii_lowercase())
+    }
+}
+
+/// Middleware refusing requests that name a host outside the policy
+///
+/// HTTP/2 requests may carry the host in the URI instead of a header.
+/// Requests naming no host at all, which browsers never send, pass.
+pub async fn check_host(
+    State(policy): State<HostPolicy>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let host = match request.headers().get(header::HOST) {
+        // A header that isn't text can't name an allowed host
+        Some(host) => Some(host.to_str().unwrap_or_default()),
+        None => request.uri().authority().map(|authority| authority.as_str()),
+    };
+    if let Some(host) = host {
+        if !policy.allows(host) {
+            tracing::warn!(
+                "Refused {} {} for host {:?}",
+                request.method(),
+                request.uri().path(),
+                host
+            );
+            return AppError::Forbidden("Host not allowed".to_string()).into_response();
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_includes_tauri_and_configured_origins() {
+        let policy = OriginPolicy::new(&CorsConfig {
+            allowed_origins: vec!["http://localhost:3000".to_string()],
+            ..Default::default()
+        });
+
+        assert!(policy.allows(&HeaderValue::from_static("tauri://localhost")));
+        assert!(policy.allows(&HeaderValue::from_static("http://tauri.localhost")));
+        assert!(policy.allows(&HeaderValue::from_static("http://localhost:3000")));
+        assert!(!policy.allows(&HeaderValue::from_static("http://localhost:3001")));
+        assert!(!policy.allows(&HeaderValue::from_static("null")));
+    }
+
+    #[test]
+    fn test_host_policy_allows_loopback_and_bind_address() {
+        let mut config = Config::default();
+        config.server.host = "192.168.1.20".parse().unwrap();
+        let policy = HostPolicy::new(&config);
+
+        for host in [
+            "localhost",
+            "LocalHost:8083",
+            "127.0.0.1:8083",
+            "[::1]",
+            "[::1]:8083",
+            "192.168.1.20:8080",
+        ] {
+            assert!(policy.allows(host), "{}", host);
+        }
+        for host in ["evil.example", "evil.example:8083", "127.0.0.2", "::1", ""] {
+            assert!(!policy.allows(host), "{}", host);
+        }
+
+        config.server.host = "fd00::20".parse().unwrap();
+        assert!(HostPolicy::new(&config).allows("[fd00::20]:8080"));
+    }
+}
@@ -0,0 +1,266 @@
+//! LAN service discovery via mDNS/DNS-SD
+//!
+//! The backend advertises itself as `_bridgex._tcp.local.` with its device
+//! name and certificate fingerprint in TXT records, and browses for other
+//! BridgeX instances on the network so users don't have to type addresses.
+
+use anyhow::Result;
+use axum::{extract::State, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use tokio::sync::RwLock;
+
+use crate::AppState;
+
+/// DNS-SD service type advertised by BridgeX
+pub const SERVICE_TYPE: &str = "_bridgex._tcp.local.";
+
+/// Version of the TXT record layout
+pub const TXT_VERSION: &str = "1";
+
+/// A BridgeX instance found on the LAN
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiscoveredPeer {
+    /// Full DNS-SD instance name
+    pub fullname: String,
+    pub name: String,
+    pub fingerprint: String,
+    pub addresses: Vec<SocketAddr>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Discovery state shared with the API
+///
+/// Holds the addresses this server is reachable on (embedded in pairing
+/// URIs) and the peers found by the mDNS browser.
+#[derive(Default)]
+pub struct Discovery {
+    local_addrs: RwLock<Vec<SocketAddr>>,
+    peers: RwLock<HashMap<String, DiscoveredPeer>>,
+}
+
+impl Discovery {
+    /// Create an empty discovery state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the addresses this server is reachable on
+    pub async fn set_local_addrs(&self, addrs: Vec<SocketAddr>) {
+        *self.local_addrs.write().await = addrs;
+    }
+
+    /// Addresses this server is reachable on
+    pub async fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.local_addrs.read().await.clone()
+    }
+
+    /// Record a resolved peer, replacing any previous record with the same name
+    pub async fn upsert_peer(&self, peer: DiscoveredPeer) {
+        self.peers.write().await.insert(peer.fullname.clone(), peer);
+    }
+
+    /// Forget a peer that left the network
+    pub async fn remove_peer(&self, fullname: &str) {
+        self.peers.write().await.remove(fullname);
+    }
+
+    /// Peers currently visible on the LAN, sorted by name
+    pub async fn peers(&self) -> Vec<DiscoveredPeer> {
+        let mut peers: Vec<_> = self.peers.read().await.values().cloned().collect();
+        peers.sort_by(|a, b| a.name.cmp(&b.name));
+        peers
+    }
+}
+
+/// Addresses clients can use to reach a server bound to `bind`
+///
+/// A wildcard bind expands to every non-loopback interface address.
+pub fn reachable_addrs(bind: SocketAddr) -> Vec<SocketAddr> {
+    if !bind.ip().is_unspecified() {
+        return vec![bind];
+    }
+
+    let mut addrs: Vec<SocketAddr> = if_addrs::get_if_addrs()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|iface| !iface.is_loopback())
+        .map(|iface| iface.ip())
+        .filter(|ip| ip.is_ipv4() == bind.is_ipv4() || bind.is_ipv6())
+        .map(|ip| SocketAddr::new(ip, bind.port()))
+        .collect();
+    addrs.sort();
+    addrs.dedup();
+    addrs
+}
+
+/// TXT record properties advertised for this server
+pub fn txt_properties(device_name: &str, fingerprint: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("v", TXT_VERSION.to_string()),
+        ("name", device_name.to_string()),
+        ("fp", fingerprint.to_string()),
+    ]
+}
+
+/// Convert a resolved DNS-SD record into a peer, ignoring foreign or malformed records
+pub fn peer_from_service(info: &ServiceInfo) -> Option<DiscoveredPeer> {
+    let fingerprint = info.get_property_val_str("fp")?;
+    let name = info
+        .get_property_val_str("name")
+        .unwrap_or_else(|| info.get_fullname());
+
+    let mut addresses: Vec<SocketAddr> = info
+        .get_addresses()
+        .iter()
+        .map(|ip: &IpAddr| SocketAddr::new(*ip, info.get_port()))
+        .collect();
+    addresses.sort();
+
+    Some(DiscoveredPeer {
+        fullname: info.get_fullname().to_string(),
+        name: name.to_string(),
+        fingerprint: fingerprint.to_string(),
+        addresses,
+        last_seen: Utc::now(),
+    })
+}
+
+/// Advertise this server and browse for peers until the daemon shuts down
+///
+/// # Arguments
+/// * `discovery` - Shared state updated with the peers found
+/// * `device_name` - Human-readable name advertised in the TXT record
+/// * `fingerprint` - Server certificate fingerprint, used to skip ourselves
+/// * `addrs` - Addresses to advertise
+pub fn start(
+    discovery: std::sync::Arc<Discovery>,
+    device_name: &str,
+    fingerprint: &str,
+    addrs: &[SocketAddr],
+) -> Result<ServiceDaemon> {
+    let daemon = ServiceDaemon::new()?;
+    let port = addrs.first().map(|a| a.port()).unwrap_or_default();
+    let ips: Vec<IpAddr> = addrs.iter().map(|a| a.ip()).collect();
+
+    // Instance names must be unique on the link: suffix with the fingerprint
+    let instance = format!(
+        "{}-{}",
+        device_name,
+        &fingerprint[..8.min(fingerprint.len())]
+    );
+    let host_name = format!("{}.local.", instance.replace([' ', '.'], "-"));
+    let properties = txt_properties(device_name, fingerprint);
+    let properties: Vec<(&str, &str)> = properties.iter().map(|(k, v)| (*k, v.as_str())).collect();
+
+    let service = ServiceInfo::new(
+        SERVICE_TYPE,
+        &instance,
+        &host_name,
+        ips.as_slice(),
+        port,
+        properties.as_slice(),
+    )?;
+    daemon.register(service)?;
+    tracing::info!("Advertising {} as {}", SERVICE_TYPE, instance);
+
+    let events = daemon.browse(SERVICE_TYPE)?;
+    let own_fingerprint = fingerprint.to_string();
+    tokio::spawn(async move {
+        while let Ok(event) = events.recv_async().await {
+            match event {
+                ServiceEvent::ServiceResolved(info) => {
+                    if let Some(peer) = peer_from_service(&info) {
+                        if peer.fingerprint != own_fingerprint {
+                            tracing::debug!(
+                                "Discovered peer {} at {:?}",
+                                peer.name,
+                                peer.addresses
+                            );
+                            discovery.upsert_peer(peer).await;
+                        }
+                    }
+                }
+                ServiceEvent::ServiceRemoved(_, fullname) => {
+                    tracing::debug!("Peer left: {}", fullname);
+                    discovery.remove_peer(&fullname).await;
+                }
+                _ => {}
+            }
+        }
+    });
+
+    Ok(daemon)
+}
+
+/// List BridgeX peers discovered on the LAN
+pub async fn list_peers(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.discovery.peers().await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(fullname: &str, name: &str) -> DiscoveredPeer {
+        DiscoveredPeer {
+            fullname: fullname.to_string(),
+            name: name.to_string(),
+            fingerprint: "ab".repeat(32),
+            addresses: vec!["192.168.1.20:8080".parse().unwrap()],
+            last_seen: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_peer_registry() {
+        let discovery = Discovery::new();
+        discovery
+            .upsert_peer(peer("b._bridgex._tcp.local.", "Laptop"))
+            .await;
+        discovery
+            .upsert_peer(peer("a._bridgex._tcp.local.", "Desktop"))
+            .await;
+        discovery
+            .upsert_peer(peer("a._bridgex._tcp.local.", "Desktop"))
+            .await;
+
+        let peers = discovery.peers().await;
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[0].name, "Desktop");
+
+        discovery.remove_peer("a._bridgex._tcp.local.").await;
+        assert_eq!(discovery.peers().await.len(), 1);
+    }
+
+    #[test]
+    fn test_peer_from_service() {
+        let fingerprint = "cd".repeat(32);
+        let properties = txt_properties("Office PC", &fingerprint);
+        let properties: Vec<(&str, &str)> =
+            properties.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        let info = ServiceInfo::new(
+            SERVICE_TYPE,
+            "office",
+            "office.local.",
+            "192.168.1.30",
+            8080,
+            properties.as_slice(),
+        )
+        .unwrap();
+
+        let peer = peer_from_service(&info).unwrap();
+        assert_eq!(peer.name, "Office PC");
+        assert_eq!(peer.fingerprint, fingerprint);
+        assert_eq!(peer.addresses, vec!["192.168.1.30:8080".parse().unwrap()]);
+    }
+
+    #[test]
+    fn test_reachable_addrs_keeps_specific_bind() {
+        let bind: SocketAddr = "192.168.1.5:8080".parse().unwrap();
+        assert_eq!(reachable_addrs(bind), vec![bind]);
+    }
+}
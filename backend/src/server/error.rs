@@ -0,0 +1,350 @@
+//! API error taxonomy
+//!
+//! Every handler fails with an [`AppError`], rendered as
+//! `{"error": "<message>", "code": "<code>"}` with a matching HTTP status.
+//! Codes are stable and meant for programs; messages are for humans and may
+//! change. Storage and internal failures are logged in full but only
+//! described generically to clients.
+
+use axum::{
+    async_trait,
+    extract::{
+        multipart::MultipartRejection,
+        rejection::{JsonRejection, QueryRejection},
+        FromRequest, FromRequestParts, Query, Request,
+    },
+    http::{header, request::Parts, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Error returned by API handlers
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Unauthorized(String),
+
+    #[error("{0}")]
+    Forbidden(String),
+
+    #[error("{0}")]
+    MethodNotAllowed(String),
+
+    #[error("{0}")]
+    Conflict(String),
+
+    #[error("{0}")]
+    PayloadTooLarge(String),
+
+    #[error("{0}")]
+    Validation(String),
+
+    #[error("{message}")]
+    TooManyRequests {
+        message: String,
+        /// Sent as `Retry-After`, rounded up to whole seconds
+        retry_after: Duration,
+    },
+
+    #[error("storage error: {0}")]
+    Storage(#[source] anyhow::Error),
+
+    #[error("crypto error: {0}")]
+    Crypto(String),
+
+    #[error("internal error: {0}")]
+    Internal(#[source] anyhow::Error),
+}
+
+impl AppError {
+    /// Resource not found, e.g. `AppError::not_found("Device")`
+    pub fn not_found(what: &str) -> Self {
+        AppError::NotFound(format!("{} not found", what))
+    }
+
+    /// Invalid request with a message naming the offending field
+    pub fn validation(message: impl Into<String>) -> Self {
+        AppError::Validation(message.into())
+    }
+
+    /// Rate limit or lockout hit; the client may retry after `retry_after`
+    pub fn too_many_requests(message: impl Into<String>, retry_after: Duration) -> Self {
+        AppError::TooManyRequests {
+            message: message.into(),
+            retry_after,
+        }
+    }
+
+    /// HTTP status of the error
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Storage(_) | AppError::Crypto(_) | AppError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    /// Stable machine-readable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not_found",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::MethodNotAllowed(_) => "method_not_allowed",
+            AppError::Conflict(_) => "conflict",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::Validation(_) => "validation_failed",
+            AppError::TooManyRequests { .. } => "rate_limited",
+            AppError::Storage(_) => "storage_error",
+            AppError::Crypto(_) => "crypto_error",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    /// Message safe to show to clients
+    fn public_message(&self) -> String {
+        match self {
+            AppError::Storage(_) => "Storage error".to_string(),
+            AppError::Crypto(_) => "Cryptographic operation failed".to_string(),
+            AppError::Internal(_) => "Internal server error".to_string(),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            tracing::error!("API error: {:?}", self);
+        } else {
+            tracing::debug!("API error: {}", self);
+        }
+
+        let mut response = (
+            status,
+            Json(json!({
+                "error": self.public_message(),
+                "code": self.code(),
+            })),
+        )
+            .into_response();
+        if let AppError::TooManyRequests { retry_after, .. } = &self {
+            let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs.max(1)));
+        }
+        response
+    }
+}
+
+/// Database and filesystem failures are storage errors, anything else is internal
+impl From<anyhow::Error> for AppError {
+    fn from(err: anyhow::Error) -> Self {
+        if err.downcast_ref::<sqlx::Error>().is_some()
+            || err.downcast_ref::<std::io::Error>().is_some()
+        {
+            AppError::Storage(err)
+        } else {
+            AppError::Internal(err)
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        AppError::Storage(err.into())
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        match rejection.status() {
+            StatusCode::PAYLOAD_TOO_LARGE => AppError::PayloadTooLarge(rejection.body_text()),
+            _ => AppError::Validation(rejection.body_text()),
+        }
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        AppError::Validation(rejection.body_text())
+    }
+}
+
+impl From<MultipartRejection> for AppError {
+    fn from(rejection: MultipartRejection) -> Self {
+        AppError::Validation(rejection.body_text())
+    }
+}
+
+/// Fallback for paths without a route
+pub async fn route_not_found() -> AppError {
+    AppError::not_found("Route")
+}
+
+/// Fallback for routes that do not accept the request method
+pub async fn method_not_allowed() -> AppError {
+    AppError::MethodNotAllowed("Method not allowed".to_string())
+}
+
+/// JSON body extractor whose rejections are [`AppError`]s
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApiJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await?;
+        Ok(ApiJson(value))
+    }
+}
+
+/// Query string extractor whose rejections are [`AppError`]s
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApiQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiQuery<T>
+where
+    Query<T>: FromRequestParts<S, Rejection = QueryRejection>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state).await?;
+        Ok(ApiQuery(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn render(error: AppError) -> (StatusCode, serde_json::Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_status_and_code_mapping() {
+        let cases = [
+            (
+                AppError::not_found("Device"),
+                StatusCode::NOT_FOUND,
+                "not_found",
+            ),
+            (
+                AppError::Unauthorized("bad token".into()),
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+            ),
+            (
+                AppError::Forbidden("origin".into()),
+                StatusCode::FORBIDDEN,
+                "forbidden",
+            ),
+            (
+                AppError::MethodNotAllowed("method".into()),
+                StatusCode::METHOD_NOT_ALLOWED,
+                "method_not_allowed",
+            ),
+            (
+                AppError::Conflict("exists".into()),
+                StatusCode::CONFLICT,
+                "conflict",
+            ),
+            (
+                AppError::PayloadTooLarge("too big".into()),
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "payload_too_large",
+            ),
+            (
+                AppError::validation("bad"),
+                StatusCode::BAD_REQUEST,
+                "validation_failed",
+            ),
+            (
+                AppError::too_many_requests("slow down", Duration::from_secs(3)),
+                StatusCode::TOO_MANY_REQUESTS,
+                "rate_limited",
+            ),
+            (
+                AppError::Storage(anyhow::anyhow!("disk full")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "storage_error",
+            ),
+            (
+                AppError::Crypto("bad key".into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "crypto_error",
+            ),
+            (
+                AppError::Internal(anyhow::anyhow!("bug")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+            ),
+        ];
+
+        for (error, status, code) in cases {
+            let (actual_status, body) = render(error).await;
+            assert_eq!(actual_status, status);
+            assert_eq!(body["code"], code);
+            assert!(body["error"].is_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_hide_details() {
+        let (_, body) = render(AppError::Storage(anyhow::anyhow!("/secret/path: denied"))).await;
+        assert_eq!(body["error"], "Storage error");
+
+        let (_, body) = render(AppError::not_found("Device")).await;
+        assert_eq!(body["error"], "Device not found");
+    }
+
+    #[test]
+    fn test_retry_after_is_rounded_up() {
+        let response =
+            AppError::too_many_requests("slow down", Duration::from_millis(1500)).into_response();
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+
+        let response = AppError::too_many_requests("slow down", Duration::ZERO).into_response();
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    }
+
+    #[test]
+    fn test_anyhow_classification() {
+        let io = anyhow::Error::from(std::io::Error::other("disk"));
+        assert!(matches!(AppError::from(io), AppError::Storage(_)));
+
+        let other = anyhow::anyhow!("unexpected");
+        assert!(matches!(AppError::from(other), AppError::Internal(_)));
+    }
+}
@@ -0,0 +1,302 @@
+//! Prometheus metrics
+//!
+//! Counters and histograms are updated where the work happens; gauges such
+//! as active connections and transfer counts are refreshed from their source
+//! when `/metrics` or `/api/v1/status` is read, so both report the same
+//! numbers. Chunk throughput is `rate(bridgex_chunks_received_total[1m])`.
+
+use axum::{extract::State, http::header, response::IntoResponse};
+use chrono::{DateTime, Utc};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use std::fmt;
+use std::time::Instant;
+
+use super::error::AppError;
+use super::transfers::TRANSFER_STATUSES;
+use crate::db::Database;
+use crate::AppState;
+
+/// Transport a chunk or transfer arrived over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Http,
+    Tcp,
+    Quic,
+    WebRtc,
+}
+
+impl Transport {
+    /// Label value used in metrics
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Transport::Http => "http",
+            Transport::Tcp => "tcp",
+            Transport::Quic => "quic",
+            Transport::WebRtc => "webrtc",
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Transfer duration buckets, in seconds
+const DURATION_BUCKETS: &[f64] = &[
+    0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0,
+];
+
+/// Metrics registry of the server
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    started: Instant,
+    started_at: DateTime<Utc>,
+    pub bytes_received: IntCounterVec,
+    pub chunks_received: IntCounterVec,
+    pub transfer_duration: HistogramVec,
+    pub pairing_attempts: IntCounter,
+    pub pairing_failures: IntCounter,
+    pub rate_limited: IntCounterVec,
+    pub active_connections: IntGauge,
+    pub active_transfers: IntGauge,
+    pub paired_devices: IntGauge,
+    pub transfers: IntGaugeVec,
+    pub uptime: IntGauge,
+}
+
+impl Metrics {
+    /// Create the registry, including the query latency histogram of `db`
+    pub fn new(db: &Database) -> Self {
+        let registry =
+            Registry::new_custom(Some("bridgex".to_string()), None).expect("valid registry prefix");
+
+        let bytes_received = IntCounterVec::new(
+            Opts::new("bytes_received_total", "File bytes received"),
+            &["transport"],
+        )
+        .unwrap();
+        let chunks_received = IntCounterVec::new(
+            Opts::new("chunks_received_total", "File chunks received"),
+            &["transport"],
+        )
+        .unwrap();
+        let transfer_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "transfer_duration_seconds",
+                "Time from transfer init to completion",
+            )
+            .buckets(DURATION_BUCKETS.to_vec()),
+            &["transport"],
+        )
+        .unwrap();
+        let pairing_attempts =
+            IntCounter::new("pairing_attempts_total", "Pairing requests").unwrap();
+        let pairing_failures =
+            IntCounter::new("pairing_failures_total", "Rejected pairing requests").unwrap();
+        let rate_limited = IntCounterVec::new(
+            Opts::new("rate_limited_total", "Requests refused by rate limits"),
+            &["route"],
+        )
+        .unwrap();
+        let active_connections = IntGauge::new(
+            "active_connections",
+            "Devices with an open transfer connection",
+        )
+        .unwrap();
+        let active_transfers =
+            IntGauge::new("active_transfers", "Transfers being written").unwrap();
+        let paired_devices = IntGauge::new("paired_devices", "Paired devices").unwrap();
+        let transfers = IntGaugeVec::new(
+            Opts::new("transfers", "Transfers in the database by status"),
+            &["status"],
+        )
+        .unwrap();
+        let uptime = IntGauge::new("uptime_seconds", "Seconds since the server started").unwrap();
+
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(bytes_received.clone()),
+            Box::new(chunks_received.clone()),
+            Box::new(transfer_duration.clone()),
+            Box::new(pairing_attempts.clone()),
+            Box::new(pairing_failures.clone()),
+            Box::new(rate_limited.clone()),
+            Box::new(active_connections.clone()),
+            Box::new(active_transfers.clone()),
+            Box::new(paired_devices.clone()),
+            Box::new(transfers.clone()),
+            Box::new(uptime.clone()),
+            Box::new(db.query_duration().clone()),
+        ];
+        for collector in collectors {
+            registry
+                .register(collector)
+                .expect("metric names are unique");
+        }
+
+        Self {
+            registry,
+            started: Instant::now(),
+            started_at: Utc::now(),
+            bytes_received,
+            chunks_received,
+            transfer_duration,
+            pairing_attempts,
+            pairing_failures,
+            rate_limited,
+            active_connections,
+            active_transfers,
+            paired_devices,
+            transfers,
+            uptime,
+        }
+    }
+
+    /// Record a chunk written to disk
+    pub fn record_chunk(&self, transport: Transport, bytes: usize) {
+        let label = [transport.as_str()];
+        self.chunks_received.with_label_values(&label).inc();
+        self.bytes_received
+            .with_label_values(&label)
+            .inc_by(bytes as u64);
+    }
+
+    /// Record a completed transfer created at `created_at`
+    pub fn record_transfer(&self, transport: Transport, created_at: DateTime<Utc>) {
+        let seconds = (Utc::now() - created_at).num_milliseconds().max(0) as f64 / 1000.0;
+        self.transfer_duration
+            .with_label_values(&[transport.as_str()])
+            .observe(seconds);
+    }
+
+    /// Time since the server started
+    pub fn uptime(&self) -> std::time::Duration {
+        self.started.elapsed()
+    }
+
+    /// When the server started
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    /// File bytes received over every transport
+    pub fn bytes_received_total(&self) -> u64 {
+        sum_transports(|label| self.bytes_received.with_label_values(&[label]).get())
+    }
+
+    /// Chunks received over every transport
+    pub fn chunks_received_total(&self) -> u64 {
+        sum_transports(|label| self.chunks_received.with_label_values(&[label]).get())
+    }
+
+    /// Completed transfers over every transport
+    pub fn transfers_completed(&self) -> u64 {
+        sum_transports(|label| {
+            self.transfer_duration
+                .with_label_values(&[label])
+                .get_sample_count()
+        })
+    }
+
+    /// Render every metric in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("text encoding cannot fail");
+        String::from_utf8(buffer).expect("text format is UTF-8")
+    }
+}
+
+fn sum_transports(value: impl Fn(&str) -> u64) -> u64 {
+    [
+        Transport::Http,
+        Transport::Tcp,
+        Transport::Quic,
+        Transport::WebRtc,
+    ]
+    .iter()
+    .map(|transport| value(transport.as_str()))
+    .sum()
+}
+
+/// Refresh gauges from the connection manager, shutdown tracker and database
+pub async fn refresh(state: &AppState) -> anyhow::Result<()> {
+    let metrics = &state.metrics;
+    metrics.uptime.set(metrics.uptime().as_secs() as i64);
+    metrics
+        .active_connections
+        .set(state.connections.connection_count().await as i64);
+    metrics
+        .active_transfers
+        .set(state.shutdown.active_transfers().len() as i64);
+    metrics
+        .paired_devices
+        .set(state.db.count_devices().await? as i64);
+
+    let counts = state.db.count_transfers_by_status().await?;
+    for status in TRANSFER_STATUSES {
+        let count = counts
+            .iter()
+            .find(|(name, _)| name == status)
+            .map(|(_, count)| *count)
+            .unwrap_or(0);
+        metrics.transfers.with_label_values(&[status]).set(count);
+    }
+    Ok(())
+}
+
+/// `GET /metrics` in the Prometheus text format
+pub async fn metrics(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    refresh(&state).await?;
+    Ok((
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        state.metrics.render(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_render_includes_recorded_values() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        let metrics = Metrics::new(&db);
+
+        metrics.record_chunk(Transport::Tcp, 100);
+        metrics.record_chunk(Transport::Http, 20);
+        metrics.record_transfer(Transport::Tcp, Utc::now() - chrono::Duration::seconds(3));
+        metrics.pairing_attempts.inc();
+
+        assert_eq!(metrics.bytes_received_total(), 120);
+        assert_eq!(metrics.chunks_received_total(), 2);
+        assert_eq!(metrics.transfers_completed(), 1);
+
+        let text = metrics.render();
+        assert!(text.contains("bridgex_bytes_received_total{transport=\"tcp\"} 100"));
+        assert!(text.contains("bridgex_chunks_received_total{transport=\"http\"} 1"));
+        assert!(
+            text.contains("bridgex_transfer_duration_seconds_bucket{transport=\"tcp\",le=\"5\"} 1")
+        );
+        assert!(text.contains("bridgex_pairing_attempts_total 1"));
+    }
+
+    #[tokio::test]
+    async fn test_database_latency_is_registered() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        db.init_schema().await.unwrap();
+        let metrics = Metrics::new(&db);
+
+        db.get_devices().await.unwrap();
+        let text = metrics.render();
+        assert!(
+            text.contains("bridgex_db_query_duration_seconds_count{operation=\"get_devices\"} 1")
+        );
+    }
+}
@@ -1,10 +1,122 @@
 //! Server module containing API and P2P logic
 
+#[cfg(unix)]
+pub mod admin_socket;
 pub mod api;
+pub mod audit;
+pub mod auth;
+pub mod cors;
+pub mod discovery;
+pub mod error;
+pub mod metrics;
 pub mod p2p;
+pub mod ports;
+pub mod presence;
+pub mod quic;
+pub mod rate_limit;
+pub mod shutdown;
+pub mod signaling;
+pub mod tcp;
+pub mod tls;
+pub mod transfers;
 pub mod upload;
+pub mod webrtc;
 
-use serde::{Deserialize, Serialize};
+use axum::{
+    middleware,
+    routing::{get, patch, post},
+    Extension, Router,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+pub use error::AppError;
+
+use crate::db::models::DeviceType;
+use crate::AppState;
+
+/// Build the admin API router: every route, for the loopback listener
+///
+/// Requests from browser origins outside `cors`, or naming a host other
+/// than loopback or `server.host`, are refused. Rate limits
+/// apply per client IP when served with
+/// `into_make_service_with_connect_info::<SocketAddr>()`.
+pub fn router(state: AppState) -> Router {
+    let routes = device_routes()
+        .route("/metrics", get(metrics::metrics))
+        .route("/api/v1/pair", post(api::pair))
+        .route("/api/v1/transfers", get(transfers::list_transfers))
+        .route("/api/v1/transfers/:id", get(transfers::get_transfer))
+        .route("/api/v1/status", get(api::status))
+        .route("/api/v1/devices", get(api::list_devices))
+        .route(
+            "/api/v1/devices/:id",
+            patch(api::update_device).delete(api::delete_device),
+        )
+        .route("/api/v1/audit", get(audit::list_events))
+        .route("/api/v1/audit/export", get(audit::export_events))
+        .route("/api/v1/audit/verify", get(audit::verify_chain))
+        .route("/api/v1/discovery/peers", get(discovery::list_peers));
+    let hosts = cors::HostPolicy::new(&state.config);
+    with_layers(routes, state)
+        .layer(Extension(auth::AdminApi))
+        .layer(middleware::from_fn_with_state(hosts, cors::check_host))
+}
+
+/// Build the device API router for the LAN listener
+///
+/// Only what paired devices need: transfers, heartbeats and signaling,
+/// each signed by the device (see [`auth`]). Pairing, device management,
+/// history, audit and metrics are admin-only.
+pub fn device_router(state: AppState) -> Router {
+    with_layers(device_routes(), state)
+}
+
+fn device_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/v1/health", get(api::health))
+        .route("/api/v1/transfer/init", post(api::transfer_init))
+        .route("/api/v1/transfer/upload", post(upload::upload_chunk))
+        .route("/api/v1/transfer/finalize", post(upload::finalize_transfer))
+        .route("/api/v1/transfer/:id/status", get(upload::get_upload_status))
+        .route("/api/v1/devices/:id/heartbeat", post(presence::heartbeat))
+        .route("/api/v1/signaling/offer", post(signaling::create_offer))
+        .route(
+            "/api/v1/signaling/:id",
+            get(signaling::get_session).delete(signaling::delete_session),
+        )
+        .route("/api/v1/signaling/:id/answer", post(signaling::post_answer))
+        .route("/api/v1/signaling/:id/accept", post(signaling::accept_session))
+        .route(
+            "/api/v1/signaling/:id/candidates",
+            get(signaling::get_candidates).post(signaling::post_candidate),
+        )
+}
+
+fn with_layers(routes: Router<AppState>, state: AppState) -> Router {
+    let origins = cors::OriginPolicy::new(&state.config.cors);
+    routes
+        .fallback(error::route_not_found)
+        .method_not_allowed_fallback(error::method_not_allowed)
+        .layer(middleware::from_fn_with_state(state.clone(), rate_limit::limit))
+        .layer(origins.layer())
+        .layer(middleware::from_fn_with_state(origins, cors::check_origin))
+        .with_state(state)
+}
+
+/// Serve `app` on `listener` until shutdown starts, over TLS when enabled
+pub async fn serve(listener: TcpListener, app: Router, state: AppState) -> std::io::Result<()> {
+    if state.config.tls_enabled() {
+        let acceptor = tls::acceptor(&state.certificate).map_err(std::io::Error::other)?;
+        tls::serve(listener, acceptor, app, state.shutdown.clone()).await
+    } else {
+        let shutdown = state.shutdown.clone();
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(async move { shutdown.triggered().await })
+            .await
+    }
+}
 
 /// Device information
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,6 +131,12 @@ pub struct Device {
 #[derive(Debug, Deserialize)]
 pub struct PairRequest {
     pub device_name: String,
+    /// Kind of the device asking to pair; older clients omit it
+    #[serde(default)]
+    pub device_type: DeviceType,
+    pub platform: Option<String>,
+    pub os_version: Option<String>,
+    pub app_version: Option<String>,
 }
 
 /// Pairing response with QR code data
@@ -26,10 +144,38 @@ pub struct PairRequest {
 pub struct PairResponse {
     pub device_id: String,
     pub public_key: String,
+    /// SHA-256 fingerprint of the server certificate, pinned by the device
+    pub server_fingerprint: String,
     pub qr_data: String,
+    /// Versioned `bridgex://pair` URI encoded in the QR code
+    pub pairing_uri: String,
     pub expires_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// Device edit request; omitted fields are left unchanged, `null` clears
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateDeviceRequest {
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub nickname: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub icon: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub color: Option<Option<String>>,
+    pub tags: Option<Vec<String>>,
+    pub favorite: Option<bool>,
+}
+
+/// Distinguish an explicit `null` from an omitted field
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
 /// Transfer initialization request
 #[derive(Debug, Deserialize)]
 pub struct TransferRequest {
@@ -1,14 +1,34 @@
 //! P2P connection logic
 //!
-//! Handles WebRTC signaling, peer connections, and fallback to TCP/WebSocket
+//! Tracks the peers connected over the direct transports, and runs the
+//! server's side of WebRTC connections negotiated through the
+//! [signaling relay](super::signaling)
 
 use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, RwLock};
+
+use super::error::AppError;
+use super::metrics::Transport;
+use super::signaling::{
+    IceCandidate, Role, SessionDescription, SignalingError, SignalingHub, SignalingSession,
+};
+use super::tcp::{self, ProtocolError};
+use super::webrtc::{self, ChannelStream, Peer, PeerError};
+use crate::AppState;
 
 /// P2P connection manager
+///
+/// Connections are keyed by session, so a device can hold several at once
+/// and closing one leaves the others registered.
 pub struct ConnectionManager {
-    connections: Arc<RwLock<HashMap<String, PeerConnection>>>,
+    connections: Arc<RwLock<HashMap<u64, PeerConnection>>>,
+    next_session: AtomicU64,
 }
 
 impl ConnectionManager {
@@ -16,25 +36,34 @@ impl ConnectionManager {
     pub fn new() -> Self {
         Self {
             connections: Arc::new(RwLock::new(HashMap::new())),
+            next_session: AtomicU64::new(1),
         }
     }
 
     /// Add a new peer connection
-    pub async fn add_connection(&self, device_id: String, conn: PeerConnection) {
+    ///
+    /// # Returns
+    /// ID of the session, to pass to [`remove_connection`](Self::remove_connection)
+    pub async fn add_connection(&self, conn: PeerConnection) -> u64 {
+        let session = self.next_session.fetch_add(1, Ordering::Relaxed);
         let mut connections = self.connections.write().await;
-        connections.insert(device_id, conn);
+        connections.insert(session, conn);
+        session
     }
 
-    /// Get a peer connection by device ID
+    /// Get a connection of a device, if it has any
     pub async fn get_connection(&self, device_id: &str) -> Option<PeerConnection> {
         let connections = self.connections.read().await;
-        connections.get(device_id).cloned()
+        connections
+            .values()
+            .find(|conn| conn.device_id == device_id)
+            .cloned()
     }
 
-    /// Remove a peer connection
-    pub async fn remove_connection(&self, device_id: &str) {
+    /// Remove the connection of one session
+    pub async fn remove_connection(&self, session: u64) {
         let mut connections = self.connections.write().await;
-        connections.remove(device_id);
+        connections.remove(&session);
     }
 
     /// Get count of active connections
@@ -60,31 +89,228 @@ pub struct PeerConnection {
 }
 
 /// Type of P2P connection
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ConnectionType {
-    /// WebRTC direct connection
+    /// WebRTC data channels, one per file (see [`super::webrtc`])
     WebRTC,
-    /// TCP fallback
+    /// Direct TCP channel (see [`super::tcp`])
     Tcp,
     /// WebSocket fallback
     WebSocket,
+    /// QUIC connection, one stream per file (see [`super::quic`])
+    Quic,
 }
 
-/// Create a WebRTC offer
+/// How often the server's peer polls the hub for the device's candidates
+const CANDIDATE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Answer a device's offer with the server's own WebRTC peer
+///
+/// The answer is stored on the session and the peer connects in the
+/// background. Once connected, each data channel the device opens carries
+/// one transfer session, like the streams of a QUIC connection.
 ///
-/// TODO: Implement WebRTC signaling
-pub async fn create_offer(_device_id: &str) -> Result<String, Box<dyn std::error::Error>> {
-    // Placeholder for WebRTC offer creation
-    Ok("webrtc-offer-placeholder".to_string())
+/// # Returns
+/// The server's answer
+pub async fn accept_session(
+    state: &AppState,
+    session: &SignalingSession,
+) -> Result<SessionDescription, AppError> {
+    if session.answer.is_some() {
+        return Err(SignalingError::AlreadyAnswered.into());
+    }
+    webrtc::check_description(Role::Answerer, &session.offer)?;
+
+    let (peer, candidates) = Peer::new(Role::Answerer).await?;
+    let answer = peer.local_description().await;
+    if let Err(e) = state
+        .signaling
+        .set_answer(&session.id, answer.clone())
+        .await
+    {
+        peer.close().await;
+        return Err(e.into());
+    }
+
+    let state = state.clone();
+    let session = session.clone();
+    tokio::spawn(async move {
+        match connect_through_hub(
+            &state.signaling,
+            &session.id,
+            &peer,
+            candidates,
+            &session.offer,
+        )
+        .await
+        {
+            Ok(connection) => serve_connection(connection, &session.device_id, &state).await,
+            Err(e) => {
+                tracing::warn!("WebRTC session {} failed: {}", session.id, e);
+                peer.close().await;
+            }
+        }
+    });
+    Ok(answer)
+}
+
+/// Connect a peer to the other side of a signaling session
+///
+/// The peer's candidates are published on the hub as they are gathered,
+/// and the other side's are polled until the connection is up.
+///
+/// # Arguments
+/// * `candidates` - Local candidates, as returned by [`Peer::new`]
+/// * `remote` - Description of the other side
+pub async fn connect_through_hub(
+    hub: &SignalingHub,
+    session_id: &str,
+    peer: &Peer,
+    candidates: mpsc::UnboundedReceiver<IceCandidate>,
+    remote: &SessionDescription,
+) -> Result<webrtc::Connection, PeerError> {
+    tokio::select! {
+        connection = peer.connect(remote) => connection,
+        Err(e) = relay_candidates(hub, session_id, peer, candidates) => Err(e),
+    }
+}
+
+/// Trickle candidates both ways through the hub, until an error
+async fn relay_candidates(
+    hub: &SignalingHub,
+    session_id: &str,
+    peer: &Peer,
+    mut candidates: mpsc::UnboundedReceiver<IceCandidate>,
+) -> Result<Infallible, PeerError> {
+    let (role, remote_role) = match peer.role() {
+        Role::Offerer => (Role::Offerer, Role::Answerer),
+        Role::Answerer => (Role::Answerer, Role::Offerer),
+    };
+    let mut gathering = true;
+    let mut since = 0;
+    loop {
+        tokio::select! {
+            candidate = candidates.recv(), if gathering => match candidate {
+                Some(candidate) => hub.add_candidate(session_id, role, candidate).await?,
+                None => gathering = false,
+            },
+            _ = tokio::time::sleep(CANDIDATE_POLL_INTERVAL) => {
+                for candidate in hub.candidates(session_id, remote_role, since).await? {
+                    since += 1;
+                    peer.add_remote_candidate(&candidate)?;
+                }
+            }
+        }
+    }
 }
 
-/// Handle WebRTC answer
+/// Serve the data channels of a connection until it closes
+async fn serve_connection(connection: webrtc::Connection, device_id: &str, state: &AppState) {
+    // The device authenticated on the signaling routes
+    let session = tcp::register_connection(state, device_id, ConnectionType::WebRTC).await;
+    let peer = connection
+        .remote_addr()
+        .map(|addr| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    tracing::info!("WebRTC connection from {} (device {})", peer, device_id);
+
+    loop {
+        let stream = match connection.accept_channel().await {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::debug!("WebRTC connection from {} closed: {}", peer, e);
+                break;
+            }
+        };
+
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_channel(stream, peer, &state).await {
+                tracing::warn!("WebRTC data channel from {} failed: {}", peer, e);
+            }
+        });
+    }
+
+    connection.close().await;
+    state.connections.remove_connection(session).await;
+}
+
+/// Run a transfer session on one data channel
+async fn handle_channel(
+    mut stream: ChannelStream,
+    peer: IpAddr,
+    state: &AppState,
+) -> Result<(), ProtocolError> {
+    let (device_id, transfer) =
+        match tcp::accept_hello(&mut stream, state, peer, Transport::WebRtc).await {
+            Ok(session) => session,
+            Err(e) => return Err(tcp::reject(&mut stream, e).await),
+        };
+
+    tracing::info!(
+        "WebRTC data channel for transfer {} (device {})",
+        transfer.id,
+        device_id
+    );
+
+    let result =
+        match tcp::receive_transfer(&mut stream, state, &device_id, &transfer, Transport::WebRtc)
+            .await
+        {
+            Ok(()) => Ok(()),
+            Err(e) => Err(tcp::reject(&mut stream, e).await),
+        };
+    stream.shutdown().await.ok();
+    result
+}
+
+/// Send a file on its own data channel of an established connection
 ///
-/// TODO: Implement WebRTC answer handling
-pub async fn handle_answer(
-    _device_id: &str,
-    _answer: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Placeholder for WebRTC answer handling
-    Ok(())
+/// # Returns
+/// Total number of bytes the server assembled
+pub async fn send_file(
+    connection: &webrtc::Connection,
+    device_id: &str,
+    token: &[u8],
+    transfer_id: &str,
+    data: &[u8],
+    chunk_size: usize,
+) -> Result<u64, ProtocolError> {
+    let mut stream = connection
+        .open_channel()
+        .await
+        .map_err(|e| ProtocolError::Io(std::io::Error::other(e)))?;
+    let total_bytes =
+        tcp::send_session(&mut stream, device_id, token, transfer_id, data, chunk_size).await?;
+    stream.shutdown().await.ok();
+    Ok(total_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection(device_id: &str) -> PeerConnection {
+        PeerConnection {
+            device_id: device_id.to_string(),
+            device_name: "Phone".to_string(),
+            connection_type: ConnectionType::Tcp,
+            established_at: chrono::Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sessions_of_a_device_are_independent() {
+        let manager = ConnectionManager::new();
+        let first = manager.add_connection(connection("device-1")).await;
+        let second = manager.add_connection(connection("device-1")).await;
+        assert_ne!(first, second);
+
+        manager.remove_connection(first).await;
+        assert!(manager.get_connection("device-1").await.is_some());
+        assert_eq!(manager.connection_count().await, 1);
+
+        manager.remove_connection(second).await;
+        assert!(manager.get_connection("device-1").await.is_none());
+    }
 }
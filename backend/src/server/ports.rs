@@ -0,0 +1,109 @@
+//! Reporting the bound ports
+//!
+//! Every port may be configured as `0` to let the OS pick a free one, so a
+//! supervising process such as the desktop app cannot know in advance
+//! where to connect. Once all listeners are bound the server prints one
+//! handshake line on stdout, `BRIDGEX_READY {json}` (see [`BoundPorts`]),
+//! and writes the same JSON to `server.port_file` when set. The port file
+//! is removed again on shutdown.
+
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Prefix of the handshake line printed on stdout
+pub const READY_PREFIX: &str = "BRIDGEX_READY ";
+
+/// Ports the server actually listens on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoundPorts {
+    /// Device API port
+    pub port: u16,
+    /// Admin API port on loopback, unless turned off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_port: Option<u16>,
+    /// Direct TCP transfer channel port
+    pub tcp_port: u16,
+    /// QUIC transfer endpoint port (UDP)
+    pub quic_port: u16,
+    /// Unix socket serving the admin API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_socket: Option<PathBuf>,
+    /// Process id of the server
+    pub pid: u32,
+}
+
+impl BoundPorts {
+    /// The handshake line, without a trailing newline
+    pub fn handshake_line(&self) -> String {
+        format!("{}{}", READY_PREFIX, self.to_json())
+    }
+
+    /// Parse a handshake line; `None` for any other output line
+    pub fn parse_handshake(line: &str) -> Option<Self> {
+        let json = line.trim_end().strip_prefix(READY_PREFIX)?;
+        serde_json::from_str(json).ok()
+    }
+
+    /// Print the handshake line on stdout
+    pub fn announce(&self) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "{}", self.handshake_line())?;
+        stdout.flush()
+    }
+
+    /// Write the ports to `path` as JSON
+    ///
+    /// The file is written next to its final name and renamed, so readers
+    /// never see a partial file.
+    pub fn write_file(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".tmp");
+        let partial = PathBuf::from(partial);
+        std::fs::write(&partial, self.to_json() + "\n")?;
+        std::fs::rename(&partial, path)
+    }
+
+    /// Read ports written by [`write_file`](Self::write_file)
+    pub fn read_file(path: &Path) -> io::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("ports are always serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ports() -> BoundPorts {
+        BoundPorts {
+            port: 41000,
+            admin_port: Some(41001),
+            tcp_port: 41002,
+            quic_port: 41003,
+            admin_socket: Some(PathBuf::from("/run/user/1000/bridgex/admin.sock")),
+            pid: 1234,
+        }
+    }
+
+    #[test]
+    fn test_handshake_roundtrip() {
+        let line = ports().handshake_line();
+        assert!(line.starts_with("BRIDGEX_READY {"));
+        assert_eq!(BoundPorts::parse_handshake(&line), Some(ports()));
+        assert_eq!(BoundPorts::parse_handshake(&(line + "\r\n")), Some(ports()));
+    }
+
+    #[test]
+    fn test_other_lines_are_not_handshakes() {
+        assert_eq!(BoundPorts::parse_handshake("INFO starting"), None);
+        assert_eq!(BoundPorts::parse_handshake("BRIDGEX_READY {"), None);
+    }
+}
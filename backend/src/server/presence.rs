@@ -0,0 +1,197 @@
+//! Device presence tracking
+//!
+//! Devices send periodic heartbeats that refresh `last_seen`; presence is
+//! derived from how long ago that was, so it needs no background task. A
+//! device with an open transfer connection is always online.
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::fmt;
+
+use super::auth::DeviceAuth;
+use super::error::AppError;
+use crate::db::models::Device;
+use crate::AppState;
+
+/// Presence of a paired device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Presence {
+    Online,
+    Idle,
+    Offline,
+}
+
+impl fmt::Display for Presence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Presence::Online => "online",
+            Presence::Idle => "idle",
+            Presence::Offline => "offline",
+        })
+    }
+}
+
+/// How long after the last heartbeat a device stays online, then idle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresenceThresholds {
+    pub online: Duration,
+    pub idle: Duration,
+}
+
+impl Default for PresenceThresholds {
+    /// Clients heartbeat every 15 seconds, so two missed beats mark a device idle
+    fn default() -> Self {
+        Self {
+            online: Duration::seconds(45),
+            idle: Duration::minutes(5),
+        }
+    }
+}
+
+/// Computes device presence from heartbeats and open connections
+#[derive(Debug, Clone, Default)]
+pub struct PresenceTracker {
+    thresholds: PresenceThresholds,
+}
+
+impl PresenceTracker {
+    /// Create a tracker with custom thresholds
+    pub fn new(thresholds: PresenceThresholds) -> Self {
+        Self { thresholds }
+    }
+
+    /// Thresholds in use
+    pub fn thresholds(&self) -> PresenceThresholds {
+        self.thresholds
+    }
+
+    /// Presence of a device last seen at `last_seen`
+    ///
+    /// # Arguments
+    /// * `last_seen` - Time of the last heartbeat, if any
+    /// * `connected` - Whether the device has an open transfer connection
+    /// * `now` - Reference time
+    pub fn presence(
+        &self,
+        last_seen: Option<DateTime<Utc>>,
+        connected: bool,
+        now: DateTime<Utc>,
+    ) -> Presence {
+        if connected {
+            return Presence::Online;
+        }
+        match last_seen.map(|seen| now - seen) {
+            Some(age) if age <= self.thresholds.online => Presence::Online,
+            Some(age) if age <= self.thresholds.idle => Presence::Idle,
+            _ => Presence::Offline,
+        }
+    }
+}
+
+/// Paired device with its current presence
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceWithPresence {
+    #[serde(flatten)]
+    pub device: Device,
+    pub presence: Presence,
+}
+
+/// Number of devices in each presence state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct PresenceCounts {
+    pub online: usize,
+    pub idle: usize,
+    pub offline: usize,
+}
+
+impl PresenceCounts {
+    /// Count the presence states of a device list
+    pub fn from_devices(devices: &[DeviceWithPresence]) -> Self {
+        let mut counts = Self::default();
+        for device in devices {
+            match device.presence {
+                Presence::Online => counts.online += 1,
+                Presence::Idle => counts.idle += 1,
+                Presence::Offline => counts.offline += 1,
+            }
+        }
+        counts
+    }
+}
+
+/// Attach presence to every paired device
+pub async fn with_presence(state: &AppState, devices: Vec<Device>) -> Vec<DeviceWithPresence> {
+    let now = Utc::now();
+    let mut result = Vec::with_capacity(devices.len());
+    for device in devices {
+        let connected = state.connections.get_connection(&device.id).await.is_some();
+        let presence = state.presence.presence(device.last_seen, connected, now);
+        result.push(DeviceWithPresence { device, presence });
+    }
+    result
+}
+
+/// Heartbeat response
+#[derive(Debug, Serialize)]
+pub struct HeartbeatResponse {
+    pub device_id: String,
+    pub last_seen: DateTime<Utc>,
+    pub presence: Presence,
+}
+
+/// Record a heartbeat from a device
+pub async fn heartbeat(
+    State(state): State<AppState>,
+    auth: DeviceAuth,
+    Path(device_id): Path<String>,
+) -> Result<Json<HeartbeatResponse>, AppError> {
+    auth.authorize(&device_id)?;
+    let now = Utc::now();
+    if !state.db.touch_device(&device_id, now).await? {
+        return Err(AppError::not_found("Device"));
+    }
+
+    tracing::debug!("Heartbeat from device {}", device_id);
+    Ok(Json(HeartbeatResponse {
+        device_id,
+        last_seen: now,
+        presence: Presence::Online,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presence_thresholds() {
+        let tracker = PresenceTracker::new(PresenceThresholds {
+            online: Duration::seconds(30),
+            idle: Duration::minutes(2),
+        });
+        let now = Utc::now();
+
+        let ago = |seconds| Some(now - Duration::seconds(seconds));
+        assert_eq!(tracker.presence(ago(0), false, now), Presence::Online);
+        assert_eq!(tracker.presence(ago(30), false, now), Presence::Online);
+        assert_eq!(tracker.presence(ago(31), false, now), Presence::Idle);
+        assert_eq!(tracker.presence(ago(120), false, now), Presence::Idle);
+        assert_eq!(tracker.presence(ago(121), false, now), Presence::Offline);
+        assert_eq!(tracker.presence(None, false, now), Presence::Offline);
+    }
+
+    #[test]
+    fn test_connected_device_is_online() {
+        let tracker = PresenceTracker::default();
+        let now = Utc::now();
+        let long_ago = Some(now - Duration::days(1));
+
+        assert_eq!(tracker.presence(long_ago, true, now), Presence::Online);
+        assert_eq!(tracker.presence(None, true, now), Presence::Online);
+    }
+}
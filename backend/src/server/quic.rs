@@ -0,0 +1,191 @@
+//! QUIC transfer transport
+//!
+//! Carries the same framed session as the direct TCP channel (see
+//! [`super::tcp`]), but each file gets its own bidirectional QUIC stream on a
+//! single connection. Streams do not block each other on packet loss, and
+//! QUIC connection migration keeps transfers alive when the phone roams
+//! between Wi-Fi access points.
+//!
+//! The server presents its self-signed [`ServerCertificate`]; clients pin the
+//! fingerprint they received at pairing instead of relying on WebPKI.
+
+use anyhow::Result;
+use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+use quinn::{ClientConfig, Connection, Endpoint, ServerConfig};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+
+use super::metrics::Transport;
+use super::p2p::ConnectionType;
+use super::tcp::{self, ProtocolError};
+use crate::crypto::cert::{pinned_client_config, ServerCertificate, CERT_SUBJECT};
+use crate::AppState;
+
+/// ALPN protocol identifier of the transfer transport
+pub const ALPN: &[u8] = b"bridgex-transfer/1";
+
+/// Create the server endpoint presenting the server certificate
+pub fn server_endpoint(addr: SocketAddr, certificate: &ServerCertificate) -> Result<Endpoint> {
+    let crypto = certificate.server_config(&[&rustls::version::TLS13], &[ALPN])?;
+    let config = ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(crypto)?));
+    Ok(Endpoint::server(config, addr)?)
+}
+
+/// Accept QUIC connections until the endpoint is closed or shutdown starts
+///
+/// Connections already open are left to finish within the drain period.
+pub async fn serve(endpoint: Endpoint, state: AppState) -> Result<()> {
+    loop {
+        let incoming = tokio::select! {
+            incoming = endpoint.accept() => match incoming {
+                Some(incoming) => incoming,
+                None => break,
+            },
+            _ = state.shutdown.triggered() => {
+                // Refuse new connections while open ones drain
+                endpoint.set_server_config(None);
+                break;
+            }
+        };
+        let state = state.clone();
+
+        tokio::spawn(async move {
+            match incoming.await {
+                Ok(connection) => handle_connection(connection, state).await,
+                Err(e) => tracing::warn!("QUIC handshake failed: {}", e),
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Serve every stream of a connection as an independent transfer session
+async fn handle_connection(connection: Connection, state: AppState) {
+    let peer = connection.remote_address();
+    tracing::info!("QUIC connection from {}", peer);
+
+    // The device is registered once, by the first stream that authenticates
+    let session = Arc::new(OnceCell::<u64>::new());
+
+    loop {
+        let (send, recv) = match connection.accept_bi().await {
+            Ok(streams) => streams,
+            Err(e) => {
+                tracing::debug!("QUIC connection from {} closed: {}", peer, e);
+                break;
+            }
+        };
+
+        let state = state.clone();
+        let session = session.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_stream(send, recv, peer, &state, &session).await {
+                tracing::warn!("QUIC stream from {} failed: {}", peer, e);
+            }
+        });
+    }
+
+    if let Some(session) = session.get() {
+        state.connections.remove_connection(*session).await;
+    }
+}
+
+/// Run a transfer session on one bidirectional stream
+async fn handle_stream(
+    send: quinn::SendStream,
+    recv: quinn::RecvStream,
+    peer: SocketAddr,
+    state: &AppState,
+    session: &OnceCell<u64>,
+) -> Result<(), ProtocolError> {
+    let mut stream = tokio::io::join(recv, send);
+
+    let (device_id, transfer) =
+        match tcp::accept_hello(&mut stream, state, peer.ip(), Transport::Quic).await {
+            Ok(session) => session,
+            Err(e) => return Err(tcp::reject(&mut stream, e).await),
+        };
+
+    tracing::info!(
+        "QUIC stream for transfer {} (device {})",
+        transfer.id,
+        device_id
+    );
+
+    session
+        .get_or_init(|| tcp::register_connection(state, &device_id, ConnectionType::Quic))
+        .await;
+
+    let result = match tcp::receive_transfer(
+        &mut stream,
+        state,
+        &device_id,
+        &transfer,
+        Transport::Quic,
+    )
+    .await
+    {
+        Ok(()) => Ok(()),
+        Err(e) => Err(tcp::reject(&mut stream, e).await),
+    };
+
+    let (_, mut send) = stream.into_inner();
+    send.finish().ok();
+    result
+}
+
+/// Connect to a QUIC transfer endpoint, pinning its certificate fingerprint
+///
+/// # Arguments
+/// * `addr` - Address of the QUIC endpoint
+/// * `fingerprint` - Server certificate fingerprint received at pairing
+pub async fn connect(addr: SocketAddr, fingerprint: &str) -> Result<Connection> {
+    let crypto = pinned_client_config(fingerprint, &[&rustls::version::TLS13], &[ALPN])?;
+
+    let bind: SocketAddr = if addr.is_ipv6() {
+        "[::]:0".parse()?
+    } else {
+        "0.0.0.0:0".parse()?
+    };
+    let mut endpoint = Endpoint::client(bind)?;
+    endpoint.set_default_client_config(ClientConfig::new(Arc::new(QuicClientConfig::try_from(
+        crypto,
+    )?)));
+
+    let connection = endpoint.connect(addr, CERT_SUBJECT)?.await?;
+    Ok(connection)
+}
+
+/// Send a file on its own stream of an established connection
+///
+/// # Returns
+/// Total number of bytes the server assembled
+pub async fn send_file(
+    connection: &Connection,
+    device_id: &str,
+    token: &[u8],
+    transfer_id: &str,
+    data: &[u8],
+    chunk_size: usize,
+) -> Result<u64, ProtocolError> {
+    let (send, recv) = connection
+        .open_bi()
+        .await
+        .map_err(|e| ProtocolError::Io(std::io::Error::other(e)))?;
+    let mut stream = tokio::io::join(recv, send);
+
+    let total_bytes = tcp::send_session(
+        &mut stream,
+        device_id,
+        token,
+        transfer_id,
+        data,
+        chunk_size,
+    )
+    .await?;
+
+    let (_, mut send) = stream.into_inner();
+    send.finish().ok();
+    Ok(total_bytes)
+}
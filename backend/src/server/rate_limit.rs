@@ -0,0 +1,437 @@
+//! Rate limiting and brute-force lockout
+//!
+//! Requests draw from token buckets keyed by client IP and, once a device
+//! has signed the request (see [`DeviceAuth`](super::auth::DeviceAuth)),
+//! by device. A device id merely named in a path or body picks no bucket,
+//! so nobody can spend another device's budget. Pairing, chunk uploads and
+//! the remaining routes have separate budgets (see [`RateLimitConfig`]).
+//! Loopback clients may be exempt from all but the pairing budget. An IP
+//! whose direct
+//! transport authentications keep failing is locked out of every route and
+//! transport for a while. Limited requests get `429 Too Many Requests` with
+//! `Retry-After`.
+//!
+//! The client IP comes from the connection (`ConnectInfo`); requests
+//! without one, such as in-process tests, are only limited per device.
+//! Requests on the admin socket count as coming from 127.0.0.1.
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::error::AppError;
+use crate::config::{RateBudget, RateLimitConfig};
+use crate::AppState;
+
+/// How often idle buckets and expired failures are dropped
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Group of routes sharing a budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteClass {
+    Pair,
+    Upload,
+    Api,
+}
+
+impl RouteClass {
+    /// Class of a request
+    pub fn of(method: &Method, path: &str) -> Self {
+        match (method, path) {
+            (&Method::POST, "/api/v1/pair") => RouteClass::Pair,
+            (&Method::POST, "/api/v1/transfer/upload") => RouteClass::Upload,
+            _ => RouteClass::Api,
+        }
+    }
+
+    /// Label value used in metrics
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RouteClass::Pair => "pair",
+            RouteClass::Upload => "upload",
+            RouteClass::Api => "api",
+        }
+    }
+}
+
+/// Who a bucket belongs to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Ip(IpAddr),
+    Device(String),
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(budget: RateBudget, now: Instant) -> Self {
+        Self {
+            tokens: budget.burst as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, budget: RateBudget, now: Instant) {
+        let rate = budget.per_minute as f64 / 60.0;
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(budget.burst as f64);
+        self.updated = now;
+    }
+
+    /// Take a token, or return how long until one is available
+    fn take(&mut self, budget: RateBudget, now: Instant) -> Result<(), Duration> {
+        self.refill(budget, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let rate = budget.per_minute as f64 / 60.0;
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+}
+
+/// Failed authentications of one IP
+#[derive(Debug)]
+struct Failures {
+    count: u32,
+    window_start: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Token buckets and lockouts shared by the HTTP API and direct transports
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<(Client, RouteClass), Bucket>>,
+    failures: Mutex<HashMap<IpAddr, Failures>>,
+    last_prune: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            failures: Mutex::new(HashMap::new()),
+            last_prune: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Admit a request from `ip`
+    ///
+    /// Pairing is limited even for exempt loopback clients: it is only
+    /// served on loopback and the admin socket, where every other local
+    /// user could otherwise mint pairings without limit.
+    pub fn check_request(&self, ip: Option<IpAddr>, class: RouteClass) -> Result<(), AppError> {
+        let Some(ip) = ip else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        if !self.is_exempt(ip) {
+            if let Some(remaining) = self.lockout_at(ip, now) {
+                return Err(AppError::too_many_requests(
+                    "Too many failed authentications",
+                    remaining,
+                ));
+            }
+        } else if class != RouteClass::Pair {
+            return Ok(());
+        }
+        self.take(Client::Ip(ip), class, now)
+    }
+
+    /// Admit a request signed by `device_id`
+    pub fn check_device(&self, device_id: &str, class: RouteClass) -> Result<(), AppError> {
+        self.take(Client::Device(device_id.to_string()), class, Instant::now())
+    }
+
+    /// Remaining lockout of an IP, if it is locked out
+    pub fn lockout(&self, ip: IpAddr) -> Option<Duration> {
+        if self.is_exempt(ip) {
+            return None;
+        }
+        self.lockout_at(ip, Instant::now())
+    }
+
+    /// Count a failed authentication from `ip`
+    ///
+    /// # Returns
+    /// The lockout duration if this failure locked the IP out
+    pub fn record_failure(&self, ip: IpAddr) -> Option<Duration> {
+        if self.is_exempt(ip) {
+            return None;
+        }
+        self.record_failure_at(ip, Instant::now())
+    }
+
+    /// Forget the failures of an IP after it authenticated successfully
+    pub fn clear_failures(&self, ip: IpAddr) {
+        let mut failures = self.failures.lock().unwrap();
+        if failures
+            .get(&ip)
+            .is_some_and(|entry| entry.locked_until.is_none())
+        {
+            failures.remove(&ip);
+        }
+    }
+
+    fn is_exempt(&self, ip: IpAddr) -> bool {
+        !self.config.enabled || (self.config.exempt_loopback && ip.is_loopback())
+    }
+
+    fn budget(&self, class: RouteClass) -> RateBudget {
+        match class {
+            RouteClass::Pair => self.config.pair,
+            RouteClass::Upload => self.config.upload,
+            RouteClass::Api => self.config.api,
+        }
+    }
+
+    fn take(&self, client: Client, class: RouteClass, now: Instant) -> Result<(), AppError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        self.prune(now);
+
+        let budget = self.budget(class);
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets
+            .entry((client, class))
+            .or_insert_with(|| Bucket::full(budget, now))
+            .take(budget, now)
+            .map_err(|wait| AppError::too_many_requests("Too many requests", wait))
+    }
+
+    fn lockout_at(&self, ip: IpAddr, now: Instant) -> Option<Duration> {
+        let failures = self.failures.lock().unwrap();
+        let locked_until = failures.get(&ip)?.locked_until?;
+        (locked_until > now).then(|| locked_until - now)
+    }
+
+    fn record_failure_at(&self, ip: IpAddr, now: Instant) -> Option<Duration> {
+        let window = Duration::from_secs(self.config.failure_window_secs);
+        let lockout = Duration::from_secs(self.config.lockout_secs);
+
+        let mut failures = self.failures.lock().unwrap();
+        let entry = failures.entry(ip).or_insert(Failures {
+            count: 0,
+            window_start: now,
+            locked_until: None,
+        });
+        if entry.locked_until.is_some_and(|until| until > now) {
+            return None;
+        }
+        if entry.locked_until.is_some() || now.duration_since(entry.window_start) > window {
+            entry.count = 0;
+            entry.window_start = now;
+            entry.locked_until = None;
+        }
+
+        entry.count += 1;
+        if entry.count >= self.config.max_failures {
+            entry.locked_until = Some(now + lockout);
+            Some(lockout)
+        } else {
+            None
+        }
+    }
+
+    /// Drop buckets that refilled completely and failures that expired
+    fn prune(&self, now: Instant) {
+        {
+            let mut last_prune = self.last_prune.lock().unwrap();
+            if now.saturating_duration_since(*last_prune) < PRUNE_INTERVAL {
+                return;
+            }
+            *last_prune = now;
+        }
+
+        self.buckets.lock().unwrap().retain(|(_, class), bucket| {
+            let budget = self.budget(*class);
+            bucket.refill(budget, now);
+            bucket.tokens < budget.burst as f64
+        });
+
+        let window = Duration::from_secs(self.config.failure_window_secs);
+        self.failures
+            .lock()
+            .unwrap()
+            .retain(|_, entry| match entry.locked_until {
+                Some(until) => until > now,
+                None => now.saturating_duration_since(entry.window_start) <= window,
+            });
+    }
+}
+
+/// Admit a request signed by `device_id`, counting refusals in the metrics
+pub(crate) fn admit_device(
+    state: &AppState,
+    device_id: &str,
+    class: RouteClass,
+) -> Result<(), AppError> {
+    state
+        .rate_limiter
+        .check_device(device_id, class)
+        .inspect_err(|_| {
+            state
+                .metrics
+                .rate_limited
+                .with_label_values(&[class.as_str()])
+                .inc();
+            tracing::warn!("Rate limited device {} ({})", device_id, class.as_str());
+        })
+}
+
+/// Middleware applying the per-IP limits to every route of the router
+pub async fn limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let class = RouteClass::of(request.method(), request.uri().path());
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+
+    if let Err(e) = state.rate_limiter.check_request(ip, class) {
+        state
+            .metrics
+            .rate_limited
+            .with_label_values(&[class.as_str()])
+            .inc();
+        tracing::warn!(
+            "Rate limited {} {} from {}",
+            request.method(),
+            request.uri().path(),
+            ip.map(|ip| ip.to_string())
+                .unwrap_or_else(|| "-".to_string())
+        );
+        return e.into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 168, 1, 20));
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            pair: RateBudget {
+                per_minute: 60,
+                burst: 2,
+            },
+            max_failures: 3,
+            ..Default::default()
+        })
+    }
+
+    fn retry_after(result: Result<(), AppError>) -> Duration {
+        match result {
+            Err(AppError::TooManyRequests { retry_after, .. }) => retry_after,
+            other => panic!("expected a rate limit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bucket_refills_over_time() {
+        let limiter = limiter();
+        let now = Instant::now();
+        let client = || Client::Ip(IP);
+
+        limiter.take(client(), RouteClass::Pair, now).unwrap();
+        limiter.take(client(), RouteClass::Pair, now).unwrap();
+        let wait = retry_after(limiter.take(client(), RouteClass::Pair, now));
+        assert!(wait <= Duration::from_secs(1), "{:?}", wait);
+
+        // Other budgets and clients are independent
+        limiter.take(client(), RouteClass::Api, now).unwrap();
+        limiter
+            .take(Client::Device("d1".to_string()), RouteClass::Pair, now)
+            .unwrap();
+
+        let later = now + Duration::from_secs(1);
+        limiter.take(client(), RouteClass::Pair, later).unwrap();
+        assert!(limiter.take(client(), RouteClass::Pair, later).is_err());
+    }
+
+    #[test]
+    fn test_lockout_after_repeated_failures() {
+        let limiter = limiter();
+        let now = Instant::now();
+
+        assert_eq!(limiter.record_failure_at(IP, now), None);
+        assert_eq!(limiter.record_failure_at(IP, now), None);
+        assert_eq!(
+            limiter.record_failure_at(IP, now),
+            Some(Duration::from_secs(900))
+        );
+        assert!(limiter.lockout_at(IP, now).is_some());
+
+        // Success does not lift a lockout
+        limiter.clear_failures(IP);
+        assert!(limiter.lockout(IP).is_some());
+        let retry = retry_after(limiter.check_request(Some(IP), RouteClass::Api));
+        assert!(retry > Duration::from_secs(890), "{:?}", retry);
+
+        let expired = now + Duration::from_secs(901);
+        assert_eq!(limiter.lockout_at(IP, expired), None);
+        assert_eq!(limiter.record_failure_at(IP, expired), None);
+    }
+
+    #[test]
+    fn test_failures_outside_the_window_are_forgotten() {
+        let limiter = limiter();
+        let now = Instant::now();
+        limiter.record_failure_at(IP, now);
+        limiter.record_failure_at(IP, now);
+
+        let later = now + Duration::from_secs(601);
+        assert_eq!(limiter.record_failure_at(IP, later), None);
+        assert_eq!(limiter.record_failure_at(IP, later), None);
+        assert!(limiter.record_failure_at(IP, later).is_some());
+    }
+
+    #[test]
+    fn test_loopback_is_exempt_except_from_pairing() {
+        let limiter = limiter();
+        let loopback = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+        for _ in 0..10 {
+            limiter
+                .check_request(Some(loopback), RouteClass::Api)
+                .unwrap();
+            assert_eq!(limiter.record_failure(loopback), None);
+        }
+
+        limiter.check_request(Some(loopback), RouteClass::Pair).unwrap();
+        limiter.check_request(Some(loopback), RouteClass::Pair).unwrap();
+        retry_after(limiter.check_request(Some(loopback), RouteClass::Pair));
+    }
+
+    #[test]
+    fn test_route_classes() {
+        assert_eq!(
+            RouteClass::of(&Method::POST, "/api/v1/pair"),
+            RouteClass::Pair
+        );
+        assert_eq!(
+            RouteClass::of(&Method::POST, "/api/v1/transfer/upload"),
+            RouteClass::Upload
+        );
+        assert_eq!(
+            RouteClass::of(&Method::GET, "/api/v1/pair"),
+            RouteClass::Api
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Graceful shutdown
+//!
+//! On SIGTERM or SIGINT the server stops accepting connections and gives
+//! uploads in flight a drain period to finish their current chunk or
+//! session. Transfers still `uploading` afterwards are marked
+//! `interrupted`: their received chunks stay on disk and the client resumes
+//! by sending the missing chunks and finalizing again, over any transport.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+
+use crate::AppState;
+
+/// Coordinates shutdown between the listeners and active transfers
+#[derive(Debug)]
+pub struct Shutdown {
+    triggered: watch::Sender<bool>,
+    /// Number of active guards per transfer
+    active: Mutex<HashMap<String, usize>>,
+    idle: Notify,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    /// Create a coordinator with no active transfers
+    pub fn new() -> Self {
+        Self {
+            triggered: watch::channel(false).0,
+            active: Mutex::new(HashMap::new()),
+            idle: Notify::new(),
+        }
+    }
+
+    /// Start shutting down; listeners stop accepting new connections
+    pub fn trigger(&self) {
+        self.triggered.send_replace(true);
+    }
+
+    /// Whether shutdown has started
+    pub fn is_triggered(&self) -> bool {
+        *self.triggered.borrow()
+    }
+
+    /// Wait until shutdown starts
+    pub async fn triggered(&self) {
+        let mut receiver = self.triggered.subscribe();
+        // The sender lives in `self`, so the channel cannot close while borrowed
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+
+    /// Mark a transfer as being written until the guard is dropped
+    pub fn begin(self: &Arc<Self>, transfer_id: &str) -> TransferGuard {
+        *self
+            .active
+            .lock()
+            .unwrap()
+            .entry(transfer_id.to_string())
+            .or_insert(0) += 1;
+        TransferGuard {
+            shutdown: self.clone(),
+            transfer_id: transfer_id.to_string(),
+        }
+    }
+
+    /// Transfers currently being written
+    pub fn active_transfers(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.active.lock().unwrap().keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Wait until no transfer is being written
+    pub async fn idle(&self) {
+        loop {
+            let notified = self.idle.notified();
+            if self.active.lock().unwrap().is_empty() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    fn end(&self, transfer_id: &str) {
+        let mut active = self.active.lock().unwrap();
+        if let Some(count) = active.get_mut(transfer_id) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(transfer_id);
+            }
+        }
+        if active.is_empty() {
+            self.idle.notify_waiters();
+        }
+    }
+}
+
+/// Keeps a transfer registered as active, see [`Shutdown::begin`]
+#[derive(Debug)]
+pub struct TransferGuard {
+    shutdown: Arc<Shutdown>,
+    transfer_id: String,
+}
+
+impl Drop for TransferGuard {
+    fn drop(&mut self) {
+        self.shutdown.end(&self.transfer_id);
+    }
+}
+
+/// Wait for SIGINT (Ctrl+C) or, on Unix, SIGTERM
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut stream) => {
+                stream.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("Received Ctrl+C"),
+        _ = terminate => tracing::info!("Received SIGTERM"),
+    }
+}
+
+/// Drain active transfers, then record the ones left unfinished
+///
+/// # Arguments
+/// * `state` - Application state whose shutdown was triggered
+/// * `grace` - Longest time to wait for active transfers
+///
+/// # Returns
+/// Number of transfers marked `interrupted`
+pub async fn drain(state: &AppState, grace: Duration) -> anyhow::Result<u64> {
+    let active = state.shutdown.active_transfers();
+    if !active.is_empty() {
+        tracing::info!(
+            "Waiting up to {}s for {} active transfers",
+            grace.as_secs(),
+            active.len()
+        );
+        if tokio::time::timeout(grace, state.shutdown.idle())
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                "Drain period elapsed with transfers still active: {}",
+                state.shutdown.active_transfers().join(", ")
+            );
+        }
+    }
+
+    let interrupted = state.db.interrupt_uploading_transfers().await?;
+    if interrupted > 0 {
+        tracing::info!("Marked {} unfinished transfers as interrupted", interrupted);
+    }
+    Ok(interrupted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_guards_track_active_transfers() {
+        let shutdown = Arc::new(Shutdown::new());
+        let first = shutdown.begin("a");
+        let second = shutdown.begin("a");
+        let other = shutdown.begin("b");
+        assert_eq!(shutdown.active_transfers(), vec!["a", "b"]);
+
+        drop(first);
+        drop(other);
+        assert_eq!(shutdown.active_transfers(), vec!["a"]);
+
+        let waiter = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.idle().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+
+        drop(second);
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("idle once every guard is dropped")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_trigger_wakes_waiters() {
+        let shutdown = Arc::new(Shutdown::new());
+        assert!(!shutdown.is_triggered());
+
+        let waiter = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.triggered().await }
+        });
+        shutdown.trigger();
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(shutdown.is_triggered());
+
+        // Already triggered: returns immediately
+        shutdown.triggered().await;
+    }
+}
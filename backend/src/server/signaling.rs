@@ -0,0 +1,618 @@
+//! WebRTC signaling relay
+//!
+//! Peers cannot reach each other before they have exchanged session
+//! descriptions and ICE candidates, so the backend relays them: the offerer
+//! posts its SDP offer and opens a signaling session, the answerer fetches it
+//! and posts its answer, and both sides trickle ICE candidates that the other
+//! side polls with a cursor.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::audit::{self, Actor, AuditKind};
+use super::auth::DeviceAuth;
+use super::error::{ApiJson, ApiQuery, AppError};
+use super::p2p;
+use crate::AppState;
+
+/// Lifetime of a signaling session
+pub const SESSION_TTL_MINUTES: i64 = 5;
+
+/// Maximum number of ICE candidates accepted per side of a session
+pub const MAX_CANDIDATES: usize = 64;
+
+/// Maximum number of open sessions
+pub const MAX_SESSIONS: usize = 256;
+
+/// Maximum number of open sessions of one device
+pub const MAX_SESSIONS_PER_DEVICE: usize = 8;
+
+/// Kind of session description
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SdpType {
+    Offer,
+    Answer,
+}
+
+/// SDP session description, in the shape of `RTCSessionDescriptionInit`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionDescription {
+    #[serde(rename = "type")]
+    pub sdp_type: SdpType,
+    pub sdp: String,
+}
+
+/// ICE candidate, in the shape of `RTCIceCandidateInit`
+///
+/// An empty `candidate` signals the end of candidate gathering.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IceCandidate {
+    pub candidate: String,
+    #[serde(rename = "sdpMid", default)]
+    pub sdp_mid: Option<String>,
+    #[serde(rename = "sdpMLineIndex", default)]
+    pub sdp_m_line_index: Option<u16>,
+}
+
+/// Side of a signaling session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Offerer,
+    Answerer,
+}
+
+/// Offer/answer exchange between two peers
+#[derive(Debug, Clone, Serialize)]
+pub struct SignalingSession {
+    pub id: String,
+    pub device_id: String,
+    pub offer: SessionDescription,
+    pub answer: Option<SessionDescription>,
+    pub offerer_candidates: Vec<IceCandidate>,
+    pub answerer_candidates: Vec<IceCandidate>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Signaling errors
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum SignalingError {
+    #[error("signaling session not found")]
+    NotFound,
+    #[error("invalid session description: {0}")]
+    InvalidSdp(&'static str),
+    #[error("invalid ICE candidate")]
+    InvalidCandidate,
+    #[error("session already answered")]
+    AlreadyAnswered,
+    #[error("too many ICE candidates")]
+    TooManyCandidates,
+    #[error("too many open signaling sessions")]
+    TooManySessions {
+        /// Until the oldest session in the way expires
+        retry_after: std::time::Duration,
+    },
+}
+
+impl From<SignalingError> for AppError {
+    fn from(error: SignalingError) -> Self {
+        match error {
+            SignalingError::NotFound => AppError::NotFound(error.to_string()),
+            SignalingError::AlreadyAnswered => AppError::Conflict(error.to_string()),
+            SignalingError::TooManySessions { retry_after } => {
+                AppError::too_many_requests(error.to_string(), retry_after)
+            }
+            SignalingError::InvalidSdp(_)
+            | SignalingError::InvalidCandidate
+            | SignalingError::TooManyCandidates => AppError::Validation(error.to_string()),
+        }
+    }
+}
+
+/// In-memory store of pending signaling sessions
+pub struct SignalingHub {
+    sessions: RwLock<HashMap<String, SignalingSession>>,
+    ttl: Duration,
+}
+
+impl SignalingHub {
+    /// Create a hub with the default session lifetime
+    pub fn new() -> Self {
+        Self::with_ttl(Duration::minutes(SESSION_TTL_MINUTES))
+    }
+
+    /// Create a hub with a custom session lifetime
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            sessions: RwLock::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    /// Open a session with the offerer's SDP offer
+    ///
+    /// Refused once [`MAX_SESSIONS`] sessions, or [`MAX_SESSIONS_PER_DEVICE`]
+    /// of the device, are open.
+    pub async fn create_session(
+        &self,
+        device_id: &str,
+        offer: SessionDescription,
+    ) -> Result<SignalingSession, SignalingError> {
+        if offer.sdp_type != SdpType::Offer {
+            return Err(SignalingError::InvalidSdp("expected an offer"));
+        }
+        validate_sdp(&offer.sdp)?;
+
+        let now = Utc::now();
+        let session = SignalingSession {
+            id: Uuid::new_v4().to_string(),
+            device_id: device_id.to_string(),
+            offer,
+            answer: None,
+            offerer_candidates: Vec::new(),
+            answerer_candidates: Vec::new(),
+            created_at: now,
+            expires_at: now + self.ttl,
+        };
+
+        let mut sessions = self.sessions.write().await;
+        sessions.retain(|_, s| s.expires_at > now);
+        let own = sessions.values().filter(|s| s.device_id == device_id);
+        let in_the_way = if sessions.len() >= MAX_SESSIONS {
+            sessions.values().map(|s| s.expires_at).min()
+        } else if own.clone().count() >= MAX_SESSIONS_PER_DEVICE {
+            own.map(|s| s.expires_at).min()
+        } else {
+            None
+        };
+        if let Some(expires_at) = in_the_way {
+            return Err(SignalingError::TooManySessions {
+                retry_after: (expires_at - now).to_std().unwrap_or_default(),
+            });
+        }
+        sessions.insert(session.id.clone(), session.clone());
+        Ok(session)
+    }
+
+    /// Get a session that has not expired
+    pub async fn get_session(&self, id: &str) -> Result<SignalingSession, SignalingError> {
+        let sessions = self.sessions.read().await;
+        sessions
+            .get(id)
+            .filter(|s| s.expires_at > Utc::now())
+            .cloned()
+            .ok_or(SignalingError::NotFound)
+    }
+
+    /// Store the answerer's SDP answer
+    pub async fn set_answer(
+        &self,
+        id: &str,
+        answer: SessionDescription,
+    ) -> Result<(), SignalingError> {
+        if answer.sdp_type != SdpType::Answer {
+            return Err(SignalingError::InvalidSdp("expected an answer"));
+        }
+        validate_sdp(&answer.sdp)?;
+
+        let mut sessions = self.sessions.write().await;
+        let session = live_session(&mut sessions, id)?;
+        if session.answer.is_some() {
+            return Err(SignalingError::AlreadyAnswered);
+        }
+        session.answer = Some(answer);
+        Ok(())
+    }
+
+    /// Add an ICE candidate gathered by one side
+    pub async fn add_candidate(
+        &self,
+        id: &str,
+        role: Role,
+        candidate: IceCandidate,
+    ) -> Result<(), SignalingError> {
+        if !candidate.candidate.is_empty() && !candidate.candidate.starts_with("candidate:") {
+            return Err(SignalingError::InvalidCandidate);
+        }
+
+        let mut sessions = self.sessions.write().await;
+        let session = live_session(&mut sessions, id)?;
+        let candidates = match role {
+            Role::Offerer => &mut session.offerer_candidates,
+            Role::Answerer => &mut session.answerer_candidates,
+        };
+        if candidates.len() >= MAX_CANDIDATES {
+            return Err(SignalingError::TooManyCandidates);
+        }
+        candidates.push(candidate);
+        Ok(())
+    }
+
+    /// Candidates gathered by `role`, starting at index `since`
+    pub async fn candidates(
+        &self,
+        id: &str,
+        role: Role,
+        since: usize,
+    ) -> Result<Vec<IceCandidate>, SignalingError> {
+        let session = self.get_session(id).await?;
+        let candidates = match role {
+            Role::Offerer => session.offerer_candidates,
+            Role::Answerer => session.answerer_candidates,
+        };
+        Ok(candidates.into_iter().skip(since).collect())
+    }
+
+    /// Close a session
+    pub async fn remove_session(&self, id: &str) -> Result<(), SignalingError> {
+        let mut sessions = self.sessions.write().await;
+        sessions
+            .remove(id)
+            .map(|_| ())
+            .ok_or(SignalingError::NotFound)
+    }
+}
+
+impl Default for SignalingHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn live_session<'a>(
+    sessions: &'a mut HashMap<String, SignalingSession>,
+    id: &str,
+) -> Result<&'a mut SignalingSession, SignalingError> {
+    sessions
+        .get_mut(id)
+        .filter(|s| s.expires_at > Utc::now())
+        .ok_or(SignalingError::NotFound)
+}
+
+/// Check that an SDP blob has the mandatory session-level lines and a media section
+pub fn validate_sdp(sdp: &str) -> Result<(), SignalingError> {
+    let mut lines = sdp.lines().map(str::trim_end);
+    if lines.next() != Some("v=0") {
+        return Err(SignalingError::InvalidSdp("must start with v=0"));
+    }
+
+    let (mut origin, mut name, mut media) = (false, false, false);
+    for line in lines {
+        if line.len() < 2 || line.as_bytes()[1] != b'=' {
+            if line.is_empty() {
+                continue;
+            }
+            return Err(SignalingError::InvalidSdp("malformed line"));
+        }
+        match &line[..2] {
+            "o=" => origin = true,
+            "s=" => name = true,
+            "m=" => media = true,
+            _ => {}
+        }
+    }
+
+    if !origin || !name {
+        return Err(SignalingError::InvalidSdp("missing o= or s= line"));
+    }
+    if !media {
+        return Err(SignalingError::InvalidSdp("no media section"));
+    }
+    Ok(())
+}
+
+/// Request to open a signaling session
+#[derive(Debug, Deserialize)]
+pub struct OfferRequest {
+    pub device_id: String,
+    pub offer: SessionDescription,
+}
+
+/// Request to publish an ICE candidate
+#[derive(Debug, Deserialize)]
+pub struct CandidateRequest {
+    pub role: Role,
+    #[serde(flatten)]
+    pub candidate: IceCandidate,
+}
+
+/// Query of the candidate polling endpoint
+#[derive(Debug, Deserialize)]
+pub struct CandidateQuery {
+    pub role: Role,
+    #[serde(default)]
+    pub since: usize,
+}
+
+/// Session `id`, if the caller may act for its device
+async fn authorized_session(
+    state: &AppState,
+    auth: &DeviceAuth,
+    id: &str,
+) -> Result<SignalingSession, AppError> {
+    let session = state.signaling.get_session(id).await?;
+    auth.authorize(&session.device_id)?;
+    Ok(session)
+}
+
+/// Open a signaling session with an SDP offer
+pub async fn create_offer(
+    State(state): State<AppState>,
+    auth: DeviceAuth,
+    ApiJson(payload): ApiJson<OfferRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    auth.authorize(&payload.device_id)?;
+    if state.db.get_device(&payload.device_id).await?.is_none() {
+        return Err(AppError::not_found("Device"));
+    }
+
+    let session = state
+        .signaling
+        .create_session(&payload.device_id, payload.offer)
+        .await?;
+    audit::record(
+        &state.db,
+        AuditKind::SessionCreated
+            .event(&Actor::Device(session.device_id.clone()))
+            .device(&session.device_id)
+            .details(serde_json::json!({
+                "session_id": session.id,
+                "transport": "webrtc",
+            })),
+    )
+    .await;
+
+    tracing::info!(
+        "Signaling session {} opened for device {}",
+        session.id,
+        session.device_id
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "session_id": session.id,
+            "expires_at": session.expires_at,
+        })),
+    ))
+}
+
+/// Get a signaling session (offer, answer and candidates)
+pub async fn get_session(
+    State(state): State<AppState>,
+    auth: DeviceAuth,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let session = authorized_session(&state, &auth, &session_id).await?;
+    Ok(Json(session))
+}
+
+/// Post the SDP answer of a session
+pub async fn post_answer(
+    State(state): State<AppState>,
+    auth: DeviceAuth,
+    Path(session_id): Path<String>,
+    ApiJson(answer): ApiJson<SessionDescription>,
+) -> Result<impl IntoResponse, AppError> {
+    authorized_session(&state, &auth, &session_id).await?;
+    state.signaling.set_answer(&session_id, answer).await?;
+    tracing::info!("Signaling session {} answered", session_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Publish an ICE candidate
+pub async fn post_candidate(
+    State(state): State<AppState>,
+    auth: DeviceAuth,
+    Path(session_id): Path<String>,
+    ApiJson(payload): ApiJson<CandidateRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    authorized_session(&state, &auth, &session_id).await?;
+    state
+        .signaling
+        .add_candidate(&session_id, payload.role, payload.candidate)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Answer a session with the server's own WebRTC peer
+///
+/// The device then sends files on data channels to the server (see
+/// [`p2p::accept_session`]).
+pub async fn accept_session(
+    State(state): State<AppState>,
+    auth: DeviceAuth,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let session = authorized_session(&state, &auth, &session_id).await?;
+    let answer = p2p::accept_session(&state, &session).await?;
+    tracing::info!("Signaling session {} answered by the server", session_id);
+    Ok(Json(answer))
+}
+
+/// Poll the ICE candidates published by one side
+pub async fn get_candidates(
+    State(state): State<AppState>,
+    auth: DeviceAuth,
+    Path(session_id): Path<String>,
+    ApiQuery(query): ApiQuery<CandidateQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    authorized_session(&state, &auth, &session_id).await?;
+    let candidates = state
+        .signaling
+        .candidates(&session_id, query.role, query.since)
+        .await?;
+    Ok(Json(serde_json::json!({
+        "candidates": candidates,
+        "next": query.since + candidates.len(),
+    })))
+}
+
+/// Close a signaling session
+pub async fn delete_session(
+    State(state): State<AppState>,
+    auth: DeviceAuth,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    authorized_session(&state, &auth, &session_id).await?;
+    state.signaling.remove_session(&session_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SDP: &str = "v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\nm=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n";
+
+    fn description(sdp_type: SdpType) -> SessionDescription {
+        SessionDescription {
+            sdp_type,
+            sdp: SDP.to_string(),
+        }
+    }
+
+    fn candidate(value: &str) -> IceCandidate {
+        IceCandidate {
+            candidate: value.to_string(),
+            sdp_mid: Some("0".to_string()),
+            sdp_m_line_index: Some(0),
+        }
+    }
+
+    #[test]
+    fn test_validate_sdp() {
+        assert!(validate_sdp(SDP).is_ok());
+        assert!(validate_sdp("webrtc-offer-placeholder").is_err());
+        assert!(validate_sdp("v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=-\r\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_offer_answer_exchange() {
+        let hub = SignalingHub::new();
+        let session = hub
+            .create_session("device-1", description(SdpType::Offer))
+            .await
+            .unwrap();
+
+        hub.set_answer(&session.id, description(SdpType::Answer))
+            .await
+            .unwrap();
+        assert_eq!(
+            hub.set_answer(&session.id, description(SdpType::Answer))
+                .await,
+            Err(SignalingError::AlreadyAnswered)
+        );
+
+        let stored = hub.get_session(&session.id).await.unwrap();
+        assert_eq!(stored.answer, Some(description(SdpType::Answer)));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_description_type() {
+        let hub = SignalingHub::new();
+        assert!(hub
+            .create_session("device-1", description(SdpType::Answer))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_candidate_cursor() {
+        let hub = SignalingHub::new();
+        let session = hub
+            .create_session("device-1", description(SdpType::Offer))
+            .await
+            .unwrap();
+
+        for port in [5000, 5001] {
+            let value = format!("candidate:1 1 UDP 2122252543 192.168.1.2 {} typ host", port);
+            hub.add_candidate(&session.id, Role::Answerer, candidate(&value))
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            hub.add_candidate(&session.id, Role::Offerer, candidate("bogus"))
+                .await,
+            Err(SignalingError::InvalidCandidate)
+        );
+
+        let all = hub
+            .candidates(&session.id, Role::Answerer, 0)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 2);
+        let rest = hub
+            .candidates(&session.id, Role::Answerer, 1)
+            .await
+            .unwrap();
+        assert_eq!(rest, all[1..].to_vec());
+        assert!(hub
+            .candidates(&session.id, Role::Offerer, 0)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_open_sessions_are_capped() {
+        let hub = SignalingHub::new();
+        for _ in 0..MAX_SESSIONS_PER_DEVICE {
+            hub.create_session("device-1", description(SdpType::Offer))
+                .await
+                .unwrap();
+        }
+        let error = hub
+            .create_session("device-1", description(SdpType::Offer))
+            .await
+            .unwrap_err();
+        let SignalingError::TooManySessions { retry_after } = error else {
+            panic!("{:?}", error);
+        };
+        assert!(retry_after <= std::time::Duration::from_secs(SESSION_TTL_MINUTES as u64 * 60));
+
+        // Other devices share the global cap only
+        for i in MAX_SESSIONS_PER_DEVICE..MAX_SESSIONS {
+            hub.create_session(&format!("device-{}", i), description(SdpType::Offer))
+                .await
+                .unwrap();
+        }
+        assert!(matches!(
+            hub.create_session("device-99", description(SdpType::Offer))
+                .await,
+            Err(SignalingError::TooManySessions { .. })
+        ));
+
+        // Closed sessions make room again
+        let session = hub.sessions.read().await.keys().next().cloned().unwrap();
+        hub.remove_session(&session).await.unwrap();
+        assert!(hub
+            .create_session("device-99", description(SdpType::Offer))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_expired_session_is_gone() {
+        let hub = SignalingHub::with_ttl(Duration::zero());
+        let session = hub
+            .create_session("device-1", description(SdpType::Offer))
+            .await
+            .unwrap();
+        assert_eq!(
+            hub.get_session(&session.id).await.unwrap_err(),
+            SignalingError::NotFound
+        );
+    }
+}
@@ -0,0 +1,800 @@
+//! Direct TCP transfer channel
+//!
+//! A length-prefixed binary protocol for high-throughput LAN transfers
+//! without the overhead of HTTP multipart uploads. Every frame is encoded as:
+//!
+//! ```text
+//! +---------+----------------+-----------------+
+//! | type u8 | length u32 BE  | payload         |
+//! +---------+----------------+-----------------+
+//! ```
+//!
+//! The client opens a session with an empty `Open` frame and the server
+//! answers with a `Challenge` carrying a fresh nonce. The client replies
+//! with a `Hello` naming the device and the transfer (previously created
+//! through `POST /api/v1/transfer/init`), authenticated by a tag over the
+//! nonce keyed with the device's pairing secret, so a recorded `Hello`
+//! cannot be replayed. `Data` frames then carry chunks at explicit offsets.
+//! The server answers each frame with an `Ack` and the client ends the
+//! session with `Close`, which assembles and verifies the file and
+//! completes the transfer record.
+
+use anyhow::Result;
+use rand::RngCore;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::audit::{self, Actor, AuditKind};
+use super::metrics::Transport;
+use super::p2p::{ConnectionType, PeerConnection};
+use super::upload;
+use crate::crypto::keys::derive_session_key;
+use crate::db::models::{token_hash, Transfer};
+use crate::AppState;
+
+/// Maximum accepted payload size of a single frame (16 MiB)
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// HKDF context used to derive the `Hello` authentication tag
+const HELLO_AUTH_INFO: &[u8] = b"bridgex-tcp-hello";
+
+/// How long a new connection may take to send its `Hello`
+const HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+
+const FRAME_HELLO: u8 = 0x01;
+const FRAME_DATA: u8 = 0x02;
+const FRAME_ACK: u8 = 0x03;
+const FRAME_CLOSE: u8 = 0x04;
+const FRAME_ERROR: u8 = 0x05;
+const FRAME_CHALLENGE: u8 = 0x06;
+const FRAME_OPEN: u8 = 0x07;
+
+/// Protocol frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    /// First frame of a session, asking the server for a `Challenge`
+    Open,
+    /// Sent by the server in reply to `Open`; the `Hello` tag covers `nonce`
+    Challenge { nonce: [u8; 32] },
+    /// Opens a session for a transfer
+    Hello {
+        device_id: String,
+        transfer_id: String,
+        auth: [u8; 32],
+    },
+    /// File chunk at the given byte offset
+    Data { offset: u64, payload: Vec<u8> },
+    /// Acknowledges a frame; `length` is the number of bytes accepted at `offset`
+    Ack { offset: u64, length: u64 },
+    /// Ends the session; the server answers with the final `Ack`
+    Close,
+    /// Fatal error, the connection is closed after sending it
+    Error { message: String },
+}
+
+/// Framing and protocol errors
+#[derive(Debug, thiserror::Error)]
+pub enum ProtocolError {
+    #[error("unknown frame type 0x{0:02x}")]
+    UnknownFrame(u8),
+    #[error("frame of {0} bytes exceeds the maximum size")]
+    FrameTooLarge(usize),
+    #[error("malformed frame payload")]
+    Malformed,
+    #[error("unexpected frame: {0}")]
+    Unexpected(&'static str),
+    #[error("session rejected: {0}")]
+    Rejected(&'static str),
+    #[error("transfer is {0} and accepts no data")]
+    NotWritable(String),
+    #[error("chunk at offset {0} ends past the declared file size")]
+    OutOfBounds(u64),
+    #[error("field of {0} bytes does not fit in a frame")]
+    FieldTooLong(usize),
+    #[error("transfer refused: {0}")]
+    Assembly(#[from] upload::AssemblyError),
+    #[error("peer error: {0}")]
+    Peer(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl Frame {
+    /// Encode the frame into its wire representation
+    pub fn encode(&self) -> Result<Vec<u8>, ProtocolError> {
+        let (frame_type, payload) = match self {
+            Frame::Open => (FRAME_OPEN, Vec::new()),
+            Frame::Challenge { nonce } => (FRAME_CHALLENGE, nonce.to_vec()),
+            Frame::Hello {
+                device_id,
+                transfer_id,
+                auth,
+            } => {
+                let mut payload = Vec::with_capacity(4 + device_id.len() + transfer_id.len() + 32);
+                put_str(&mut payload, device_id)?;
+                put_str(&mut payload, transfer_id)?;
+                payload.extend_from_slice(auth);
+                (FRAME_HELLO, payload)
+            }
+            Frame::Data {
+                offset,
+                payload: data,
+            } => {
+                let mut payload = Vec::with_capacity(8 + data.len());
+                payload.extend_from_slice(&offset.to_be_bytes());
+                payload.extend_from_slice(data);
+                (FRAME_DATA, payload)
+            }
+            Frame::Ack { offset, length } => {
+                let mut payload = Vec::with_capacity(16);
+                payload.extend_from_slice(&offset.to_be_bytes());
+                payload.extend_from_slice(&length.to_be_bytes());
+                (FRAME_ACK, payload)
+            }
+            Frame::Close => (FRAME_CLOSE, Vec::new()),
+            Frame::Error { message } => (FRAME_ERROR, message.as_bytes().to_vec()),
+        };
+
+        let len =
+            u32::try_from(payload.len()).map_err(|_| ProtocolError::FrameTooLarge(payload.len()))?;
+        let mut buf = Vec::with_capacity(5 + payload.len());
+        buf.push(frame_type);
+        buf.extend_from_slice(&len.to_be_bytes());
+        buf.extend_from_slice(&payload);
+        Ok(buf)
+    }
+
+    /// Decode a frame from its type and payload
+    pub fn decode(frame_type: u8, payload: Vec<u8>) -> Result<Self, ProtocolError> {
+        match frame_type {
+            FRAME_OPEN => Ok(Frame::Open),
+            FRAME_CHALLENGE => {
+                let nonce = payload
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| ProtocolError::Malformed)?;
+                Ok(Frame::Challenge { nonce })
+            }
+            FRAME_HELLO => {
+                let mut rest = payload.as_slice();
+                let device_id = take_str(&mut rest)?;
+                let transfer_id = take_str(&mut rest)?;
+                let auth = rest.try_into().map_err(|_| ProtocolError::Malformed)?;
+                Ok(Frame::Hello {
+                    device_id,
+                    transfer_id,
+                    auth,
+                })
+            }
+            FRAME_DATA => {
+                if payload.len() < 8 {
+                    return Err(ProtocolError::Malformed);
+                }
+                let offset = u64::from_be_bytes(payload[..8].try_into().unwrap());
+                Ok(Frame::Data {
+                    offset,
+                    payload: payload[8..].to_vec(),
+                })
+            }
+            FRAME_ACK => {
+                if payload.len() != 16 {
+                    return Err(ProtocolError::Malformed);
+                }
+                Ok(Frame::Ack {
+                    offset: u64::from_be_bytes(payload[..8].try_into().unwrap()),
+                    length: u64::from_be_bytes(payload[8..].try_into().unwrap()),
+                })
+            }
+            FRAME_CLOSE => Ok(Frame::Close),
+            FRAME_ERROR => Ok(Frame::Error {
+                message: String::from_utf8_lossy(&payload).into_owned(),
+            }),
+            other => Err(ProtocolError::UnknownFrame(other)),
+        }
+    }
+}
+
+fn put_str(buf: &mut Vec<u8>, value: &str) -> Result<(), ProtocolError> {
+    let len = u16::try_from(value.len()).map_err(|_| ProtocolError::FieldTooLong(value.len()))?;
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn take_str(rest: &mut &[u8]) -> Result<String, ProtocolError> {
+    if rest.len() < 2 {
+        return Err(ProtocolError::Malformed);
+    }
+    let len = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+    if rest.len() < 2 + len {
+        return Err(ProtocolError::Malformed);
+    }
+    let value = std::str::from_utf8(&rest[2..2 + len])
+        .map_err(|_| ProtocolError::Malformed)?
+        .to_string();
+    *rest = &rest[2 + len..];
+    Ok(value)
+}
+
+/// Read the next frame, returning `None` on a clean end of stream
+pub async fn read_frame<R>(reader: &mut R) -> Result<Option<Frame>, ProtocolError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut header = [0u8; 5];
+    match reader.read_exact(&mut header).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let len = u32::from_be_bytes(header[1..].try_into().unwrap()) as usize;
+    if len > MAX_FRAME_SIZE + 8 {
+        return Err(ProtocolError::FrameTooLarge(len));
+    }
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    Frame::decode(header[0], payload).map(Some)
+}
+
+/// Write a frame and flush it
+pub async fn write_frame<W>(writer: &mut W, frame: &Frame) -> Result<(), ProtocolError>
+where
+    W: AsyncWrite + Unpin,
+{
+    writer.write_all(&frame.encode()?).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Compute the `Hello` authentication tag answering a `Challenge`
+///
+/// Keyed with the hash of the pairing token (see
+/// [`token_hash`](crate::db::models::token_hash)), so only a peer that
+/// scanned the device's QR code can open a session for it. The tag covers
+/// the server's nonce and the transfer, so it is only good for one session.
+pub fn hello_auth(
+    token_hash: &[u8; 32],
+    nonce: &[u8; 32],
+    device_id: &str,
+    transfer_id: &str,
+) -> [u8; 32] {
+    let mut info = HELLO_AUTH_INFO.to_vec();
+    info.extend_from_slice(nonce);
+    for id in [device_id, transfer_id] {
+        info.extend_from_slice(&(id.len() as u64).to_be_bytes());
+        info.extend_from_slice(id.as_bytes());
+    }
+    derive_session_key(token_hash, &info)
+}
+
+/// Compare tags in constant time
+pub(crate) fn tags_match(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Accept TCP transfer sessions until the listener fails or shutdown starts
+///
+/// Sessions already running are left to finish within the drain period.
+pub async fn serve(listener: TcpListener, state: AppState) -> Result<()> {
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = state.shutdown.triggered() => return Ok(()),
+        };
+        let state = state.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, peer, state).await {
+                tracing::warn!("TCP session with {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// Run a single transfer session
+async fn handle_connection(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    state: AppState,
+) -> Result<(), ProtocolError> {
+    stream.set_nodelay(true)?;
+
+    let (device_id, transfer) =
+        match accept_hello(&mut stream, &state, peer.ip(), Transport::Tcp).await {
+            Ok(session) => session,
+            Err(e) => return Err(reject(&mut stream, e).await),
+        };
+
+    tracing::info!(
+        "TCP session from {} for transfer {} (device {})",
+        peer,
+        transfer.id,
+        device_id
+    );
+
+    let session = register_connection(&state, &device_id, ConnectionType::Tcp).await;
+    let result = receive_transfer(&mut stream, &state, &device_id, &transfer, Transport::Tcp).await;
+    state.connections.remove_connection(session).await;
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) => Err(reject(&mut stream, e).await),
+    }
+}
+
+/// Report a fatal error to the peer before closing the session
+pub(crate) async fn reject<S>(stream: &mut S, error: ProtocolError) -> ProtocolError
+where
+    S: AsyncWrite + Unpin,
+{
+    let message = error.to_string();
+    write_frame(stream, &Frame::Error { message }).await.ok();
+    error
+}
+
+/// Register a peer authenticated on a direct transport with the connection manager
+///
+/// # Returns
+/// ID of the session in the connection manager
+pub(crate) async fn register_connection(
+    state: &AppState,
+    device_id: &str,
+    connection_type: ConnectionType,
+) -> u64 {
+    if let Err(e) = state.db.touch_device(device_id, chrono::Utc::now()).await {
+        tracing::warn!("Failed to update last_seen of {}: {}", device_id, e);
+    }
+
+    let device_name = state
+        .db
+        .get_device(device_id)
+        .await
+        .ok()
+        .flatten()
+        .map(|d| d.name)
+        .unwrap_or_default();
+
+    state
+        .connections
+        .add_connection(PeerConnection {
+            device_id: device_id.to_string(),
+            device_name,
+            connection_type,
+            established_at: chrono::Utc::now(),
+        })
+        .await
+}
+
+/// Challenge the peer and validate its `Hello` frame
+///
+/// Successful and failed authentications of paired devices are audited.
+/// Rejected hellos count towards the lockout of the peer's IP, see
+/// [`RateLimiter`](crate::RateLimiter). A peer that does not complete the
+/// handshake within `HELLO_TIMEOUT` is dropped.
+///
+/// # Returns
+/// The authenticated device ID and the transfer of the session
+pub(crate) async fn accept_hello<S>(
+    stream: &mut S,
+    state: &AppState,
+    peer: IpAddr,
+    transport: Transport,
+) -> Result<(String, Transfer), ProtocolError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    if state.rate_limiter.lockout(peer).is_some() {
+        return Err(ProtocolError::Rejected("too many failed attempts"));
+    }
+
+    let mut nonce = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let (device_id, transfer_id, auth) =
+        tokio::time::timeout(HELLO_TIMEOUT, challenge(stream, nonce))
+            .await
+            .map_err(|_| ProtocolError::Unexpected("timed out waiting for hello"))??;
+
+    let transfer = match verify_hello(state, &nonce, &device_id, &transfer_id, &auth, transport)
+        .await
+    {
+        Ok(transfer) => {
+            state.rate_limiter.clear_failures(peer);
+            transfer
+        }
+        Err(e) => {
+            if let ProtocolError::Rejected(_) = e {
+                if let Some(lockout) = state.rate_limiter.record_failure(peer) {
+                    tracing::warn!(
+                        "Locked out {} for {}s after repeated failed authentications",
+                        peer,
+                        lockout.as_secs()
+                    );
+                }
+            }
+            return Err(e);
+        }
+    };
+
+    if !upload::accepts_chunks(&transfer.status) {
+        return Err(ProtocolError::NotWritable(transfer.status));
+    }
+    state
+        .db
+        .update_transfer_status(&transfer_id, "uploading", None)
+        .await
+        .map_err(|e| ProtocolError::Peer(format!("database error: {}", e)))?;
+
+    write_frame(
+        stream,
+        &Frame::Ack {
+            offset: 0,
+            length: 0,
+        },
+    )
+    .await?;
+    Ok((device_id, transfer))
+}
+
+/// Answer the peer's `Open` with a `Challenge` and read its `Hello`
+///
+/// # Returns
+/// The device ID, transfer ID and tag of the `Hello`
+async fn challenge<S>(
+    stream: &mut S,
+    nonce: [u8; 32],
+) -> Result<(String, String, [u8; 32]), ProtocolError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    match read_frame(stream).await? {
+        Some(Frame::Open) => {}
+        Some(_) => return Err(ProtocolError::Unexpected("expected open")),
+        None => return Err(ProtocolError::Unexpected("connection closed before hello")),
+    }
+    write_frame(stream, &Frame::Challenge { nonce }).await?;
+
+    match read_frame(stream).await? {
+        Some(Frame::Hello {
+            device_id,
+            transfer_id,
+            auth,
+        }) => Ok((device_id, transfer_id, auth)),
+        Some(_) => Err(ProtocolError::Unexpected("expected hello")),
+        None => Err(ProtocolError::Unexpected("connection closed before hello")),
+    }
+}
+
+/// Check that a `Hello` answering `nonce` comes from the paired device
+/// owning the transfer
+async fn verify_hello(
+    state: &AppState,
+    nonce: &[u8; 32],
+    device_id: &str,
+    transfer_id: &str,
+    auth: &[u8; 32],
+    transport: Transport,
+) -> Result<Transfer, ProtocolError> {
+    let device = state
+        .db
+        .get_device(device_id)
+        .await
+        .map_err(|e| ProtocolError::Peer(format!("database error: {}", e)))?
+        .ok_or(ProtocolError::Rejected("unknown device"))?;
+
+    let actor = Actor::Device(device_id.to_string());
+    let now = chrono::Utc::now();
+    let expected = device
+        .usable_token_hash(now)
+        .and_then(|token_hash| <&[u8; 32]>::try_from(token_hash).ok())
+        .map(|token_hash| hello_auth(token_hash, nonce, device_id, transfer_id));
+    if !expected.is_some_and(|expected| tags_match(&expected, auth)) {
+        audit::record(
+            &state.db,
+            AuditKind::VerificationFailed
+                .event(&actor)
+                .device(device_id)
+                .transfer(transfer_id)
+                .details(serde_json::json!({ "transport": transport.as_str() })),
+        )
+        .await;
+        return Err(ProtocolError::Rejected("authentication failed"));
+    }
+    if device.confirmed_at.is_none() {
+        state
+            .db
+            .confirm_device(device_id, now)
+            .await
+            .map_err(|e| ProtocolError::Peer(format!("database error: {}", e)))?;
+    }
+    audit::record(
+        &state.db,
+        AuditKind::DeviceVerified
+            .event(&actor)
+            .device(device_id)
+            .transfer(transfer_id)
+            .details(serde_json::json!({ "transport": transport.as_str() })),
+    )
+    .await;
+
+    let transfer = state
+        .db
+        .get_transfer(transfer_id)
+        .await
+        .map_err(|e| ProtocolError::Peer(format!("database error: {}", e)))?
+        .ok_or(ProtocolError::Rejected("unknown transfer"))?;
+
+    if transfer.device_id != device_id {
+        return Err(ProtocolError::Rejected("transfer belongs to another device"));
+    }
+    Ok(transfer)
+}
+
+/// Receive data frames until `Close`, then assemble the file
+///
+/// The outcome is audited as `transfer.completed` or `transfer.failed`.
+pub(crate) async fn receive_transfer<S>(
+    stream: &mut S,
+    state: &AppState,
+    device_id: &str,
+    transfer: &Transfer,
+    transport: Transport,
+) -> Result<(), ProtocolError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let _guard = state.shutdown.begin(&transfer.id);
+    let result = receive_frames(stream, state, device_id, transfer, transport).await;
+    if let Err(e) = &result {
+        let outcome = Err(e.to_string());
+        audit::record(
+            &state.db,
+            audit::transfer_outcome(device_id, &transfer.id, transport, outcome),
+        )
+        .await;
+    }
+    result
+}
+
+/// Frame loop of [`receive_transfer`]
+async fn receive_frames<S>(
+    stream: &mut S,
+    state: &AppState,
+    device_id: &str,
+    transfer: &Transfer,
+    transport: Transport,
+) -> Result<(), ProtocolError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let transfer_id = transfer.id.as_str();
+    loop {
+        match read_frame(stream).await? {
+            Some(Frame::Data { offset, payload }) => {
+                if payload.len() > MAX_FRAME_SIZE {
+                    return Err(ProtocolError::FrameTooLarge(payload.len()));
+                }
+                upload::check_chunk_bounds(transfer, offset, payload.len())
+                    .map_err(|_| ProtocolError::OutOfBounds(offset))?;
+                upload::write_chunk(
+                    &state.config.storage.upload_dir,
+                    transfer_id,
+                    offset,
+                    &payload,
+                )?;
+                state.metrics.record_chunk(transport, payload.len());
+                tracing::debug!(
+                    "Direct chunk for transfer {} at offset {} ({} bytes)",
+                    transfer_id,
+                    offset,
+                    payload.len()
+                );
+                write_frame(
+                    stream,
+                    &Frame::Ack {
+                        offset,
+                        length: payload.len() as u64,
+                    },
+                )
+                .await?;
+            }
+            Some(Frame::Close) => {
+                let (final_path, total_bytes) = upload::finish_transfer(state, transfer).await?;
+                tracing::info!(
+                    "Direct transfer {} assembled at {:?} ({} bytes)",
+                    transfer_id,
+                    final_path,
+                    total_bytes
+                );
+                state.metrics.record_transfer(transport, transfer.created_at);
+                audit::record(
+                    &state.db,
+                    audit::transfer_outcome(device_id, transfer_id, transport, Ok(total_bytes)),
+                )
+                .await;
+
+                write_frame(
+                    stream,
+                    &Frame::Ack {
+                        offset: 0,
+                        length: total_bytes,
+                    },
+                )
+                .await?;
+                return Ok(());
+            }
+            Some(Frame::Error { message }) => return Err(ProtocolError::Peer(message)),
+            Some(_) => return Err(ProtocolError::Unexpected("expected data or close")),
+            None => return Err(ProtocolError::Unexpected("connection closed before close")),
+        }
+    }
+}
+
+/// Send a file over the TCP channel
+///
+/// # Arguments
+/// * `addr` - Address of the TCP transfer listener
+/// * `device_id` - Paired device sending the file
+/// * `token` - Pairing token from that device's QR code
+/// * `transfer_id` - Transfer created through `POST /api/v1/transfer/init`
+/// * `data` - File contents
+/// * `chunk_size` - Size of each data frame
+///
+/// # Returns
+/// Total number of bytes the server assembled
+pub async fn send_transfer(
+    addr: SocketAddr,
+    device_id: &str,
+    token: &[u8],
+    transfer_id: &str,
+    data: &[u8],
+    chunk_size: usize,
+) -> Result<u64, ProtocolError> {
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.set_nodelay(true)?;
+    send_session(&mut stream, device_id, token, transfer_id, data, chunk_size).await
+}
+
+/// Client side of a transfer session over any ordered byte stream
+pub(crate) async fn send_session<S>(
+    stream: &mut S,
+    device_id: &str,
+    token: &[u8],
+    transfer_id: &str,
+    data: &[u8],
+    chunk_size: usize,
+) -> Result<u64, ProtocolError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    write_frame(stream, &Frame::Open).await?;
+    let nonce = match read_frame(stream).await? {
+        Some(Frame::Challenge { nonce }) => nonce,
+        Some(Frame::Error { message }) => return Err(ProtocolError::Peer(message)),
+        Some(_) => return Err(ProtocolError::Unexpected("expected challenge")),
+        None => return Err(ProtocolError::Unexpected("connection closed")),
+    };
+    let hello = Frame::Hello {
+        device_id: device_id.to_string(),
+        transfer_id: transfer_id.to_string(),
+        auth: hello_auth(&token_hash(token), &nonce, device_id, transfer_id),
+    };
+    write_frame(stream, &hello).await?;
+    expect_ack(stream).await?;
+
+    let chunk_size = chunk_size.clamp(1, MAX_FRAME_SIZE);
+    for (index, chunk) in data.chunks(chunk_size).enumerate() {
+        let offset = (index * chunk_size) as u64;
+        let frame = Frame::Data {
+            offset,
+            payload: chunk.to_vec(),
+        };
+        write_frame(stream, &frame).await?;
+        expect_ack(stream).await?;
+    }
+
+    write_frame(stream, &Frame::Close).await?;
+    let (_, total_bytes) = expect_ack(stream).await?;
+    Ok(total_bytes)
+}
+
+async fn expect_ack<S>(stream: &mut S) -> Result<(u64, u64), ProtocolError>
+where
+    S: AsyncRead + Unpin,
+{
+    match read_frame(stream).await? {
+        Some(Frame::Ack { offset, length }) => Ok((offset, length)),
+        Some(Frame::Error { message }) => Err(ProtocolError::Peer(message)),
+        Some(_) => Err(ProtocolError::Unexpected("expected ack")),
+        None => Err(ProtocolError::Unexpected("connection closed")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn roundtrip(frame: Frame) -> Frame {
+        let bytes = frame.encode().unwrap();
+        let mut reader = bytes.as_slice();
+        read_frame(&mut reader).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_frame_roundtrip() {
+        let frames = vec![
+            Frame::Open,
+            Frame::Challenge { nonce: [9u8; 32] },
+            Frame::Hello {
+                device_id: "device-1".to_string(),
+                transfer_id: "transfer-1".to_string(),
+                auth: [7u8; 32],
+            },
+            Frame::Data {
+                offset: 1 << 40,
+                payload: vec![1, 2, 3, 4],
+            },
+            Frame::Ack {
+                offset: 42,
+                length: 1024,
+            },
+            Frame::Close,
+            Frame::Error {
+                message: "boom".to_string(),
+            },
+        ];
+
+        for frame in frames {
+            assert_eq!(roundtrip(frame.clone()).await, frame);
+        }
+    }
+
+    #[test]
+    fn test_overlong_strings_are_refused() {
+        let hello = Frame::Hello {
+            device_id: "d".repeat(usize::from(u16::MAX) + 1),
+            transfer_id: "transfer-1".to_string(),
+            auth: [0u8; 32],
+        };
+        assert!(matches!(
+            hello.encode(),
+            Err(ProtocolError::FieldTooLong(65536))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_clean_eof() {
+        let mut reader: &[u8] = &[];
+        assert!(read_frame(&mut reader).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unknown_and_oversized_frames() {
+        let mut reader: &[u8] = &[0x7f, 0, 0, 0, 0];
+        assert!(matches!(
+            read_frame(&mut reader).await,
+            Err(ProtocolError::UnknownFrame(0x7f))
+        ));
+
+        let mut reader: &[u8] = &[FRAME_DATA, 0xff, 0xff, 0xff, 0xff];
+        assert!(matches!(
+            read_frame(&mut reader).await,
+            Err(ProtocolError::FrameTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn test_hello_auth_is_bound_to_device() {
+        let key = token_hash(b"pairing token");
+        let nonce = [1u8; 32];
+        let tag = hello_auth(&key, &nonce, "a", "t");
+        assert_eq!(tag, hello_auth(&key, &nonce, "a", "t"));
+        assert_ne!(tag, hello_auth(&key, &nonce, "b", "t"));
+        assert_ne!(tag, hello_auth(&key, &nonce, "a", "u"));
+        assert_ne!(tag, hello_auth(&key, &[2u8; 32], "a", "t"));
+        assert_ne!(tag, hello_auth(&token_hash(b"other"), &nonce, "a", "t"));
+    }
+}
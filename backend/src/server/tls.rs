@@ -0,0 +1,114 @@
+//! HTTPS for the HTTP API
+//!
+//! When TLS is enabled (see [`Config::tls_enabled`](crate::Config::tls_enabled))
+//! the API is served over rustls with the same self-signed
+//! [`ServerCertificate`] the QUIC endpoint presents, so a single fingerprint
+//! identifies the server on every transport. Clients learn it from the
+//! pairing QR code (`fp`, with `tls=1`) and pin it instead of validating the
+//! certificate against a CA; see [`client_config`].
+
+use anyhow::Result;
+use axum::{extract::ConnectInfo, http::Request, Router};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper_util::rt::TokioIo;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tower::ServiceExt;
+
+use crate::crypto::cert::{pinned_client_config, ServerCertificate};
+use crate::Shutdown;
+
+/// ALPN protocol identifier of the HTTP API
+pub const ALPN_HTTP1: &[u8] = b"http/1.1";
+
+/// Time a client gets to complete the TLS handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pause after a failed accept, e.g. when out of file descriptors
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+
+/// TLS acceptor presenting the server certificate
+pub fn acceptor(certificate: &ServerCertificate) -> Result<TlsAcceptor> {
+    let config = certificate.server_config(
+        &[&rustls::version::TLS13, &rustls::version::TLS12],
+        &[ALPN_HTTP1],
+    )?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// rustls client configuration for the HTTP API of a paired server
+///
+/// # Arguments
+/// * `fingerprint` - Server certificate fingerprint received at pairing
+pub fn client_config(fingerprint: &str) -> Result<rustls::ClientConfig> {
+    pinned_client_config(
+        fingerprint,
+        &[&rustls::version::TLS13, &rustls::version::TLS12],
+        &[ALPN_HTTP1],
+    )
+}
+
+/// Serve `app` over TLS until shutdown starts
+///
+/// Like `axum::serve(..).with_graceful_shutdown(..)`: once shutdown is
+/// triggered no new connections are accepted, and the future completes when
+/// the open ones have closed. Handlers see the peer address as
+/// `ConnectInfo<SocketAddr>`.
+pub async fn serve(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    app: Router,
+    shutdown: Arc<Shutdown>,
+) -> std::io::Result<()> {
+    let graceful = GracefulShutdown::new();
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    tracing::warn!("Failed to accept HTTPS connection: {}", e);
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            },
+            _ = shutdown.triggered() => break,
+        };
+
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            let stream =
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => stream,
+                    Ok(Err(e)) => {
+                        tracing::debug!("TLS handshake with {} failed: {}", peer, e);
+                        return;
+                    }
+                    Err(_) => {
+                        tracing::debug!("TLS handshake with {} timed out", peer);
+                        return;
+                    }
+                };
+
+            let service = app.map_request(move |mut request: Request<Incoming>| {
+                request.extensions_mut().insert(ConnectInfo(peer));
+                request
+            });
+            let connection = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), TowerToHyperService::new(service));
+            if let Err(e) = watcher.watch(connection).await {
+                tracing::debug!("HTTPS connection from {} failed: {}", peer, e);
+            }
+        });
+    }
+
+    drop(listener);
+    graceful.shutdown().await;
+    Ok(())
+}
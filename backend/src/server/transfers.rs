@@ -0,0 +1,220 @@
+//! Transfer history API
+//!
+//! `GET /api/v1/transfers` lists transfers newest first by default, with
+//! optional filters. Results are paginated with an opaque cursor: pass the
+//! `next_cursor` of a page back as `cursor` to fetch the following one.
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::error::{ApiQuery, AppError};
+use crate::db::models::Transfer;
+use crate::db::{SortOrder, TransferQuery};
+use crate::AppState;
+
+/// Page size when `limit` is omitted
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+
+/// Largest accepted `limit`
+pub const MAX_PAGE_SIZE: u32 = 200;
+
+/// Statuses a transfer can be in
+///
+/// `interrupted` transfers were cut off by a server shutdown and can be resumed.
+pub const TRANSFER_STATUSES: &[&str] =
+    &["pending", "uploading", "interrupted", "completed", "failed"];
+
+/// Query parameters of `GET /api/v1/transfers`
+#[derive(Debug, Default, Deserialize)]
+pub struct ListTransfersParams {
+    pub device_id: Option<String>,
+    pub status: Option<String>,
+    /// RFC 3339 lower bound on `created_at`, inclusive
+    pub from: Option<DateTime<Utc>>,
+    /// RFC 3339 upper bound on `created_at`, exclusive
+    pub to: Option<DateTime<Utc>>,
+    /// File name substring
+    pub q: Option<String>,
+    /// `asc` or `desc` (default)
+    pub sort: Option<String>,
+    pub limit: Option<u32>,
+    pub cursor: Option<String>,
+}
+
+/// A page of transfers
+#[derive(Debug, Serialize)]
+pub struct TransferPage {
+    pub transfers: Vec<Transfer>,
+    /// Cursor of the next page, absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Transfer with details resolved for display
+#[derive(Debug, Serialize)]
+pub struct TransferDetail {
+    #[serde(flatten)]
+    pub transfer: Transfer,
+    /// Name of the device, if it is still paired
+    pub device_name: Option<String>,
+}
+
+/// Encode the position of a transfer as an opaque cursor
+pub fn encode_cursor(transfer: &Transfer) -> String {
+    URL_SAFE_NO_PAD.encode(format!(
+        "{}\n{}",
+        transfer.created_at.to_rfc3339(),
+        transfer.id
+    ))
+}
+
+/// Decode a cursor produced by [`encode_cursor`]
+pub fn decode_cursor(cursor: &str) -> Option<(DateTime<Utc>, String)> {
+    let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+    let (created_at, id) = decoded.split_once('\n')?;
+    let created_at = DateTime::parse_from_rfc3339(created_at).ok()?;
+    Some((created_at.with_timezone(&Utc), id.to_string()))
+}
+
+impl ListTransfersParams {
+    /// Validate the parameters into a database query
+    pub fn into_query(self) -> Result<TransferQuery, AppError> {
+        if let Some(status) = &self.status {
+            if !TRANSFER_STATUSES.contains(&status.as_str()) {
+                return Err(AppError::validation("Unknown status"));
+            }
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from >= to {
+                return Err(AppError::validation("`from` must be before `to`"));
+            }
+        }
+
+        let order = match self.sort.as_deref() {
+            None | Some("desc") => SortOrder::Desc,
+            Some("asc") => SortOrder::Asc,
+            Some(_) => return Err(AppError::validation("`sort` must be `asc` or `desc`")),
+        };
+        let limit = match self.limit {
+            None => DEFAULT_PAGE_SIZE,
+            Some(limit @ 1..=MAX_PAGE_SIZE) => limit,
+            Some(_) => return Err(AppError::validation("`limit` must be between 1 and 200")),
+        };
+        let after = match self.cursor.as_deref() {
+            None => None,
+            Some(cursor) => {
+                Some(decode_cursor(cursor).ok_or_else(|| AppError::validation("Invalid cursor"))?)
+            }
+        };
+
+        Ok(TransferQuery {
+            device_id: self.device_id,
+            status: self.status,
+            from: self.from,
+            to: self.to,
+            name: self.q.filter(|q| !q.is_empty()),
+            order,
+            after,
+            limit,
+        })
+    }
+}
+
+/// List transfers
+pub async fn list_transfers(
+    State(state): State<AppState>,
+    ApiQuery(params): ApiQuery<ListTransfersParams>,
+) -> Result<Json<TransferPage>, AppError> {
+    let mut query = params.into_query()?;
+    let limit = query.limit as usize;
+
+    // Fetch one extra row to learn whether another page follows
+    query.limit += 1;
+    let mut transfers = state.db.list_transfers(&query).await?;
+
+    let next_cursor = if transfers.len() > limit {
+        transfers.truncate(limit);
+        transfers.last().map(encode_cursor)
+    } else {
+        None
+    };
+
+    Ok(Json(TransferPage {
+        transfers,
+        next_cursor,
+    }))
+}
+
+/// Get a single transfer
+pub async fn get_transfer(
+    State(state): State<AppState>,
+    Path(transfer_id): Path<String>,
+) -> Result<Json<TransferDetail>, AppError> {
+    let transfer = state
+        .db
+        .get_transfer(&transfer_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Transfer"))?;
+
+    let device_name = state
+        .db
+        .get_device(&transfer.device_id)
+        .await?
+        .map(|device| device.name);
+
+    Ok(Json(TransferDetail {
+        transfer,
+        device_name,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let transfer = Transfer::new(
+            "transfer-1".to_string(),
+            "device".to_string(),
+            "a.txt".to_string(),
+            1,
+            "hash".to_string(),
+        );
+        let cursor = encode_cursor(&transfer);
+
+        assert_eq!(
+            decode_cursor(&cursor),
+            Some((transfer.created_at, transfer.id))
+        );
+        assert_eq!(decode_cursor("not a cursor"), None);
+    }
+
+    #[test]
+    fn test_params_validation() {
+        let params = |f: fn(&mut ListTransfersParams)| {
+            let mut params = ListTransfersParams::default();
+            f(&mut params);
+            params.into_query()
+        };
+
+        let query = params(|_| {}).unwrap();
+        assert_eq!(query.limit, DEFAULT_PAGE_SIZE);
+        assert_eq!(query.order, SortOrder::Desc);
+
+        assert!(params(|p| p.status = Some("lost".to_string())).is_err());
+        assert!(params(|p| p.sort = Some("newest".to_string())).is_err());
+        assert!(params(|p| p.limit = Some(0)).is_err());
+        assert!(params(|p| p.limit = Some(MAX_PAGE_SIZE + 1)).is_err());
+        assert!(params(|p| p.cursor = Some("%%".to_string())).is_err());
+        assert!(params(|p| {
+            p.from = Some(Utc::now());
+            p.to = Some(Utc::now() - chrono::Duration::days(1));
+        })
+        .is_err());
+    }
+}
@@ -1,18 +1,26 @@
 //! File upload handling
 
 use axum::{
-    body::Bytes,
-    extract::{Multipart, Path, State},
-    http::{HeaderMap, StatusCode},
+    extract::{
+        multipart::{MultipartError, MultipartRejection},
+        Multipart, Path, State,
+    },
+    http::StatusCode,
     response::IntoResponse,
     Json,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use serde_json::json;
+use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path as FsPath, PathBuf};
 
+use super::audit;
+use super::auth::DeviceAuth;
+use super::error::{ApiJson, AppError};
+use super::metrics::Transport;
+use crate::db::models::Transfer;
 use crate::AppState;
 
 #[derive(Debug, Deserialize)]
@@ -23,37 +31,54 @@ pub struct FinalizeRequest {
 /// Upload file chunk (multipart form)
 pub async fn upload_chunk(
     State(state): State<AppState>,
-    mut multipart: Multipart,
-) -> Result<impl IntoResponse, StatusCode> {
+    auth: DeviceAuth,
+    multipart: Result<Multipart, MultipartRejection>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut multipart = multipart?;
     let mut transfer_id: Option<String> = None;
     let mut offset: Option<usize> = None;
     let mut chunk_data: Option<Vec<u8>> = None;
 
     // Parse multipart fields
-    while let Some(field) = multipart.next_field().await.map_err(|e| {
-        tracing::error!("Multipart error: {}", e);
-        StatusCode::BAD_REQUEST
-    })? {
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
         let name = field.name().unwrap_or("").to_string();
         
         match name.as_str() {
             "transfer_id" => {
-                transfer_id = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
+                transfer_id = Some(field.text().await.map_err(multipart_error)?);
             }
             "offset" => {
-                let text = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
-                offset = text.parse().ok();
+                let text = field.text().await.map_err(multipart_error)?;
+                offset = Some(
+                    text.parse()
+                        .map_err(|_| AppError::validation("`offset` must be a non-negative integer"))?,
+                );
             }
             "chunk" => {
-                chunk_data = Some(field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?.to_vec());
+                chunk_data = Some(field.bytes().await.map_err(multipart_error)?.to_vec());
             }
             _ => {}
         }
     }
 
-    let transfer_id = transfer_id.ok_or(StatusCode::BAD_REQUEST)?;
-    let offset = offset.ok_or(StatusCode::BAD_REQUEST)?;
-    let chunk_data = chunk_data.ok_or(StatusCode::BAD_REQUEST)?;
+    let transfer_id = transfer_id.ok_or_else(|| AppError::validation("Missing `transfer_id` field"))?;
+    let offset = offset.ok_or_else(|| AppError::validation("Missing `offset` field"))?;
+    let chunk_data = chunk_data.ok_or_else(|| AppError::validation("Missing `chunk` field"))?;
+
+    // Only known transfers get a directory, which also keeps ids out of the filesystem path
+    let transfer = state
+        .db
+        .get_transfer(&transfer_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Transfer"))?;
+    auth.authorize(&transfer.device_id)?;
+    if !accepts_chunks(&transfer.status) {
+        return Err(AppError::Conflict(format!(
+            "Transfer is {} and accepts no chunks",
+            transfer.status
+        )));
+    }
+    check_chunk_bounds(&transfer, offset as u64, chunk_data.len())?;
 
     tracing::info!(
         "Upload chunk for transfer: {} (offset: {}, size: {})",
@@ -62,31 +87,17 @@ pub async fn upload_chunk(
         chunk_data.len()
     );
 
-    // Create upload directory
-    let upload_dir = PathBuf::from("./data/uploads");
-    fs::create_dir_all(&upload_dir).map_err(|e| {
-        tracing::error!("Failed to create upload directory: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    // Create transfer directory
-    let transfer_dir = upload_dir.join(&transfer_id);
-    fs::create_dir_all(&transfer_dir).map_err(|e| {
-        tracing::error!("Failed to create transfer directory: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    // Write chunk to file with offset as name
-    let chunk_path = transfer_dir.join(format!("chunk_{:010}", offset));
-    let mut file = fs::File::create(&chunk_path).map_err(|e| {
-        tracing::error!("Failed to create chunk file: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    file.write_all(&chunk_data).map_err(|e| {
-        tracing::error!("Failed to write chunk: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let _guard = state.shutdown.begin(&transfer_id);
+    if transfer.status == "pending" || transfer.status == "interrupted" {
+        state
+            .db
+            .update_transfer_status(&transfer_id, "uploading", None)
+            .await?;
+    }
+
+    let upload_dir = &state.config.storage.upload_dir;
+    write_chunk(upload_dir, &transfer_id, offset as u64, &chunk_data)?;
+    state.metrics.record_chunk(Transport::Http, chunk_data.len());
 
     tracing::debug!("Chunk at offset {} saved successfully", offset);
 
@@ -100,77 +111,48 @@ pub async fn upload_chunk(
 /// Finalize transfer - assemble all chunks into final file
 pub async fn finalize_transfer(
     State(state): State<AppState>,
-    Json(payload): Json<FinalizeRequest>,
-) -> Result<impl IntoResponse, StatusCode> {
+    auth: DeviceAuth,
+    ApiJson(payload): ApiJson<FinalizeRequest>,
+) -> Result<impl IntoResponse, AppError> {
     let transfer_id = &payload.transfer_id;
     tracing::info!("Finalizing transfer: {}", transfer_id);
 
-    let upload_dir = PathBuf::from("./data/uploads");
-    let transfer_dir = upload_dir.join(transfer_id);
-
-    if !transfer_dir.exists() {
-        tracing::error!("Transfer directory not found: {:?}", transfer_dir);
-        return Err(StatusCode::NOT_FOUND);
-    }
-
-    // Get all chunk files sorted by offset
-    let mut chunk_files: Vec<_> = fs::read_dir(&transfer_dir)
-        .map_err(|e| {
-            tracing::error!("Failed to read transfer directory: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| {
-            entry
-                .file_name()
-                .to_string_lossy()
-                .starts_with("chunk_")
-        })
-        .collect();
-
-    chunk_files.sort_by_key(|entry| entry.file_name());
-
-    tracing::info!("Found {} chunks to assemble", chunk_files.len());
-
-    // Assemble chunks into final file
-    let final_path = transfer_dir.join("file");
-    let mut final_file = fs::File::create(&final_path).map_err(|e| {
-        tracing::error!("Failed to create final file: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    let mut total_bytes = 0;
-    for chunk_file in &chunk_files {
-        let chunk_data = fs::read(chunk_file.path()).map_err(|e| {
-            tracing::error!("Failed to read chunk: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-        
-        final_file.write_all(&chunk_data).map_err(|e| {
-            tracing::error!("Failed to write to final file: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-        total_bytes += chunk_data.len();
-
-        // Delete chunk after adding to final file
-        fs::remove_file(chunk_file.path()).ok();
+    let transfer = state
+        .db
+        .get_transfer(transfer_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Transfer"))?;
+    auth.authorize(&transfer.device_id)?;
+    if !accepts_chunks(&transfer.status) {
+        return Err(AppError::Conflict(format!(
+            "Transfer is {} and cannot be finalized",
+            transfer.status
+        )));
     }
+    let upload_dir = &state.config.storage.upload_dir;
+    if !transfer_dir(upload_dir, transfer_id).exists() {
+        return Err(AppError::Conflict("No chunks have been uploaded".to_string()));
+    }
+
+    let _guard = state.shutdown.begin(transfer_id);
+    let assembled = finish_transfer(&state, &transfer).await;
+    let outcome = match &assembled {
+        Ok((_, total_bytes)) => Ok(*total_bytes),
+        Err(e) => Err(e.to_string()),
+    };
+    audit::record(
+        &state.db,
+        audit::transfer_outcome(&transfer.device_id, transfer_id, Transport::Http, outcome),
+    )
+    .await;
+    let (final_path, total_bytes) = assembled?;
 
     tracing::info!(
         "File assembled successfully at: {:?} ({} bytes)",
         final_path,
         total_bytes
     );
-
-    // Update transfer status in database
-    if let Err(e) = state
-        .db
-        .update_transfer_status(transfer_id, "completed", Some(chrono::Utc::now()))
-        .await
-    {
-        tracing::error!("Failed to update transfer status: {}", e);
-    }
+    state.metrics.record_transfer(Transport::Http, transfer.created_at);
 
     Ok(Json(json!({
         "status": "completed",
@@ -180,23 +162,30 @@ pub async fn finalize_transfer(
     })))
 }
 
+fn multipart_error(error: MultipartError) -> AppError {
+    if error.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        AppError::PayloadTooLarge(error.body_text())
+    } else {
+        AppError::validation(error.body_text())
+    }
+}
+
 /// Get upload status
 pub async fn get_upload_status(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
+    auth: DeviceAuth,
     Path(transfer_id): Path<String>,
-) -> impl IntoResponse {
-    let upload_dir = PathBuf::from("./data/uploads");
-    let transfer_dir = upload_dir.join(&transfer_id);
-
-    if !transfer_dir.exists() {
-        return Json(json!({
-            "transfer_id": transfer_id,
-            "status": "not_found",
-        }));
-    }
+) -> Result<impl IntoResponse, AppError> {
+    let transfer = state
+        .db
+        .get_transfer(&transfer_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Transfer"))?;
+    auth.authorize(&transfer.device_id)?;
 
     // Count chunks
-    let chunk_count = fs::read_dir(&transfer_dir)
+    let upload_dir = &state.config.storage.upload_dir;
+    let chunk_count = fs::read_dir(transfer_dir(upload_dir, &transfer_id))
         .ok()
         .map(|entries| {
             entries
@@ -210,9 +199,314 @@ pub async fn get_upload_status(
         })
         .unwrap_or(0);
 
-    Json(json!({
+    Ok(Json(json!({
         "transfer_id": transfer_id,
-        "status": "uploading",
         "chunks_received": chunk_count,
-    }))
+        "resumable": transfer.status == "interrupted",
+        "status": transfer.status,
+    })))
+}
+
+/// Transfer statuses that still take chunks
+const WRITABLE_STATUSES: &[&str] = &["pending", "uploading", "interrupted"];
+
+/// Whether a transfer in `status` still takes chunks
+///
+/// Completed and failed transfers are never written to again.
+pub fn accepts_chunks(status: &str) -> bool {
+    WRITABLE_STATUSES.contains(&status)
+}
+
+/// Reject a chunk that would end past the transfer's declared file size
+///
+/// `offset` comes from the client, so the end is computed without
+/// overflowing.
+pub fn check_chunk_bounds(transfer: &Transfer, offset: u64, len: usize) -> Result<(), AppError> {
+    let end = u64::try_from(len)
+        .ok()
+        .and_then(|len| offset.checked_add(len));
+    match end {
+        Some(end) if end <= transfer.file_size.max(0) as u64 => Ok(()),
+        _ => Err(AppError::PayloadTooLarge(format!(
+            "Chunk ends past the declared file size of {} bytes",
+            transfer.file_size
+        ))),
+    }
+}
+
+/// Why the received chunks were not accepted as the transfer's file
+#[derive(Debug, thiserror::Error)]
+pub enum AssemblyError {
+    #[error("chunks leave a gap or overlap at offset {0}")]
+    Coverage(u64),
+    #[error("received {received} bytes, expected {expected}")]
+    Size { received: u64, expected: u64 },
+    #[error("file hash does not match")]
+    HashMismatch,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<AssemblyError> for AppError {
+    fn from(error: AssemblyError) -> Self {
+        match error {
+            AssemblyError::Io(e) => e.into(),
+            other => AppError::Validation(other.to_string()),
+        }
+    }
+}
+
+/// Parse a declared file hash, `sha256:` followed by 64 hex digits
+///
+/// The prefix is optional.
+pub fn parse_file_hash(file_hash: &str) -> Option<[u8; 32]> {
+    let hex_digest = file_hash.strip_prefix("sha256:").unwrap_or(file_hash);
+    let mut digest = [0u8; 32];
+    hex::decode_to_slice(hex_digest, &mut digest).ok()?;
+    Some(digest)
+}
+
+/// Assemble the file of a transfer and record the outcome
+///
+/// The transfer is marked `completed`, or `failed` when its chunks are
+/// refused by [`assemble_chunks`]. I/O errors leave it as it was, so the
+/// client can try again.
+pub async fn finish_transfer(
+    state: &AppState,
+    transfer: &Transfer,
+) -> Result<(PathBuf, u64), AssemblyError> {
+    let result = assemble_chunks(&state.config.storage.upload_dir, transfer);
+    let update = match &result {
+        Ok(_) => {
+            state
+                .db
+                .update_transfer_status(&transfer.id, "completed", Some(chrono::Utc::now()))
+                .await
+        }
+        Err(AssemblyError::Io(_)) => return result,
+        Err(e) => {
+            tracing::warn!("Refused the chunks of transfer {}: {}", transfer.id, e);
+            state
+                .db
+                .update_transfer_status(&transfer.id, "failed", None)
+                .await
+        }
+    };
+    if let Err(e) = update {
+        tracing::error!("Failed to update transfer status: {}", e);
+    }
+    result
+}
+
+/// Directory holding the chunks and assembled file of a transfer
+pub fn transfer_dir(upload_dir: &FsPath, transfer_id: &str) -> PathBuf {
+    upload_dir.join(transfer_id)
+}
+
+/// Write a chunk received at `offset` for a transfer
+///
+/// Chunks are stored as separate files named after their offset so they can
+/// arrive out of order and be assembled by [`assemble_chunks`]. Each chunk
+/// is written to a temporary file and renamed into place, so a killed server
+/// never leaves a truncated chunk behind.
+pub fn write_chunk(
+    upload_dir: &FsPath,
+    transfer_id: &str,
+    offset: u64,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let transfer_dir = transfer_dir(upload_dir, transfer_id);
+    fs::create_dir_all(&transfer_dir)?;
+
+    let chunk_name = format!("chunk_{:020}", offset);
+    let partial_path = transfer_dir.join(format!(".{}.part", chunk_name));
+    let mut file = fs::File::create(&partial_path)?;
+    file.write_all(data)?;
+    file.sync_data()?;
+    fs::rename(&partial_path, transfer_dir.join(chunk_name))?;
+    Ok(())
+}
+
+/// A chunk file and the range it covers
+struct Chunk {
+    path: PathBuf,
+    offset: u64,
+    len: u64,
+}
+
+/// Chunks received for a transfer, sorted by offset
+fn received_chunks(transfer_dir: &FsPath) -> std::io::Result<Vec<Chunk>> {
+    let mut chunks = Vec::new();
+    for entry in fs::read_dir(transfer_dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(offset) = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("chunk_"))
+            .and_then(|offset| offset.parse().ok())
+        else {
+            continue;
+        };
+        chunks.push(Chunk {
+            path: entry.path(),
+            offset,
+            len: entry.metadata()?.len(),
+        });
+    }
+    chunks.sort_by_key(|chunk| chunk.offset);
+    Ok(chunks)
+}
+
+/// Assemble all chunks of a transfer into its final file
+///
+/// The chunks must cover the declared file size exactly, without gaps or
+/// overlaps, and the assembled file must match the declared hash. Chunks
+/// are only deleted once the file is accepted.
+///
+/// # Returns
+/// Path of the assembled file and its size in bytes
+pub fn assemble_chunks(
+    upload_dir: &FsPath,
+    transfer: &Transfer,
+) -> Result<(PathBuf, u64), AssemblyError> {
+    let transfer_dir = transfer_dir(upload_dir, &transfer.id);
+    let chunks = received_chunks(&transfer_dir)?;
+
+    tracing::info!("Found {} chunks to assemble", chunks.len());
+
+    let mut total_bytes = 0;
+    for chunk in &chunks {
+        if chunk.offset != total_bytes {
+            return Err(AssemblyError::Coverage(chunk.offset.min(total_bytes)));
+        }
+        total_bytes += chunk.len;
+    }
+    let expected = transfer.file_size.max(0) as u64;
+    if total_bytes != expected {
+        return Err(AssemblyError::Size {
+            received: total_bytes,
+            expected,
+        });
+    }
+    let digest = parse_file_hash(&transfer.file_hash).ok_or(AssemblyError::HashMismatch)?;
+
+    let partial_path = transfer_dir.join(".file.part");
+    let mut final_file = fs::File::create(&partial_path)?;
+    let mut hasher = Sha256::new();
+    for chunk in &chunks {
+        let chunk_data = fs::read(&chunk.path)?;
+        hasher.update(&chunk_data);
+        final_file.write_all(&chunk_data)?;
+    }
+    final_file.sync_data()?;
+    if hasher.finalize().as_slice() != digest {
+        fs::remove_file(&partial_path).ok();
+        return Err(AssemblyError::HashMismatch);
+    }
+
+    let final_path = transfer_dir.join("file");
+    fs::rename(&partial_path, &final_path)?;
+    for chunk in &chunks {
+        fs::remove_file(&chunk.path).ok();
+    }
+
+    Ok((final_path, total_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::sha256_hash;
+
+    fn transfer(data: &[u8]) -> Transfer {
+        Transfer::new(
+            uuid::Uuid::new_v4().to_string(),
+            "device-1".to_string(),
+            "notes.txt".to_string(),
+            data.len() as i64,
+            format!("sha256:{}", sha256_hash(data)),
+        )
+    }
+
+    fn upload_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("bridgex-upload-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_chunks_must_tile_the_file() {
+        let dir = upload_dir();
+        let transfer = transfer(b"abcdef");
+
+        write_chunk(&dir, &transfer.id, 0, b"ab").unwrap();
+        write_chunk(&dir, &transfer.id, 4, b"ef").unwrap();
+        assert!(matches!(
+            assemble_chunks(&dir, &transfer),
+            Err(AssemblyError::Coverage(2))
+        ));
+
+        write_chunk(&dir, &transfer.id, 1, b"bc").unwrap();
+        assert!(matches!(
+            assemble_chunks(&dir, &transfer),
+            Err(AssemblyError::Coverage(1))
+        ));
+
+        fs::remove_file(transfer_dir(&dir, &transfer.id).join(format!("chunk_{:020}", 1))).unwrap();
+        write_chunk(&dir, &transfer.id, 2, b"cd").unwrap();
+        let (path, total_bytes) = assemble_chunks(&dir, &transfer).unwrap();
+        assert_eq!(total_bytes, 6);
+        assert_eq!(fs::read(path).unwrap(), b"abcdef");
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_size_and_hash_are_checked() {
+        let dir = upload_dir();
+        let transfer = transfer(b"abcd");
+
+        write_chunk(&dir, &transfer.id, 0, b"ab").unwrap();
+        assert!(matches!(
+            assemble_chunks(&dir, &transfer),
+            Err(AssemblyError::Size {
+                received: 2,
+                expected: 4
+            })
+        ));
+
+        write_chunk(&dir, &transfer.id, 2, b"xx").unwrap();
+        assert!(matches!(
+            assemble_chunks(&dir, &transfer),
+            Err(AssemblyError::HashMismatch)
+        ));
+        // Nothing is assembled or deleted for a refused file
+        let transfer_dir = transfer_dir(&dir, &transfer.id);
+        assert!(!transfer_dir.join("file").exists());
+        assert_eq!(received_chunks(&transfer_dir).unwrap().len(), 2);
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_chunks_are_ordered_by_offset() {
+        let dir = upload_dir();
+        let transfer = transfer(b"");
+        for offset in [10_000_000_000, 9_999_999_999, 0] {
+            write_chunk(&dir, &transfer.id, offset, b"x").unwrap();
+        }
+
+        let offsets: Vec<u64> = received_chunks(&transfer_dir(&dir, &transfer.id))
+            .unwrap()
+            .iter()
+            .map(|chunk| chunk.offset)
+            .collect();
+        assert_eq!(offsets, vec![0, 9_999_999_999, 10_000_000_000]);
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_parse_file_hash() {
+        let digest = sha256_hash(b"data");
+        assert!(parse_file_hash(&format!("sha256:{}", digest)).is_some());
+        assert!(parse_file_hash(&digest).is_some());
+        assert!(parse_file_hash("sha256:abc").is_none());
+        assert!(parse_file_hash("hash").is_none());
+    }
 }
@@ -0,0 +1,502 @@
+//! WebRTC data channel peer
+//!
+//! The server's side of a WebRTC connection, built from the ICE, DTLS and
+//! SCTP layers of webrtc-rs. Only data channels are negotiated, so the media
+//! stack (SRTP, RTP) is left out:
+//!
+//! ```text
+//! data channel (DCEP) > SCTP association > DTLS > ICE (UDP)
+//! ```
+//!
+//! Session descriptions carry a single `application` section with the ICE
+//! credentials, the SHA-256 fingerprint of the peer's self-signed DTLS
+//! certificate and its `setup` role. The offerer is the controlling ICE
+//! agent and answers `actpass`; the answerer takes the `active` role and is
+//! the DTLS client. The certificate presented in the handshake must match
+//! the fingerprint of the remote description.
+//!
+//! Each data channel is a reliable, ordered byte stream ([`ChannelStream`]),
+//! so it carries the same framed session as the direct TCP channel.
+
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::mpsc;
+use webrtc_data::data_channel::{self, DataChannel, PollDataChannel};
+use webrtc_dtls::config::{ClientAuthType, ExtendedMasterSecretType};
+use webrtc_dtls::conn::DTLSConn;
+use webrtc_dtls::crypto::Certificate;
+use webrtc_ice::agent::agent_config::AgentConfig;
+use webrtc_ice::agent::Agent;
+use webrtc_ice::candidate::candidate_base::unmarshal_candidate;
+use webrtc_ice::candidate::{Candidate, CandidateType};
+use webrtc_ice::mdns::MulticastDnsMode;
+use webrtc_ice::network_type::NetworkType;
+use webrtc_sctp::association::{self, Association};
+use webrtc_util::Conn;
+
+use super::error::AppError;
+use super::signaling::{IceCandidate, Role, SdpType, SessionDescription, SignalingError};
+
+/// Label of the data channels carrying transfer sessions
+pub const CHANNEL_LABEL: &str = "bridgex-transfer";
+
+/// Largest message written to a data channel, the size every browser accepts
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024;
+
+/// How long ICE and the DTLS handshake may take
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// SCTP port announced in session descriptions
+const SCTP_PORT: u16 = 5000;
+
+/// Largest message accepted from the remote peer
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Errors of the WebRTC peer
+#[derive(Debug, thiserror::Error)]
+pub enum PeerError {
+    #[error("invalid remote description: {0}")]
+    InvalidDescription(&'static str),
+    #[error("invalid ICE candidate: {0}")]
+    InvalidCandidate(String),
+    #[error("DTLS certificate does not match the remote fingerprint")]
+    FingerprintMismatch,
+    #[error("timed out connecting to the remote peer")]
+    TimedOut,
+    #[error(transparent)]
+    Signaling(#[from] SignalingError),
+    #[error("ICE error: {0}")]
+    Ice(#[from] webrtc_ice::Error),
+    #[error("DTLS error: {0}")]
+    Dtls(#[from] webrtc_dtls::Error),
+    #[error("SCTP error: {0}")]
+    Sctp(#[from] webrtc_sctp::Error),
+    #[error("data channel error: {0}")]
+    DataChannel(#[from] webrtc_data::Error),
+}
+
+impl From<PeerError> for AppError {
+    fn from(error: PeerError) -> Self {
+        match error {
+            PeerError::InvalidDescription(_) | PeerError::InvalidCandidate(_) => {
+                AppError::Validation(error.to_string())
+            }
+            PeerError::Signaling(error) => error.into(),
+            other => AppError::Internal(other.into()),
+        }
+    }
+}
+
+/// Fields of a session description the peer needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RemoteDescription {
+    ufrag: String,
+    pwd: String,
+    fingerprint: Vec<u8>,
+    setup: String,
+    candidates: Vec<String>,
+}
+
+/// One side of a WebRTC connection, before it is connected
+pub struct Peer {
+    role: Role,
+    agent: Arc<Agent>,
+    certificate: Certificate,
+}
+
+impl Peer {
+    /// Create a peer and start gathering its host candidates
+    ///
+    /// # Returns
+    /// The peer and the receiver of its local candidates, ending with the
+    /// empty candidate once gathering completes
+    pub async fn new(
+        role: Role,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<IceCandidate>), PeerError> {
+        let agent = Arc::new(
+            Agent::new(AgentConfig {
+                network_types: vec![NetworkType::Udp4, NetworkType::Udp6],
+                candidate_types: vec![CandidateType::Host],
+                multicast_dns_mode: MulticastDnsMode::Disabled,
+                is_controlling: role == Role::Offerer,
+                ..Default::default()
+            })
+            .await?,
+        );
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        agent.on_candidate(Box::new(move |candidate| {
+            let candidate = match candidate {
+                Some(candidate) => format!("candidate:{}", candidate.marshal()),
+                None => String::new(),
+            };
+            sender
+                .send(IceCandidate {
+                    candidate,
+                    sdp_mid: Some("0".to_string()),
+                    sdp_m_line_index: Some(0),
+                })
+                .ok();
+            Box::pin(async {})
+        }));
+        agent.gather_candidates()?;
+
+        let certificate = Certificate::generate_self_signed(vec!["bridgex".to_string()])?;
+        Ok((
+            Self {
+                role,
+                agent,
+                certificate,
+            },
+            receiver,
+        ))
+    }
+
+    /// Side of the session the peer is on
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Local session description, an offer or an answer depending on the role
+    pub async fn local_description(&self) -> SessionDescription {
+        let (ufrag, pwd) = self.agent.get_local_user_credentials().await;
+        let (sdp_type, setup) = match self.role {
+            Role::Offerer => (SdpType::Offer, "actpass"),
+            Role::Answerer => (SdpType::Answer, "active"),
+        };
+        let session_id = rand::thread_rng().next_u64() >> 1;
+        let sdp = [
+            "v=0".to_string(),
+            format!("o=- {} 2 IN IP4 127.0.0.1", session_id),
+            "s=-".to_string(),
+            "t=0 0".to_string(),
+            "a=group:BUNDLE 0".to_string(),
+            "m=application 9 UDP/DTLS/SCTP webrtc-datachannel".to_string(),
+            "c=IN IP4 0.0.0.0".to_string(),
+            format!("a=ice-ufrag:{}", ufrag),
+            format!("a=ice-pwd:{}", pwd),
+            format!("a=fingerprint:sha-256 {}", self.fingerprint()),
+            format!("a=setup:{}", setup),
+            "a=mid:0".to_string(),
+            format!("a=sctp-port:{}", SCTP_PORT),
+            format!("a=max-message-size:{}", MAX_MESSAGE_SIZE),
+            String::new(),
+        ]
+        .join("\r\n");
+        SessionDescription { sdp_type, sdp }
+    }
+
+    /// Colon-separated SHA-256 fingerprint of the DTLS certificate
+    fn fingerprint(&self) -> String {
+        let digest = Sha256::digest(&self.certificate.certificate[0].0);
+        digest
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    /// Add a candidate trickled by the remote peer
+    ///
+    /// The empty end-of-candidates marker is ignored.
+    pub fn add_remote_candidate(&self, candidate: &IceCandidate) -> Result<(), PeerError> {
+        let value = candidate.candidate.trim();
+        if value.is_empty() {
+            return Ok(());
+        }
+        let value = value.strip_prefix("candidate:").unwrap_or(value);
+        let parsed: Arc<dyn Candidate + Send + Sync> = Arc::new(
+            unmarshal_candidate(value).map_err(|e| PeerError::InvalidCandidate(e.to_string()))?,
+        );
+        self.agent.add_remote_candidate(&parsed)?;
+        Ok(())
+    }
+
+    /// Connect to the remote peer described by `remote`
+    ///
+    /// Candidates may keep arriving through
+    /// [`add_remote_candidate`](Self::add_remote_candidate) while this runs.
+    /// Fails after [`CONNECT_TIMEOUT`].
+    pub async fn connect(&self, remote: &SessionDescription) -> Result<Connection, PeerError> {
+        let (description, is_client) = remote_description(self.role, remote)?;
+        for candidate in &description.candidates {
+            self.add_remote_candidate(&IceCandidate {
+                candidate: candidate.clone(),
+                sdp_mid: None,
+                sdp_m_line_index: None,
+            })?;
+        }
+
+        tokio::time::timeout(CONNECT_TIMEOUT, self.handshake(&description, is_client))
+            .await
+            .map_err(|_| PeerError::TimedOut)?
+    }
+
+    /// Bring up ICE, DTLS and SCTP on top of each other
+    async fn handshake(
+        &self,
+        remote: &RemoteDescription,
+        is_client: bool,
+    ) -> Result<Connection, PeerError> {
+        // Dropping the sender would cancel the connectivity checks
+        let (_cancel, cancel_rx) = mpsc::channel(1);
+        let ice: Arc<dyn Conn + Send + Sync> = match self.role {
+            Role::Offerer => {
+                self.agent
+                    .dial(cancel_rx, remote.ufrag.clone(), remote.pwd.clone())
+                    .await?
+            }
+            Role::Answerer => {
+                self.agent
+                    .accept(cancel_rx, remote.ufrag.clone(), remote.pwd.clone())
+                    .await?
+            }
+        };
+
+        let config = webrtc_dtls::config::Config {
+            certificates: vec![self.certificate.clone()],
+            // Self-signed on both sides: pinned to the fingerprint below
+            insecure_skip_verify: true,
+            client_auth: ClientAuthType::RequireAnyClientCert,
+            extended_master_secret: ExtendedMasterSecretType::Require,
+            ..Default::default()
+        };
+        let dtls = Arc::new(DTLSConn::new(ice, config, is_client, None).await?);
+        let state = dtls.connection_state().await;
+        let presented = state.peer_certificates.first().map(Sha256::digest);
+        if presented.as_deref() != Some(remote.fingerprint.as_slice()) {
+            dtls.close().await.ok();
+            return Err(PeerError::FingerprintMismatch);
+        }
+
+        let config = association::Config {
+            net_conn: dtls.clone(),
+            max_receive_buffer_size: 0,
+            max_message_size: 0,
+            name: "bridgex".to_string(),
+        };
+        // The DTLS client sends the INIT, the server waits for it
+        let association = if is_client {
+            Association::client(config).await?
+        } else {
+            Association::server(config).await?
+        };
+
+        Ok(Connection {
+            agent: self.agent.clone(),
+            dtls,
+            association: Arc::new(association),
+            // RFC 8832: the DTLS client uses even stream IDs, the server odd ones
+            next_stream: AtomicU16::new(if is_client { 0 } else { 1 }),
+        })
+    }
+
+    /// Stop gathering and release the peer's sockets
+    pub async fn close(&self) {
+        self.agent.close().await.ok();
+    }
+}
+
+/// Check that `remote` is a description a peer on `role` can connect to
+pub fn check_description(role: Role, remote: &SessionDescription) -> Result<(), PeerError> {
+    remote_description(role, remote).map(|_| ())
+}
+
+/// Parse the remote description of a peer on `role`
+///
+/// # Returns
+/// The description and whether the local peer is the DTLS client
+fn remote_description(
+    role: Role,
+    remote: &SessionDescription,
+) -> Result<(RemoteDescription, bool), PeerError> {
+    let expected = match role {
+        Role::Offerer => SdpType::Answer,
+        Role::Answerer => SdpType::Offer,
+    };
+    if remote.sdp_type != expected {
+        return Err(PeerError::InvalidDescription("unexpected description type"));
+    }
+    let description = parse_description(&remote.sdp)?;
+    let is_client = match (role, description.setup.as_str()) {
+        (Role::Answerer, "actpass" | "passive") => true,
+        (Role::Offerer, "active") => false,
+        (Role::Offerer, "passive") => true,
+        _ => return Err(PeerError::InvalidDescription("unsupported setup role")),
+    };
+    Ok((description, is_client))
+}
+
+/// Extract the ICE credentials, DTLS fingerprint, setup role and inline
+/// candidates of a session description
+fn parse_description(sdp: &str) -> Result<RemoteDescription, PeerError> {
+    let (mut ufrag, mut pwd, mut fingerprint, mut setup) = (None, None, None, None);
+    let mut candidates = Vec::new();
+    for line in sdp.lines().map(str::trim_end) {
+        let Some(attribute) = line.strip_prefix("a=") else {
+            continue;
+        };
+        let (name, value) = attribute.split_once(':').unwrap_or((attribute, ""));
+        match name {
+            "ice-ufrag" => ufrag = Some(value.to_string()),
+            "ice-pwd" => pwd = Some(value.to_string()),
+            "setup" => setup = Some(value.to_string()),
+            "candidate" => candidates.push(format!("candidate:{}", value)),
+            "fingerprint" => {
+                if let Some(("sha-256", hex)) = value.split_once(' ') {
+                    fingerprint = Some(parse_fingerprint(hex)?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(RemoteDescription {
+        ufrag: ufrag.ok_or(PeerError::InvalidDescription("missing ice-ufrag"))?,
+        pwd: pwd.ok_or(PeerError::InvalidDescription("missing ice-pwd"))?,
+        fingerprint: fingerprint
+            .ok_or(PeerError::InvalidDescription("missing sha-256 fingerprint"))?,
+        setup: setup.ok_or(PeerError::InvalidDescription("missing setup"))?,
+        candidates,
+    })
+}
+
+fn parse_fingerprint(value: &str) -> Result<Vec<u8>, PeerError> {
+    let bytes = hex::decode(value.trim().replace(':', ""))
+        .map_err(|_| PeerError::InvalidDescription("malformed fingerprint"))?;
+    if bytes.len() != 32 {
+        return Err(PeerError::InvalidDescription("malformed fingerprint"));
+    }
+    Ok(bytes)
+}
+
+/// Established WebRTC connection
+pub struct Connection {
+    agent: Arc<Agent>,
+    dtls: Arc<DTLSConn>,
+    association: Arc<Association>,
+    next_stream: AtomicU16,
+}
+
+impl Connection {
+    /// Address of the remote peer on the selected candidate pair
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.dtls.remote_addr()
+    }
+
+    /// Open a data channel to the remote peer
+    pub async fn open_channel(&self) -> Result<ChannelStream, PeerError> {
+        let stream_id = self.next_stream.fetch_add(2, Ordering::Relaxed);
+        let config = data_channel::Config {
+            label: CHANNEL_LABEL.to_string(),
+            ..Default::default()
+        };
+        let channel = DataChannel::dial(&self.association, stream_id, config).await?;
+        Ok(ChannelStream::new(channel))
+    }
+
+    /// Wait for the remote peer to open a data channel
+    pub async fn accept_channel(&self) -> Result<ChannelStream, PeerError> {
+        let channel =
+            DataChannel::accept(&self.association, Default::default(), &[] as &[DataChannel])
+                .await?;
+        Ok(ChannelStream::new(channel))
+    }
+
+    /// Close the association and release the connection's sockets
+    pub async fn close(&self) {
+        self.association.close().await.ok();
+        self.dtls.close().await.ok();
+        self.agent.close().await.ok();
+    }
+}
+
+/// Data channel as a byte stream
+///
+/// Writes are split into messages of at most [`MAX_MESSAGE_SIZE`].
+pub struct ChannelStream {
+    inner: PollDataChannel,
+}
+
+impl ChannelStream {
+    fn new(channel: DataChannel) -> Self {
+        let mut inner = PollDataChannel::new(Arc::new(channel));
+        inner.set_read_buf_capacity(READ_BUFFER_SIZE);
+        Self { inner }
+    }
+
+    /// Label the opener gave the channel
+    pub fn label(&self) -> String {
+        self.inner.clone_inner().config.label.clone()
+    }
+}
+
+impl AsyncRead for ChannelStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ChannelStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let len = buf.len().min(MAX_MESSAGE_SIZE);
+        Pin::new(&mut self.inner).poll_write(cx, &buf[..len])
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_description_round_trips() {
+        let (peer, _) = Peer::new(Role::Offerer).await.unwrap();
+        let offer = peer.local_description().await;
+        assert_eq!(offer.sdp_type, SdpType::Offer);
+        assert!(crate::server::signaling::validate_sdp(&offer.sdp).is_ok());
+
+        let description = parse_description(&offer.sdp).unwrap();
+        let (ufrag, pwd) = peer.agent.get_local_user_credentials().await;
+        assert_eq!((description.ufrag, description.pwd), (ufrag, pwd));
+        assert_eq!(
+            description.fingerprint,
+            Sha256::digest(&peer.certificate.certificate[0].0).to_vec()
+        );
+        assert_eq!(description.setup, "actpass");
+        peer.close().await;
+    }
+
+    #[test]
+    fn test_descriptions_without_credentials_are_refused() {
+        let sdp = "v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\nm=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\na=setup:actpass\r\n";
+        assert!(matches!(
+            parse_description(sdp),
+            Err(PeerError::InvalidDescription("missing ice-ufrag"))
+        ));
+        assert!(parse_fingerprint("AB:CD").is_err());
+    }
+}
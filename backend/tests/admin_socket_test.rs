@@ -0,0 +1,114 @@
+//! Admin API on a Unix domain socket
+#![cfg(unix)]
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use hyper_util::rt::TokioIo;
+use uuid::Uuid;
+
+use bridgex_backend::db::models::{Device, DeviceType};
+use bridgex_backend::server::{self, admin_socket};
+use bridgex_backend::{AppState, Database, ServerCertificate};
+
+async fn test_state() -> AppState {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+    AppState::new(db, ServerCertificate::generate().unwrap())
+}
+
+fn socket_path() -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("bridgex-socket-{}", Uuid::new_v4()))
+        .join("admin.sock")
+}
+
+/// Send one request over the socket
+async fn send(path: &Path, request: Request<Body>) -> StatusCode {
+    let stream = tokio::net::UnixStream::connect(path).await.unwrap();
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .unwrap();
+    tokio::spawn(connection);
+    sender.send_request(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn test_admin_api_is_served_on_the_socket() {
+    let state = test_state().await;
+    let device = Device::new(
+        Uuid::new_v4().to_string(),
+        "Phone".to_string(),
+        DeviceType::Mobile,
+        vec![1; 32],
+    );
+    state.db.save_device(&device).await.unwrap();
+
+    let path = socket_path();
+    let listener = admin_socket::bind(&path).unwrap();
+    let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(&path), 0o600);
+    assert_eq!(mode(path.parent().unwrap()), 0o700);
+
+    let server = tokio::spawn(admin_socket::serve(
+        listener,
+        path.clone(),
+        server::router(state.clone()),
+        state.shutdown.clone(),
+    ));
+
+    let request = Request::get("/api/v1/health")
+        .header("host", "localhost")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(&path, request).await, StatusCode::OK);
+
+    let request = Request::delete(format!("/api/v1/devices/{}", device.id))
+        .header("host", "localhost")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(&path, request).await, StatusCode::NO_CONTENT);
+    assert!(state.db.get_devices().await.unwrap().is_empty());
+
+    // A second server must not take over the socket
+    assert!(admin_socket::bind(&path).is_err());
+
+    state.shutdown.trigger();
+    server.await.unwrap().unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn test_stale_socket_is_replaced() {
+    let path = socket_path();
+    drop(admin_socket::bind(&path).unwrap());
+    assert!(path.exists());
+
+    // Nobody listens on the leftover socket any more
+    admin_socket::bind(&path).unwrap();
+}
+
+#[test]
+fn test_other_files_are_not_replaced() {
+    let path = socket_path();
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "data").unwrap();
+
+    assert!(admin_socket::bind(&path).is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+}
+
+#[test]
+fn test_shared_directories_are_refused() {
+    let path = socket_path();
+    let dir = path.parent().unwrap();
+    std::fs::create_dir_all(dir).unwrap();
+
+    for mode in [0o755, 0o1777, 0o770] {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(mode)).unwrap();
+        assert!(admin_socket::bind(&path).is_err());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,403 @@
+//! Audit log: recorded events, hash chain, query and export
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use serde_json::Value;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use bridgex_backend::cli::{admin, AuditCommand};
+use bridgex_backend::db::models::{Device, DeviceType, Transfer};
+use bridgex_backend::server::{self, audit::Actor, audit::AuditKind, tcp};
+use bridgex_backend::{AppState, Config, Database, ServerCertificate};
+use bridgex_backend::util::sha256_hash;
+
+/// Pairing token of the test device
+const TOKEN: &[u8] = b"test pairing token";
+
+fn test_state(db: Database) -> AppState {
+    let mut config = Config::default();
+    config.storage.upload_dir =
+        std::env::temp_dir().join(format!("bridgex-audit-{}", Uuid::new_v4()));
+    AppState::with_config(db, ServerCertificate::generate().unwrap(), config)
+}
+
+async fn memory_db() -> Database {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+    db
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, bytes.to_vec())
+}
+
+async fn get_json(app: &Router, uri: &str) -> Value {
+    let (status, body) = send(app, Request::get(uri).body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn post_json(uri: &str, body: Value) -> Request<Body> {
+    Request::post(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn kinds(page: &Value) -> Vec<String> {
+    page["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["kind"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_device_lifecycle_is_audited() {
+    let state = test_state(memory_db().await);
+    let app = server::router(state.clone());
+
+    let (status, body) = send(
+        &app,
+        post_json(
+            "/api/v1/pair",
+            serde_json::json!({ "device_name": "Phone", "device_type": "mobile" }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let device_id = serde_json::from_slice::<Value>(&body).unwrap()["device_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let (status, body) = send(
+        &app,
+        post_json(
+            "/api/v1/transfer/init",
+            serde_json::json!({
+                "device_id": device_id,
+                "file_name": "notes.txt",
+                "file_size": 4,
+                "file_hash": format!("sha256:{}", sha256_hash(b"data")),
+            }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let transfer_id = serde_json::from_slice::<Value>(&body).unwrap()["transfer_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let request = Request::patch(format!("/api/v1/devices/{}", device_id))
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"nickname":"Work phone"}"#))
+        .unwrap();
+    assert_eq!(send(&app, request).await.0, StatusCode::OK);
+
+    let request = Request::delete(format!("/api/v1/devices/{}", device_id))
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(&app, request).await.0, StatusCode::NO_CONTENT);
+
+    // Events outlive the device they describe
+    let page = get_json(
+        &app,
+        &format!("/api/v1/audit?device_id={}&sort=asc", device_id),
+    )
+    .await;
+    assert_eq!(
+        kinds(&page),
+        vec![
+            "device.paired",
+            "transfer.started",
+            "device.updated",
+            "device.revoked"
+        ]
+    );
+    let events = page["events"].as_array().unwrap();
+    assert_eq!(events[0]["actor"], "api");
+    assert_eq!(events[0]["details"]["name"], "Phone");
+    assert_eq!(events[1]["transfer_id"], transfer_id.as_str());
+    assert_eq!(events[1]["actor"], format!("device:{}", device_id));
+    assert_eq!(
+        events[2]["details"],
+        serde_json::json!({ "nickname": "Work phone" })
+    );
+    assert_eq!(events[1]["prev_hash"], events[0]["hash"]);
+
+    let verification = get_json(&app, "/api/v1/audit/verify").await;
+    assert_eq!(verification["valid"], true);
+    assert_eq!(verification["events"], 4);
+    assert_eq!(verification["head"], events[3]["hash"]);
+}
+
+#[tokio::test]
+async fn test_direct_sessions_are_audited() {
+    let state = test_state(memory_db().await);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve(listener, state.clone()));
+
+    let device = Device::new(
+        Uuid::new_v4().to_string(),
+        "Phone".to_string(),
+        DeviceType::Mobile,
+        vec![9; 32],
+    )
+    .with_pairing_token(TOKEN, chrono::Utc::now() + chrono::Duration::minutes(5));
+    state.db.save_device(&device).await.unwrap();
+    let transfer = Transfer::new(
+        Uuid::new_v4().to_string(),
+        device.id.clone(),
+        "photo.jpg".to_string(),
+        4,
+        format!("sha256:{}", sha256_hash(b"data")),
+    );
+    state.db.save_transfer(&transfer).await.unwrap();
+
+    assert!(
+        tcp::send_transfer(addr, &device.id, b"wrong token", &transfer.id, b"data", 4)
+            .await
+            .is_err()
+    );
+    tcp::send_transfer(
+        addr,
+        &device.id,
+        TOKEN,
+        &transfer.id,
+        b"data",
+        4,
+    )
+    .await
+    .unwrap();
+
+    let app = server::router(state.clone());
+    let page = get_json(
+        &app,
+        &format!("/api/v1/audit?transfer_id={}&sort=asc", transfer.id),
+    )
+    .await;
+    assert_eq!(
+        kinds(&page),
+        vec![
+            "device.verification_failed",
+            "device.verified",
+            "transfer.completed"
+        ]
+    );
+    let events = page["events"].as_array().unwrap();
+    assert_eq!(events[1]["details"]["transport"], "tcp");
+    assert_eq!(events[2]["details"]["total_bytes"], 4);
+
+    let page = get_json(&app, "/api/v1/audit?kind=device.verification_failed").await;
+    assert_eq!(page["events"].as_array().unwrap().len(), 1);
+
+    std::fs::remove_dir_all(&state.config.storage.upload_dir).ok();
+}
+
+#[tokio::test]
+async fn test_audit_pagination_and_export() {
+    let db = memory_db().await;
+    for i in 0..5 {
+        db.append_audit_event(
+            &AuditKind::DeviceRevoked
+                .event(&Actor::Cli)
+                .device(format!("device-{}", i)),
+        )
+        .await
+        .unwrap();
+    }
+    let app = server::router(test_state(db));
+
+    let first = get_json(&app, "/api/v1/audit?limit=3").await;
+    let seqs: Vec<i64> = first["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["seq"].as_i64().unwrap())
+        .collect();
+    assert_eq!(seqs, vec![5, 4, 3]);
+
+    let cursor = first["next_cursor"].as_str().unwrap();
+    let second = get_json(&app, &format!("/api/v1/audit?limit=3&cursor={}", cursor)).await;
+    assert_eq!(second["events"].as_array().unwrap().len(), 2);
+    assert!(second["next_cursor"].is_null());
+
+    let (status, body) = send(
+        &app,
+        Request::get("/api/v1/audit?kind=device.exploded")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(!body.is_empty());
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/api/v1/audit/export")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let lines: Vec<Value> = String::from_utf8(body.to_vec())
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[0]["seq"], 1);
+    assert_eq!(lines[0]["device_id"], "device-0");
+}
+
+#[tokio::test]
+async fn test_tampering_breaks_the_chain() {
+    let path = std::env::temp_dir().join(format!("bridgex-audit-{}.db", Uuid::new_v4()));
+    let url = format!("sqlite:{}?mode=rwc", path.display());
+    let db = Database::new(&url).await.unwrap();
+    db.init_schema().await.unwrap();
+    for kind in [AuditKind::DevicePaired, AuditKind::DeviceRevoked] {
+        db.append_audit_event(&kind.event(&Actor::Api).device("d1"))
+            .await
+            .unwrap();
+    }
+    assert!(db.verify_audit_chain().await.unwrap().is_valid());
+
+    let pool = sqlx::SqlitePool::connect(&url).await.unwrap();
+    let update = "UPDATE audit_events SET actor = 'cli' WHERE seq = 1";
+    let error = sqlx::query(update).execute(&pool).await.unwrap_err();
+    assert!(error.to_string().contains("append-only"), "{}", error);
+    assert!(sqlx::query("DELETE FROM audit_events")
+        .execute(&pool)
+        .await
+        .is_err());
+
+    // Someone with write access to the file can drop the triggers, but
+    // not rewrite history without breaking the hashes
+    sqlx::query("DROP TRIGGER audit_events_no_update")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query(update).execute(&pool).await.unwrap();
+    pool.close().await;
+
+    let status = db.verify_audit_chain().await.unwrap();
+    assert_eq!(status.events, 2);
+    assert_eq!(status.first_invalid, Some(1));
+
+    let mut out = Vec::new();
+    let error = admin::audit_log(&db, AuditCommand::Verify, &mut out)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("broken at event 1"), "{}", error);
+    std::fs::remove_file(&path).ok();
+}
+
+#[tokio::test]
+async fn test_cli_audit_commands() {
+    let db = memory_db().await;
+    let device = Device::new(
+        "device-1".to_string(),
+        "Phone".to_string(),
+        DeviceType::Mobile,
+        vec![1; 32],
+    );
+    db.save_device(&device).await.unwrap();
+    let config = Config::default();
+
+    let rm = bridgex_backend::cli::DevicesCommand::Rm {
+        id: device.id.clone(),
+    };
+    admin::devices(&db, &config, rm, &mut Vec::new())
+        .await
+        .unwrap();
+
+    let mut out = Vec::new();
+    let list = AuditCommand::List {
+        kind: None,
+        device: Some(device.id.clone()),
+        limit: 10,
+        json: false,
+    };
+    admin::audit_log(&db, list, &mut out).await.unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.contains("device.revoked"), "{}", text);
+    assert!(text.contains("cli"), "{}", text);
+
+    let path = std::env::temp_dir().join(format!("bridgex-audit-{}.ndjson", Uuid::new_v4()));
+    let export = || AuditCommand::Export {
+        output: Some(path.clone()),
+        kind: None,
+        device: None,
+    };
+    let mut out = Vec::new();
+    admin::audit_log(&db, export(), &mut out).await.unwrap();
+    assert!(String::from_utf8(out)
+        .unwrap()
+        .starts_with("Exported 1 events"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+    // An existing export is never overwritten
+    assert!(admin::audit_log(&db, export(), &mut Vec::new())
+        .await
+        .is_err());
+
+    let mut out = Vec::new();
+    admin::audit_log(&db, AuditCommand::Verify, &mut out)
+        .await
+        .unwrap();
+    assert!(String::from_utf8(out)
+        .unwrap()
+        .starts_with("Audit log intact: 1 events"));
+    std::fs::remove_file(&path).ok();
+}
+
+#[tokio::test]
+async fn test_concurrent_writers_share_the_chain() {
+    let path = std::env::temp_dir().join(format!("bridgex-audit-{}.db", Uuid::new_v4()));
+    let url = format!("sqlite:{}?mode=rwc", path.display());
+    // Separate handles stand in for the server and the CLI
+    let server_db = std::sync::Arc::new(Database::new(&url).await.unwrap());
+    server_db.init_schema().await.unwrap();
+    let cli_db = std::sync::Arc::new(Database::new(&url).await.unwrap());
+
+    let append = |db: std::sync::Arc<Database>, actor: Actor| async move {
+        for i in 0..20 {
+            db.append_audit_event(
+                &AuditKind::DeviceRevoked
+                    .event(&actor)
+                    .device(format!("device-{}", i)),
+            )
+            .await
+            .unwrap();
+        }
+    };
+    let (server, cli) = tokio::join!(
+        tokio::spawn(append(server_db.clone(), Actor::Api)),
+        tokio::spawn(append(cli_db, Actor::Cli)),
+    );
+    server.unwrap();
+    cli.unwrap();
+
+    let status = server_db.verify_audit_chain().await.unwrap();
+    assert!(status.is_valid());
+    assert_eq!(status.events, 40);
+    std::fs::remove_file(&path).ok();
+}
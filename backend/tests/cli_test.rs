@@ -0,0 +1,195 @@
+//! Admin subcommand tests
+
+use chrono::{Duration, Utc};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use bridgex_backend::cli::{admin, DbCommand, DevicesCommand, PairArgs, TransfersCommand};
+use bridgex_backend::db::models::{Device, DeviceType, Transfer};
+use bridgex_backend::server::upload;
+use bridgex_backend::{parse_pairing_uri, AppState, Config, Database, ServerCertificate};
+
+async fn database() -> Database {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+    db
+}
+
+async fn save_device(db: &Database, name: &str) -> Device {
+    let device = Device::new(
+        Uuid::new_v4().to_string(),
+        name.to_string(),
+        DeviceType::Mobile,
+        vec![1; 32],
+    );
+    db.save_device(&device).await.unwrap();
+    device
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bridgex-cli-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn test_devices_commands() {
+    let db = database().await;
+    let config = Config::default();
+    let device = save_device(&db, "Phone").await;
+    let mut out = Vec::new();
+
+    admin::devices(&db, &config, DevicesCommand::List { json: false }, &mut out)
+        .await
+        .unwrap();
+    let table = String::from_utf8(out.clone()).unwrap();
+    assert!(table.starts_with("ID"));
+    assert!(table.contains(&device.id) && table.contains("Phone"));
+
+    let rename = DevicesCommand::Rename {
+        id: device.id.clone(),
+        name: " Work Phone ".to_string(),
+    };
+    admin::devices(&db, &config, rename, &mut out)
+        .await
+        .unwrap();
+    let stored = db.get_device(&device.id).await.unwrap().unwrap();
+    assert_eq!(stored.name, "Work Phone");
+
+    let rename = DevicesCommand::Rename {
+        id: device.id.clone(),
+        name: "".to_string(),
+    };
+    assert!(admin::devices(&db, &config, rename, &mut out)
+        .await
+        .is_err());
+
+    let rm = || DevicesCommand::Rm {
+        id: device.id.clone(),
+    };
+    admin::devices(&db, &config, rm(), &mut out).await.unwrap();
+    assert!(db.get_device(&device.id).await.unwrap().is_none());
+    assert!(admin::devices(&db, &config, rm(), &mut out).await.is_err());
+}
+
+#[tokio::test]
+async fn test_transfers_purge_removes_old_finished_transfers() {
+    let db = database().await;
+    let mut config = Config::default();
+    config.storage.upload_dir = temp_dir();
+    let device = save_device(&db, "Phone").await;
+
+    let save = |status: &str, age_days: i64| {
+        let mut transfer = Transfer::new(
+            Uuid::new_v4().to_string(),
+            device.id.clone(),
+            "a.txt".to_string(),
+            1,
+            "hash".to_string(),
+        );
+        transfer.status = status.to_string();
+        transfer.created_at = Utc::now() - Duration::days(age_days);
+        let dir = upload::transfer_dir(&config.storage.upload_dir, &transfer.id);
+        std::fs::create_dir_all(&dir).unwrap();
+        (transfer, dir)
+    };
+    let old_completed = save("completed", 40);
+    let old_pending = save("pending", 40);
+    let recent_failed = save("failed", 1);
+    for (transfer, _) in [&old_completed, &old_pending, &recent_failed] {
+        db.save_transfer(transfer).await.unwrap();
+    }
+
+    let mut out = Vec::new();
+    let purge = TransfersCommand::Purge {
+        older_than_days: 30,
+        statuses: vec!["completed".to_string(), "failed".to_string()],
+    };
+    admin::transfers(&db, &config, purge, &mut out)
+        .await
+        .unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "Purged 1 transfers\n");
+
+    assert!(db
+        .get_transfer(&old_completed.0.id)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(!old_completed.1.exists());
+    for (transfer, dir) in [&old_pending, &recent_failed] {
+        assert!(db.get_transfer(&transfer.id).await.unwrap().is_some());
+        assert!(dir.exists());
+    }
+
+    let purge = TransfersCommand::Purge {
+        older_than_days: 30,
+        statuses: vec!["lost".to_string()],
+    };
+    assert!(admin::transfers(&db, &config, purge, &mut Vec::new())
+        .await
+        .is_err());
+
+    std::fs::remove_dir_all(&config.storage.upload_dir).ok();
+}
+
+#[tokio::test]
+async fn test_db_backup() {
+    let dir = temp_dir();
+    let db = Database::new(&format!(
+        "sqlite:{}?mode=rwc",
+        dir.join("bridge.db").display()
+    ))
+    .await
+    .unwrap();
+    db.init_schema().await.unwrap();
+    let device = save_device(&db, "Phone").await;
+    let path = dir.join("backup.db");
+
+    let backup = || DbCommand::Backup { path: path.clone() };
+    admin::database(&db, backup(), &mut Vec::new())
+        .await
+        .unwrap();
+
+    let copy = Database::new(&format!("sqlite:{}", path.display()))
+        .await
+        .unwrap();
+    assert!(copy.get_device(&device.id).await.unwrap().is_some());
+
+    // Never overwrite an existing file
+    assert!(admin::database(&db, backup(), &mut Vec::new())
+        .await
+        .is_err());
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn test_pair_prints_scannable_uri() {
+    let state = AppState::new(database().await, ServerCertificate::generate().unwrap());
+    let args = PairArgs {
+        name: "Tablet".to_string(),
+        device_type: DeviceType::Tablet,
+        ..PairArgs::startup(&Config::default())
+    };
+    let mut out = Vec::new();
+    admin::pair(&state, args, &mut out).await.unwrap();
+
+    let output = String::from_utf8(out).unwrap();
+    assert!(output.starts_with('█'), "QR code is printed first");
+    let uri = output
+        .lines()
+        .find_map(|line| line.strip_prefix("URI:"))
+        .unwrap()
+        .trim();
+    let payload = parse_pairing_uri(uri).unwrap();
+    assert_eq!(payload.server_fingerprint, state.certificate.fingerprint());
+
+    let device = state
+        .db
+        .get_device(&payload.device_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(device.name, "Tablet");
+    assert_eq!(device.device_type, DeviceType::Tablet);
+}
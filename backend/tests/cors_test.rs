@@ -0,0 +1,211 @@
+//! Cross-origin policy: trusted origins, untrusted origins, Private Network Access
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    response::Response,
+    Router,
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+use bridgex_backend::config::CorsConfig;
+use bridgex_backend::server;
+use bridgex_backend::{AppState, Config, Database, ServerCertificate};
+
+const WEB_APP: &str = "http://localhost:3000";
+const EVIL: &str = "https://evil.example";
+
+async fn test_app(cors: CorsConfig) -> (Router, AppState) {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+    let config = Config {
+        cors,
+        ..Default::default()
+    };
+    let state = AppState::with_config(db, ServerCertificate::generate().unwrap(), config);
+    (server::router(state.clone()), state)
+}
+
+fn web_app_allowed() -> CorsConfig {
+    CorsConfig {
+        allowed_origins: vec![WEB_APP.to_string()],
+        ..Default::default()
+    }
+}
+
+async fn send(app: &Router, request: Request<Body>) -> Response {
+    app.clone().oneshot(request).await.unwrap()
+}
+
+fn preflight(origin: &str, uri: &str) -> Request<Body> {
+    Request::options(uri)
+        .header("origin", origin)
+        .header("access-control-request-method", "POST")
+        .header("access-control-request-headers", "content-type")
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn pair_request(origin: Option<&str>) -> Request<Body> {
+    let mut builder = Request::post("/api/v1/pair").header("content-type", "application/json");
+    if let Some(origin) = origin {
+        builder = builder.header("origin", origin);
+    }
+    builder
+        .body(Body::from(
+            r#"{"device_name":"Phone","device_type":"mobile"}"#,
+        ))
+        .unwrap()
+}
+
+fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response
+        .headers()
+        .get(name)
+        .map(|value| value.to_str().unwrap())
+}
+
+#[tokio::test]
+async fn test_trusted_origins_are_allowed() {
+    let (app, _) = test_app(web_app_allowed()).await;
+
+    for origin in ["tauri://localhost", "http://tauri.localhost", WEB_APP] {
+        let response = send(&app, preflight(origin, "/api/v1/pair")).await;
+        assert_eq!(response.status(), StatusCode::OK, "{}", origin);
+        assert_eq!(
+            header(&response, "access-control-allow-origin"),
+            Some(origin)
+        );
+        let methods = header(&response, "access-control-allow-methods").unwrap();
+        assert!(methods.contains("POST"), "{}", methods);
+
+        let response = send(&app, pair_request(Some(origin))).await;
+        assert_eq!(response.status(), StatusCode::OK, "{}", origin);
+        assert_eq!(
+            header(&response, "access-control-allow-origin"),
+            Some(origin)
+        );
+    }
+
+    // Native clients send no Origin at all
+    let response = send(&app, pair_request(None)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "access-control-allow-origin"), None);
+}
+
+#[tokio::test]
+async fn test_untrusted_origins_are_refused() {
+    let (app, state) = test_app(web_app_allowed()).await;
+
+    for origin in [
+        EVIL,
+        "null",
+        "http://localhost:3001",
+        "https://localhost:3000",
+    ] {
+        let response = send(&app, preflight(origin, "/api/v1/pair")).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", origin);
+        assert_eq!(header(&response, "access-control-allow-origin"), None);
+
+        // A form POST skips the preflight, so it must not run either
+        let response = send(&app, pair_request(Some(origin))).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", origin);
+        assert_eq!(header(&response, "access-control-allow-origin"), None);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "forbidden");
+    }
+    assert!(state.db.get_devices().await.unwrap().is_empty());
+
+    let request = Request::get("/api/v1/transfers")
+        .header("origin", EVIL)
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(&app, request).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_no_web_origins_by_default() {
+    let (app, _) = test_app(CorsConfig::default()).await;
+
+    let response = send(&app, pair_request(Some(WEB_APP))).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = send(&app, pair_request(Some("tauri://localhost"))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_private_network_access_preflight() {
+    let pna = |origin: &str| {
+        let mut request = preflight(origin, "/api/v1/status");
+        request.headers_mut().insert(
+            "access-control-request-private-network",
+            "true".parse().unwrap(),
+        );
+        request
+    };
+
+    let (app, _) = test_app(web_app_allowed()).await;
+    let response = send(&app, pna(WEB_APP)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        header(&response, "access-control-allow-private-network"),
+        Some("true")
+    );
+
+    let response = send(&app, pna(EVIL)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        header(&response, "access-control-allow-private-network"),
+        None
+    );
+
+    let (app, _) = test_app(CorsConfig {
+        allow_private_network: false,
+        ..web_app_allowed()
+    })
+    .await;
+    let response = send(&app, pna(WEB_APP)).await;
+    assert_eq!(
+        header(&response, "access-control-allow-private-network"),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_rebound_hosts_are_refused() {
+    let (app, state) = test_app(CorsConfig::default()).await;
+    let with_host = |host: &str| {
+        let mut request = pair_request(None);
+        request
+            .headers_mut()
+            .insert("host", host.parse().unwrap());
+        request
+    };
+
+    // A page on a domain rebound to 127.0.0.1 calls same-origin, no `Origin`
+    let response = send(&app, with_host("evil.example:8083")).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "forbidden");
+    assert!(state.db.get_devices().await.unwrap().is_empty());
+
+    for host in ["localhost:8083", "127.0.0.1:8083", "[::1]:8083"] {
+        let response = send(&app, with_host(host)).await;
+        assert_eq!(response.status(), StatusCode::OK, "{}", host);
+    }
+
+    // The device API is reached by LAN address and keeps any host
+    let devices = server::device_router(state);
+    let request = Request::get("/api/v1/health")
+        .header("host", "bridgex.local:8080")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(&devices, request).await.status(), StatusCode::OK);
+}
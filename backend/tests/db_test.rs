@@ -1,6 +1,9 @@
 //! Database module tests
 
-use bridgex_backend::db::{models::Device, Database};
+use bridgex_backend::db::{
+    models::{Device, DeviceType},
+    Database,
+};
 use uuid::Uuid;
 
 #[tokio::test]
@@ -17,7 +20,7 @@ async fn test_save_and_get_device() {
     let device = Device::new(
         Uuid::new_v4().to_string(),
         "Test Device".to_string(),
-        "mobile".to_string(),
+        DeviceType::Mobile,
         vec![1, 2, 3, 4],
     );
 
@@ -39,14 +42,14 @@ async fn test_list_devices() {
     let device1 = Device::new(
         Uuid::new_v4().to_string(),
         "Device 1".to_string(),
-        "mobile".to_string(),
+        DeviceType::Mobile,
         vec![1, 2, 3],
     );
 
     let device2 = Device::new(
         Uuid::new_v4().to_string(),
         "Device 2".to_string(),
-        "desktop".to_string(),
+        DeviceType::Desktop,
         vec![4, 5, 6],
     );
 
@@ -65,7 +68,7 @@ async fn test_delete_device() {
     let device = Device::new(
         Uuid::new_v4().to_string(),
         "Test Device".to_string(),
-        "mobile".to_string(),
+        DeviceType::Mobile,
         vec![1, 2, 3],
     );
 
@@ -84,7 +87,7 @@ async fn test_update_device_last_seen() {
     let mut device = Device::new(
         Uuid::new_v4().to_string(),
         "Test Device".to_string(),
-        "mobile".to_string(),
+        DeviceType::Mobile,
         vec![1, 2, 3],
     );
 
@@ -98,3 +101,28 @@ async fn test_update_device_last_seen() {
     let retrieved = db.get_device(&device.id).await.unwrap().unwrap();
     assert!(retrieved.last_seen.is_some());
 }
+
+#[tokio::test]
+async fn test_device_type_and_details_round_trip() {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+
+    let mut device = Device::new(
+        Uuid::new_v4().to_string(),
+        "Tablet".to_string(),
+        DeviceType::Tablet,
+        vec![1, 2, 3],
+    );
+    device.platform = Some("android".to_string());
+    device.os_version = Some("14".to_string());
+    device.app_version = Some("0.2.0".to_string());
+    db.save_device(&device).await.unwrap();
+
+    let retrieved = db.get_device(&device.id).await.unwrap().unwrap();
+    assert_eq!(retrieved.device_type, DeviceType::Tablet);
+    assert_eq!(retrieved.platform.as_deref(), Some("android"));
+    assert_eq!(retrieved.os_version.as_deref(), Some("14"));
+    assert_eq!(retrieved.app_version.as_deref(), Some("0.2.0"));
+    assert_eq!("tablet".parse::<DeviceType>(), Ok(DeviceType::Tablet));
+    assert!("watch".parse::<DeviceType>().is_err());
+}
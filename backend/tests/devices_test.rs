@@ -0,0 +1,140 @@
+//! Device editing tests
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use bridgex_backend::db::models::{Device, DeviceType};
+use bridgex_backend::{server, AppState, Database, ServerCertificate};
+
+async fn test_app() -> (Router, Device, Device) {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+
+    let mut devices = Vec::new();
+    for name in ["Pixel", "iPhone"] {
+        let device = Device::new(
+            Uuid::new_v4().to_string(),
+            name.to_string(),
+            DeviceType::Mobile,
+            vec![1; 32],
+        );
+        db.save_device(&device).await.unwrap();
+        devices.push(device);
+    }
+
+    let state = AppState::new(db, ServerCertificate::generate().unwrap());
+    let iphone = devices.pop().unwrap();
+    let pixel = devices.pop().unwrap();
+    (server::router(state), pixel, iphone)
+}
+
+async fn call(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+#[tokio::test]
+async fn test_edit_device_labels() {
+    let (app, pixel, _) = test_app().await;
+    let uri = format!("/api/v1/devices/{}", pixel.id);
+
+    let (status, device) = call(
+        &app,
+        "PATCH",
+        &uri,
+        Some(json!({
+            "name": "  Pixel 8  ",
+            "nickname": "QA phone",
+            "icon": "phone",
+            "color": "#1E88E5",
+            "tags": ["qa", "android"],
+            "favorite": true,
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(device["name"], "Pixel 8");
+    assert_eq!(device["nickname"], "QA phone");
+    assert_eq!(device["color"], "#1e88e5");
+    assert_eq!(device["tags"], json!(["qa", "android"]));
+    assert_eq!(device["favorite"], true);
+
+    // Omitted fields are kept, null clears
+    let (status, device) = call(&app, "PATCH", &uri, Some(json!({ "nickname": null }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(device["nickname"].is_null());
+    assert_eq!(device["icon"], "phone");
+    assert_eq!(device["tags"], json!(["qa", "android"]));
+}
+
+#[tokio::test]
+async fn test_favorites_are_listed_first() {
+    let (app, pixel, iphone) = test_app().await;
+
+    let (_, devices) = call(&app, "GET", "/api/v1/devices", None).await;
+    assert_eq!(devices[0]["id"], iphone.id.as_str());
+    assert!(devices[0].get("public_key").is_none());
+
+    call(
+        &app,
+        "PATCH",
+        &format!("/api/v1/devices/{}", pixel.id),
+        Some(json!({ "favorite": true })),
+    )
+    .await;
+    let (_, devices) = call(&app, "GET", "/api/v1/devices", None).await;
+    assert_eq!(devices[0]["id"], pixel.id.as_str());
+}
+
+#[tokio::test]
+async fn test_edit_validation() {
+    let (app, pixel, _) = test_app().await;
+    let uri = format!("/api/v1/devices/{}", pixel.id);
+
+    for body in [
+        json!({ "name": "   " }),
+        json!({ "nickname": "x".repeat(33) }),
+        json!({ "icon": "Phone!" }),
+        json!({ "color": "blue" }),
+        json!({ "tags": ["work", "Work"] }),
+        json!({ "tags": [""] }),
+    ] {
+        let (status, error) = call(&app, "PATCH", &uri, Some(body.clone())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        assert_eq!(error["code"], "validation_failed");
+        assert!(error["error"].is_string());
+    }
+
+    let (status, error) = call(&app, "PATCH", &uri, Some(json!({ "owner": "me" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["code"], "validation_failed");
+
+    let (status, error) = call(
+        &app,
+        "PATCH",
+        "/api/v1/devices/missing",
+        Some(json!({ "favorite": true })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(error["code"], "not_found");
+}
@@ -0,0 +1,243 @@
+//! Error status and code mapping across handlers
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use bridgex_backend::db::models::{Device, DeviceType, Transfer};
+use bridgex_backend::server::{self, upload};
+use bridgex_backend::util::sha256_hash;
+use bridgex_backend::{AppState, Config, Database, ServerCertificate};
+
+const BOUNDARY: &str = "bridgex-test-boundary";
+
+async fn test_app() -> (Router, Transfer) {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+
+    let device = Device::new(
+        Uuid::new_v4().to_string(),
+        "Phone".to_string(),
+        DeviceType::Mobile,
+        vec![1; 32],
+    );
+    db.save_device(&device).await.unwrap();
+    let transfer = Transfer::new(
+        Uuid::new_v4().to_string(),
+        device.id.clone(),
+        "notes.txt".to_string(),
+        4,
+        format!("sha256:{}", sha256_hash(b"data")),
+    );
+    db.save_transfer(&transfer).await.unwrap();
+
+    let state = AppState::new(db, ServerCertificate::generate().unwrap());
+    (server::router(state), transfer)
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+fn json_request(method: &str, uri: &str, body: &str) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn chunk_request(transfer_id: &str, offset: &str, chunk: &[u8]) -> Request<Body> {
+    let mut body = Vec::new();
+    for (name, value) in [
+        ("transfer_id", transfer_id.as_bytes()),
+        ("offset", offset.as_bytes()),
+        ("chunk", chunk),
+    ] {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                BOUNDARY, name
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(value);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+
+    Request::post("/api/v1/transfer/upload")
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        )
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn assert_error(response: (StatusCode, Value), status: StatusCode, code: &str) {
+    assert_eq!(response.0, status, "{}", response.1);
+    assert_eq!(response.1["code"], code);
+    assert!(response.1["error"].is_string());
+}
+
+#[tokio::test]
+async fn test_not_found_errors() {
+    let (app, _) = test_app().await;
+
+    let missing = [
+        json_request("DELETE", "/api/v1/devices/missing", ""),
+        json_request("GET", "/api/v1/transfers/missing", ""),
+        json_request("GET", "/api/v1/transfer/missing/status", ""),
+        json_request("GET", "/api/v1/signaling/missing", ""),
+        json_request(
+            "POST",
+            "/api/v1/transfer/init",
+            &json!({
+                "device_id": "missing",
+                "file_name": "a.txt",
+                "file_size": 1,
+                "file_hash": format!("sha256:{}", "0".repeat(64)),
+            })
+            .to_string(),
+        ),
+    ];
+    for request in missing {
+        assert_error(
+            send(&app, request).await,
+            StatusCode::NOT_FOUND,
+            "not_found",
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_validation_errors() {
+    let (app, transfer) = test_app().await;
+
+    let invalid = [
+        json_request("POST", "/api/v1/pair", r#"{"device_name":"  "}"#),
+        json_request("POST", "/api/v1/pair", "{not json"),
+        json_request("GET", "/api/v1/transfers?limit=many", ""),
+        chunk_request(&transfer.id, "-1", b"data"),
+    ];
+    for request in invalid {
+        assert_error(
+            send(&app, request).await,
+            StatusCode::BAD_REQUEST,
+            "validation_failed",
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_upload_errors() {
+    let (app, transfer) = test_app().await;
+
+    assert_error(
+        send(&app, chunk_request("../../etc", "0", b"data")).await,
+        StatusCode::NOT_FOUND,
+        "not_found",
+    );
+    assert_error(
+        send(&app, chunk_request(&transfer.id, "2", b"data")).await,
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "payload_too_large",
+    );
+    assert_error(
+        send(
+            &app,
+            chunk_request(&transfer.id, &usize::MAX.to_string(), b"data"),
+        )
+        .await,
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "payload_too_large",
+    );
+    assert_error(
+        send(
+            &app,
+            json_request(
+                "POST",
+                "/api/v1/transfer/finalize",
+                &json!({ "transfer_id": transfer.id }).to_string(),
+            ),
+        )
+        .await,
+        StatusCode::CONFLICT,
+        "conflict",
+    );
+}
+
+#[tokio::test]
+async fn test_finalize_requires_a_transfer_in_progress() {
+    let (app, transfer) = test_app().await;
+    let finalize = || {
+        json_request(
+            "POST",
+            "/api/v1/transfer/finalize",
+            &json!({ "transfer_id": transfer.id }).to_string(),
+        )
+    };
+
+    assert_eq!(
+        send(&app, chunk_request(&transfer.id, "0", b"data")).await.0,
+        StatusCode::OK
+    );
+    assert_eq!(send(&app, finalize()).await.0, StatusCode::OK);
+    assert_error(
+        send(&app, finalize()).await,
+        StatusCode::CONFLICT,
+        "conflict",
+    );
+
+    let upload_dir = Config::default().storage.upload_dir;
+    std::fs::remove_dir_all(upload::transfer_dir(&upload_dir, &transfer.id)).ok();
+}
+
+#[tokio::test]
+async fn test_rejections_are_json() {
+    let (app, _) = test_app().await;
+
+    let upload = Request::post("/api/v1/transfer/upload")
+        .header("content-type", "text/plain")
+        .body(Body::from("data"))
+        .unwrap();
+    assert_error(
+        send(&app, upload).await,
+        StatusCode::BAD_REQUEST,
+        "validation_failed",
+    );
+    assert_error(
+        send(&app, json_request("GET", "/api/v1/nowhere", "")).await,
+        StatusCode::NOT_FOUND,
+        "not_found",
+    );
+
+    let response = app
+        .clone()
+        .oneshot(json_request("GET", "/api/v1/transfer/finalize", ""))
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["allow"], "POST");
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&bytes).unwrap()["code"],
+        "method_not_allowed"
+    );
+}
@@ -0,0 +1,127 @@
+//! Admin and device listeners: route sets and bound addresses
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use bridgex_backend::db::models::{Device, DeviceType};
+use bridgex_backend::config::AdminPort;
+use bridgex_backend::{server, AppState, Config, Database, ServerCertificate};
+
+async fn test_state() -> AppState {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+    AppState::new(db, ServerCertificate::generate().unwrap())
+}
+
+async fn save_device(state: &AppState) -> Device {
+    let device = Device::new(
+        Uuid::new_v4().to_string(),
+        "Phone".to_string(),
+        DeviceType::Mobile,
+        vec![1; 32],
+    );
+    state.db.save_device(&device).await.unwrap();
+    device
+}
+
+async fn status(app: &Router, method: &str, uri: &str) -> StatusCode {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(
+            r#"{"device_name":"Phone","device_type":"mobile"}"#,
+        ))
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn test_device_api_cannot_manage_devices() {
+    let state = test_state().await;
+    let device = save_device(&state).await;
+    let app = server::device_router(state.clone());
+    let device_uri = format!("/api/v1/devices/{}", device.id);
+
+    for (method, uri) in [
+        ("DELETE", device_uri.as_str()),
+        ("PATCH", device_uri.as_str()),
+        ("GET", "/api/v1/devices"),
+        ("POST", "/api/v1/pair"),
+        ("GET", "/api/v1/transfers"),
+        ("GET", "/api/v1/status"),
+        ("GET", "/api/v1/audit"),
+        ("GET", "/api/v1/discovery/peers"),
+        ("GET", "/metrics"),
+    ] {
+        assert_eq!(
+            status(&app, method, uri).await,
+            StatusCode::NOT_FOUND,
+            "{} {}",
+            method,
+            uri
+        );
+    }
+    assert_eq!(state.db.get_devices().await.unwrap().len(), 1);
+
+    assert_eq!(status(&app, "GET", "/api/v1/health").await, StatusCode::OK);
+    // Device routes are there, but only for signed requests
+    let heartbeat = format!("/api/v1/devices/{}/heartbeat", device.id);
+    assert_eq!(
+        status(&app, "POST", &heartbeat).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+async fn test_admin_api_serves_every_route() {
+    let state = test_state().await;
+    let device = save_device(&state).await;
+    let app = server::router(state.clone());
+
+    assert_eq!(status(&app, "GET", "/api/v1/health").await, StatusCode::OK);
+    let heartbeat = format!("/api/v1/devices/{}/heartbeat", device.id);
+    assert_eq!(status(&app, "POST", &heartbeat).await, StatusCode::OK);
+    assert_eq!(status(&app, "GET", "/api/v1/devices").await, StatusCode::OK);
+
+    let device_uri = format!("/api/v1/devices/{}", device.id);
+    assert_eq!(
+        status(&app, "DELETE", &device_uri).await,
+        StatusCode::NO_CONTENT
+    );
+    assert!(state.db.get_devices().await.unwrap().is_empty());
+}
+
+#[test]
+fn test_admin_listener_is_loopback() {
+    let mut config = Config::default();
+    config.server.host = "0.0.0.0".parse().unwrap();
+    let admin_addr = config.admin_addr().unwrap();
+    assert!(admin_addr.ip().is_loopback());
+    assert_eq!(Some(admin_addr.port()), config.server.admin_port.port());
+
+    config.server.host = "::".parse().unwrap();
+    assert_eq!(config.admin_addr().unwrap().to_string(), "[::1]:8083");
+}
+
+#[test]
+fn test_admin_port_can_be_turned_off() {
+    let mut config = Config::default();
+    config
+        .apply_env(|name| (name == "BRIDGEX_ADMIN_PORT").then(|| "off".to_string()))
+        .unwrap();
+    assert_eq!(config.server.admin_port, AdminPort::Off);
+    assert_eq!(config.admin_addr(), None);
+    config.validate().unwrap();
+
+    let config: Config = toml::from_str("[server]\nadmin_port = \"off\"\n").unwrap();
+    assert_eq!(config.server.admin_port, AdminPort::Off);
+    let config: Config = toml::from_str("[server]\nadmin_port = 9083\n").unwrap();
+    assert_eq!(config.server.admin_port, AdminPort::Port(9083));
+    assert!(toml::from_str::<Config>("[server]\nadmin_port = \"on\"\n").is_err());
+}
@@ -0,0 +1,144 @@
+//! Request ids, request-scoped log events and redaction
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use std::io;
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+
+use bridgex_backend::logging::{self, Redacting, REQUEST_ID_HEADER};
+use bridgex_backend::{server, AppState, Database, ServerCertificate};
+
+/// Log output captured in memory
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Captured {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl io::Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Captured {
+    type Writer = Captured;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+async fn test_app() -> Router {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+    let state = AppState::new(db, ServerCertificate::generate().unwrap());
+    logging::trace_requests(server::router(state))
+}
+
+/// Capture debug logs of the backend, as written by the server
+fn capture() -> (Captured, tracing::subscriber::DefaultGuard) {
+    let captured = Captured::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::new("bridgex_backend=debug"))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(Redacting(captured.clone())),
+        );
+    let guard = tracing::subscriber::set_default(subscriber);
+    (captured, guard)
+}
+
+#[tokio::test]
+async fn test_request_id_is_generated_and_echoed() {
+    let app = test_app().await;
+
+    let response = app
+        .clone()
+        .oneshot(Request::get("/api/v1/health").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let generated = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+    assert_eq!(generated.len(), 36, "expected a UUID, got {}", generated);
+
+    let response = app
+        .oneshot(
+            Request::get("/api/v1/health")
+                .header(REQUEST_ID_HEADER, "client-chosen-id")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.headers()[REQUEST_ID_HEADER], "client-chosen-id");
+}
+
+#[tokio::test]
+async fn test_database_events_carry_the_request_id() {
+    let app = test_app().await;
+    let (captured, _guard) = capture();
+
+    let response = app
+        .oneshot(
+            Request::get("/api/v1/devices")
+                .header(REQUEST_ID_HEADER, "req-42")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let logs = captured.text();
+    let query = logs
+        .lines()
+        .find(|line| line.contains("Database query took"))
+        .unwrap_or_else(|| panic!("no database event in\n{}", logs));
+    assert!(query.contains("request_id=req-42"), "{}", query);
+    assert!(query.contains("operation=\"get_devices\""), "{}", query);
+}
+
+#[tokio::test]
+async fn test_pairing_secrets_are_redacted() {
+    let app = test_app().await;
+    let (captured, _guard) = capture();
+
+    let response = app
+        .oneshot(
+            Request::post("/api/v1/pair")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    r#"{"device_name":"Phone","device_type":"mobile"}"#,
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let uri = url::Url::parse(body["pairing_uri"].as_str().unwrap()).unwrap();
+    let (_, token) = uri.query_pairs().find(|(name, _)| name == "token").unwrap();
+
+    let logs = captured.text();
+    assert!(logs.contains("Pairing QR code for device"), "{}", logs);
+    assert!(!logs.contains("pairing_uri"), "{}", logs);
+    assert!(!logs.contains(token.as_ref()), "{}", logs);
+}
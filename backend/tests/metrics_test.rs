@@ -0,0 +1,140 @@
+//! Prometheus endpoint and server status counts
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use serde_json::Value;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use bridgex_backend::db::models::{Device, DeviceType, Transfer};
+use bridgex_backend::server::{self, upload};
+use bridgex_backend::{AppState, Config, Database, ServerCertificate};
+use bridgex_backend::util::sha256_hash;
+
+async fn test_state() -> (AppState, Transfer) {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+
+    let device = Device::new(
+        Uuid::new_v4().to_string(),
+        "Phone".to_string(),
+        DeviceType::Mobile,
+        vec![1; 32],
+    );
+    db.save_device(&device).await.unwrap();
+    let transfer = Transfer::new(
+        Uuid::new_v4().to_string(),
+        device.id.clone(),
+        "notes.txt".to_string(),
+        5,
+        format!("sha256:{}", sha256_hash(b"hello")),
+    );
+    db.save_transfer(&transfer).await.unwrap();
+
+    let mut config = Config::default();
+    config.storage.upload_dir =
+        std::env::temp_dir().join(format!("bridgex-metrics-{}", Uuid::new_v4()));
+    let state = AppState::with_config(db, ServerCertificate::generate().unwrap(), config);
+    (state, transfer)
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, Option<String>, String) {
+    let response = app
+        .clone()
+        .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|value| value.to_str().unwrap().to_string());
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        content_type,
+        String::from_utf8(bytes.to_vec()).unwrap(),
+    )
+}
+
+async fn post_json(app: &Router, uri: &str, body: String) -> StatusCode {
+    let request = Request::post(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body))
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint() {
+    let (state, transfer) = test_state().await;
+    let app = server::router(state.clone());
+
+    // One chunk written as if by an upload, then finalized over HTTP
+    upload::write_chunk(&state.config.storage.upload_dir, &transfer.id, 0, b"hello").unwrap();
+    state
+        .metrics
+        .record_chunk(server::metrics::Transport::Http, 5);
+    let finalize = format!(r#"{{"transfer_id":"{}"}}"#, transfer.id);
+    assert_eq!(
+        post_json(&app, "/api/v1/transfer/finalize", finalize).await,
+        StatusCode::OK
+    );
+
+    // A valid and a rejected pairing request
+    let pair = r#"{"device_name":"Tablet","device_type":"tablet"}"#.to_string();
+    assert_eq!(post_json(&app, "/api/v1/pair", pair).await, StatusCode::OK);
+    let invalid = r#"{"device_name":" ","device_type":"tablet"}"#.to_string();
+    assert_eq!(
+        post_json(&app, "/api/v1/pair", invalid).await,
+        StatusCode::BAD_REQUEST
+    );
+
+    let (status, content_type, text) = get(&app, "/metrics").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type
+        .unwrap()
+        .starts_with("text/plain; version=0.0.4"));
+    for line in [
+        "bridgex_bytes_received_total{transport=\"http\"} 5",
+        "bridgex_transfer_duration_seconds_count{transport=\"http\"} 1",
+        "bridgex_pairing_attempts_total 2",
+        "bridgex_pairing_failures_total 1",
+        "bridgex_paired_devices 2",
+        "bridgex_transfers{status=\"completed\"} 1",
+        "bridgex_active_connections 0",
+    ] {
+        assert!(text.contains(line), "missing {:?} in\n{}", line, text);
+    }
+    assert!(text.contains("bridgex_db_query_duration_seconds_bucket{operation=\"get_transfer\""));
+
+    std::fs::remove_dir_all(&state.config.storage.upload_dir).ok();
+}
+
+#[tokio::test]
+async fn test_status_reports_real_counts() {
+    let (state, _) = test_state().await;
+    let app = server::router(state.clone());
+    state
+        .metrics
+        .record_chunk(server::metrics::Transport::Tcp, 64);
+
+    let (status, _, body) = get(&app, "/api/v1/status").await;
+    assert_eq!(status, StatusCode::OK);
+    let body: Value = serde_json::from_str(&body).unwrap();
+
+    assert!(body["uptime_secs"].is_u64());
+    assert!(body["started_at"].is_string());
+    assert_eq!(body["paired_devices"], 1);
+    assert_eq!(body["pending_transfers"], 1);
+    assert_eq!(body["transfers"]["pending"], 1);
+    assert_eq!(body["transfers"]["completed"], 0);
+    assert_eq!(body["bytes_received"], 64);
+    assert_eq!(body["chunks_received"], 1);
+    assert_eq!(body["active_connections"], 0);
+}
@@ -0,0 +1,104 @@
+//! Schema migration tests
+
+use bridgex_backend::db::migrations::{self, MigrationError};
+use bridgex_backend::Database;
+use sqlx::sqlite::SqlitePool;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Schema shipped by v0.1, before the schema_version table existed
+const V0_1_SCHEMA: &str = include_str!("fixtures/v0_1_schema.sql");
+
+struct TempDb(PathBuf);
+
+impl TempDb {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("bridgex-migrate-{}.db", Uuid::new_v4())))
+    }
+
+    fn url(&self) -> String {
+        format!("sqlite:{}?mode=rwc", self.0.display())
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+#[tokio::test]
+async fn test_fresh_database_is_fully_migrated() {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+
+    let applied = db.migrate().await.unwrap();
+    assert_eq!(applied.last().copied(), Some(migrations::latest_version()));
+    assert_eq!(
+        db.schema_version().await.unwrap(),
+        migrations::latest_version()
+    );
+
+    // Running again is a no-op
+    assert!(db.migrate().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_migrates_v0_1_database() {
+    let temp = TempDb::new();
+    let device_id = Uuid::new_v4().to_string();
+
+    {
+        let pool = SqlitePool::connect(&temp.url()).await.unwrap();
+        sqlx::query(V0_1_SCHEMA).execute(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO devices (id, name, type, public_key, paired_at) VALUES (?, 'Old Phone', 'mobile', x'0102', '2024-01-01T00:00:00Z')",
+        )
+        .bind(&device_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool.close().await;
+    }
+
+    let db = Database::new(&temp.url()).await.unwrap();
+    assert_eq!(db.schema_version().await.unwrap(), 0);
+    db.init_schema().await.unwrap();
+    assert_eq!(
+        db.schema_version().await.unwrap(),
+        migrations::latest_version()
+    );
+
+    let device = db.get_device(&device_id).await.unwrap().unwrap();
+    assert_eq!(device.name, "Old Phone");
+    assert_eq!(device.public_key, vec![1, 2]);
+}
+
+#[tokio::test]
+async fn test_refuses_newer_schema() {
+    let temp = TempDb::new();
+    let newer = migrations::latest_version() + 1;
+
+    {
+        let db = Database::new(&temp.url()).await.unwrap();
+        db.init_schema().await.unwrap();
+    }
+    {
+        let pool = SqlitePool::connect(&temp.url()).await.unwrap();
+        sqlx::query("INSERT INTO schema_version (version, description, applied_at) VALUES (?, 'future', '2030-01-01T00:00:00Z')")
+            .bind(newer)
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+    }
+
+    let db = Database::new(&temp.url()).await.unwrap();
+    let error = db.init_schema().await.unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<MigrationError>(),
+        Some(&MigrationError::NewerSchema {
+            found: newer,
+            supported: migrations::latest_version(),
+        })
+    );
+}
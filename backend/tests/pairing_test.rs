@@ -0,0 +1,59 @@
+//! Pairing URI round-trip property tests
+
+use chrono::{TimeZone, Utc};
+use proptest::prelude::*;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use bridgex_backend::pairing::{MAX_ADDRESSES, MIN_TOKEN_LEN, PUBLIC_KEY_LEN};
+use bridgex_backend::{parse_pairing_uri, PairingPayload};
+
+fn socket_addr() -> impl Strategy<Value = SocketAddr> {
+    let ip = prop_oneof![
+        any::<[u8; 4]>().prop_map(|o| IpAddr::V4(Ipv4Addr::from(o))),
+        any::<[u16; 8]>().prop_map(|s| IpAddr::V6(Ipv6Addr::from(s))),
+    ];
+    (ip, any::<u16>()).prop_map(|(ip, port)| SocketAddr::new(ip, port))
+}
+
+fn payload() -> impl Strategy<Value = PairingPayload> {
+    (
+        "[A-Za-z0-9_-]{1,64}",
+        any::<[u8; PUBLIC_KEY_LEN]>(),
+        prop::collection::vec(any::<u8>(), MIN_TOKEN_LEN..64),
+        prop::collection::vec(socket_addr(), 0..=MAX_ADDRESSES),
+        1i64..4_102_444_800,
+        "[0-9a-f]{64}",
+    )
+        .prop_map(|(id, key, token, addresses, exp, fp)| {
+            PairingPayload::new(
+                id,
+                key,
+                token,
+                addresses,
+                Utc.timestamp_opt(exp, 0).unwrap(),
+                fp,
+            )
+        })
+}
+
+proptest! {
+    #[test]
+    fn generated_uris_parse_back(payload in payload()) {
+        let uri = payload.to_uri();
+        prop_assert_eq!(parse_pairing_uri(&uri), Ok(payload));
+    }
+
+    #[test]
+    fn generated_uris_are_url_safe(payload in payload()) {
+        let uri = payload.to_uri();
+        let query = uri.split_once('?').unwrap().1;
+        prop_assert!(query
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.*%&=".contains(c)));
+    }
+
+    #[test]
+    fn parser_never_panics(input in "bridgex://pair\\?[ -~]{0,200}") {
+        let _ = parse_pairing_uri(&input);
+    }
+}
@@ -0,0 +1,100 @@
+//! Binding port 0 and reporting the chosen ports
+#![cfg(unix)]
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use uuid::Uuid;
+
+use bridgex_backend::server::ports::BoundPorts;
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bridgex-ports-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // Also holds the admin socket, which needs an owner-only directory
+    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).unwrap();
+    dir
+}
+
+async fn health(port: u16) -> StatusCode {
+    let stream = tokio::net::TcpStream::connect(("127.0.0.1", port))
+        .await
+        .unwrap();
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .unwrap();
+    tokio::spawn(connection);
+    let request = Request::get("/api/v1/health")
+        .header("host", "localhost")
+        .body(Body::empty())
+        .unwrap();
+    sender.send_request(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn test_server_reports_ports_it_picked() {
+    let dir = temp_dir();
+    let port_file = dir.join("ports.json");
+    let mut server = Command::new(env!("CARGO_BIN_EXE_bridgex-server"))
+        .current_dir(&dir)
+        .env("BRIDGEX_HOST", "127.0.0.1")
+        .env("BRIDGEX_PORT", "0")
+        .env("BRIDGEX_ADMIN_PORT", "0")
+        .env("BRIDGEX_TCP_PORT", "0")
+        .env("BRIDGEX_QUIC_PORT", "0")
+        .env("BRIDGEX_ADMIN_SOCKET", dir.join("admin.sock"))
+        .env("BRIDGEX_PORT_FILE", &port_file)
+        .env("BRIDGEX_DB_PATH", dir.join("bridge.db"))
+        .env("BRIDGEX_UPLOAD_DIR", dir.join("uploads"))
+        .env("BRIDGEX_AUTO_DISCOVERY", "0")
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap();
+
+    let mut lines = BufReader::new(server.stdout.take().unwrap()).lines();
+    let ports = tokio::time::timeout(Duration::from_secs(30), async {
+        while let Some(line) = lines.next_line().await.unwrap() {
+            if let Some(ports) = BoundPorts::parse_handshake(&line) {
+                return ports;
+            }
+        }
+        panic!("server exited without a handshake line");
+    })
+    .await
+    .unwrap();
+    // Keep draining so logging never blocks the server
+    tokio::spawn(async move { while let Ok(Some(_)) = lines.next_line().await {} });
+
+    assert_eq!(ports.pid, server.id().unwrap());
+    assert_ne!(ports.port, 0);
+    assert_ne!(ports.admin_port, Some(0));
+    assert!(ports.admin_port.is_some());
+    assert_ne!(ports.tcp_port, 0);
+    assert_ne!(ports.quic_port, 0);
+    assert_eq!(ports.admin_socket, Some(dir.join("admin.sock")));
+    assert_eq!(BoundPorts::read_file(&port_file).unwrap(), ports);
+
+    assert_eq!(health(ports.port).await, StatusCode::OK);
+    assert_eq!(health(ports.admin_port.unwrap()).await, StatusCode::OK);
+
+    let status = std::process::Command::new("kill")
+        .arg("-TERM")
+        .arg(ports.pid.to_string())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let exit = tokio::time::timeout(Duration::from_secs(30), server.wait())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(exit.success());
+    assert!(!port_file.exists());
+}
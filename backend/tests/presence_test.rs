@@ -0,0 +1,96 @@
+//! Heartbeat and presence tests
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use chrono::{Duration, Utc};
+use serde_json::Value;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use bridgex_backend::db::models::{Device, DeviceType};
+use bridgex_backend::{server, AppState, Database, ServerCertificate};
+
+async fn test_app() -> (Router, AppState) {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+    let state = AppState::new(db, ServerCertificate::generate().unwrap());
+    (server::router(state.clone()), state)
+}
+
+async fn save_device(state: &AppState, name: &str, last_seen_ago: Duration) -> Device {
+    let mut device = Device::new(
+        Uuid::new_v4().to_string(),
+        name.to_string(),
+        DeviceType::Mobile,
+        vec![1; 32],
+    );
+    device.last_seen = Some(Utc::now() - last_seen_ago);
+    state.db.save_device(&device).await.unwrap();
+    device
+}
+
+async fn call(app: &Router, method: &str, uri: &str) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+fn presence_of(devices: &Value, id: &str) -> String {
+    devices
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|d| d["id"] == id)
+        .unwrap()["presence"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+#[tokio::test]
+async fn test_heartbeat_brings_device_online() {
+    let (app, state) = test_app().await;
+    let phone = save_device(&state, "Phone", Duration::hours(1)).await;
+    let tablet = save_device(&state, "Tablet", Duration::minutes(2)).await;
+
+    let (status, devices) = call(&app, "GET", "/api/v1/devices").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(presence_of(&devices, &phone.id), "offline");
+    assert_eq!(presence_of(&devices, &tablet.id), "idle");
+
+    let (status, body) = call(
+        &app,
+        "POST",
+        &format!("/api/v1/devices/{}/heartbeat", phone.id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["presence"], "online");
+
+    let (_, devices) = call(&app, "GET", "/api/v1/devices").await;
+    assert_eq!(presence_of(&devices, &phone.id), "online");
+
+    let (_, status) = call(&app, "GET", "/api/v1/status").await;
+    assert_eq!(status["presence"]["online"], 1);
+    assert_eq!(status["presence"]["idle"], 1);
+    assert_eq!(status["presence"]["offline"], 0);
+}
+
+#[tokio::test]
+async fn test_heartbeat_from_unknown_device() {
+    let (app, _state) = test_app().await;
+
+    let (status, _) = call(&app, "POST", "/api/v1/devices/unknown/heartbeat").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,98 @@
+//! QUIC transfer transport tests
+
+use uuid::Uuid;
+
+use bridgex_backend::db::models::{Device, DeviceType, Transfer};
+use bridgex_backend::server::{quic, upload};
+use bridgex_backend::{AppState, Database, ServerCertificate};
+use bridgex_backend::util::sha256_hash;
+
+/// Pairing token of the test device
+const TOKEN: &[u8] = b"test pairing token";
+
+async fn start_server() -> (AppState, std::net::SocketAddr) {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+    let state = AppState::new(db, ServerCertificate::generate().unwrap());
+
+    let endpoint =
+        quic::server_endpoint("127.0.0.1:0".parse().unwrap(), &state.certificate).unwrap();
+    let addr = endpoint.local_addr().unwrap();
+    tokio::spawn(quic::serve(endpoint, state.clone()));
+    (state, addr)
+}
+
+async fn create_transfer(state: &AppState, device: &Device, data: &[u8]) -> Transfer {
+    let transfer = Transfer::new(
+        Uuid::new_v4().to_string(),
+        device.id.clone(),
+        "video.mp4".to_string(),
+        data.len() as i64,
+        format!("sha256:{}", sha256_hash(data)),
+    );
+    state.db.save_transfer(&transfer).await.unwrap();
+    transfer
+}
+
+#[tokio::test]
+async fn test_quic_transfers_files_on_parallel_streams() {
+    let (state, addr) = start_server().await;
+    let device = Device::new(
+        Uuid::new_v4().to_string(),
+        "Test Phone".to_string(),
+        DeviceType::Mobile,
+        vec![5; 32],
+    )
+    .with_pairing_token(TOKEN, chrono::Utc::now() + chrono::Duration::minutes(5));
+    state.db.save_device(&device).await.unwrap();
+
+    let first: Vec<u8> = (0..50_000u32).map(|i| (i % 251) as u8).collect();
+    let second: Vec<u8> = (0..20_000u32).map(|i| (i % 13) as u8).collect();
+    let first_transfer = create_transfer(&state, &device, &first).await;
+    let second_transfer = create_transfer(&state, &device, &second).await;
+
+    let connection = quic::connect(addr, &state.certificate.fingerprint())
+        .await
+        .unwrap();
+
+    let (first_total, second_total) = tokio::join!(
+        quic::send_file(
+            &connection,
+            &device.id,
+            TOKEN,
+            &first_transfer.id,
+            &first,
+            8192,
+        ),
+        quic::send_file(
+            &connection,
+            &device.id,
+            TOKEN,
+            &second_transfer.id,
+            &second,
+            8192,
+        ),
+    );
+    assert_eq!(first_total.unwrap(), first.len() as u64);
+    assert_eq!(second_total.unwrap(), second.len() as u64);
+
+    for (transfer, data) in [(&first_transfer, &first), (&second_transfer, &second)] {
+        let dir = upload::transfer_dir(&state.config.storage.upload_dir, &transfer.id);
+        assert_eq!(&std::fs::read(dir.join("file")).unwrap(), data);
+        let stored = state.db.get_transfer(&transfer.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, "completed");
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    assert_eq!(state.connections.connection_count().await, 1);
+    connection.close(0u32.into(), b"done");
+}
+
+#[tokio::test]
+async fn test_quic_rejects_unpinned_certificate() {
+    let (_state, addr) = start_server().await;
+    let other = ServerCertificate::generate().unwrap();
+
+    let result = quic::connect(addr, &other.fingerprint()).await;
+    assert!(result.is_err());
+}
@@ -0,0 +1,242 @@
+//! Rate limits on pairing and uploads, and lockout after failed authentications
+
+use std::net::SocketAddr;
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use serde_json::Value;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use bridgex_backend::config::{RateBudget, RateLimitConfig};
+use bridgex_backend::db::models::{Device, DeviceType, Transfer};
+use bridgex_backend::server::{self, auth, tcp};
+use bridgex_backend::{AppState, Config, Database, ServerCertificate};
+
+/// Pairing token of the test device
+const TOKEN: &[u8] = b"test pairing token";
+
+fn test_state(db: Database, rate_limit: RateLimitConfig) -> AppState {
+    let mut config = Config::default();
+    config.storage.upload_dir =
+        std::env::temp_dir().join(format!("bridgex-rate-limit-{}", Uuid::new_v4()));
+    config.rate_limit = rate_limit;
+    AppState::with_config(db, ServerCertificate::generate().unwrap(), config)
+}
+
+async fn memory_db() -> Database {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+    db
+}
+
+fn pair_request(from: &str) -> Request<Body> {
+    let mut request = Request::post("/api/v1/pair")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            r#"{"device_name":"Phone","device_type":"mobile"}"#,
+        ))
+        .unwrap();
+    let addr: SocketAddr = from.parse().unwrap();
+    request.extensions_mut().insert(ConnectInfo(addr));
+    request
+}
+
+async fn send(app: &Router, request: Request<Body>) -> axum::response::Response {
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_pairing_is_rate_limited_per_ip() {
+    let rate_limit = RateLimitConfig {
+        pair: RateBudget {
+            per_minute: 1,
+            burst: 2,
+        },
+        ..Default::default()
+    };
+    let state = test_state(memory_db().await, rate_limit);
+    let app = server::router(state.clone());
+
+    for _ in 0..2 {
+        let response = send(&app, pair_request("192.168.1.20:50000")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let response = send(&app, pair_request("192.168.1.20:50001")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after), "{}", retry_after);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "rate_limited");
+
+    // Other clients and other routes have their own budgets
+    let response = send(&app, pair_request("192.168.1.21:50000")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut request = Request::get("/api/v1/devices").body(Body::empty()).unwrap();
+    request.extensions_mut().insert(ConnectInfo(
+        "192.168.1.20:50002".parse::<SocketAddr>().unwrap(),
+    ));
+    assert_eq!(send(&app, request).await.status(), StatusCode::OK);
+
+    // Loopback is exempt from every budget but pairing, which is only
+    // served there
+    for _ in 0..5 {
+        let mut request = Request::get("/api/v1/devices").body(Body::empty()).unwrap();
+        request.extensions_mut().insert(ConnectInfo(
+            "127.0.0.1:50000".parse::<SocketAddr>().unwrap(),
+        ));
+        assert_eq!(send(&app, request).await.status(), StatusCode::OK);
+    }
+    for _ in 0..2 {
+        let response = send(&app, pair_request("127.0.0.1:50000")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = send(&app, pair_request("127.0.0.1:50001")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let metrics = state.metrics.render();
+    assert!(
+        metrics.contains(r#"bridgex_rate_limited_total{route="pair"} 2"#),
+        "{}",
+        metrics
+    );
+}
+
+#[tokio::test]
+async fn test_repeated_auth_failures_lock_out_the_peer() {
+    let rate_limit = RateLimitConfig {
+        exempt_loopback: false,
+        max_failures: 2,
+        ..Default::default()
+    };
+    let state = test_state(memory_db().await, rate_limit);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve(listener, state.clone()));
+
+    let device = Device::new(
+        Uuid::new_v4().to_string(),
+        "Phone".to_string(),
+        DeviceType::Mobile,
+        vec![9; 32],
+    )
+    .with_pairing_token(TOKEN, chrono::Utc::now() + chrono::Duration::minutes(5));
+    state.db.save_device(&device).await.unwrap();
+    let transfer = Transfer::new(
+        Uuid::new_v4().to_string(),
+        device.id.clone(),
+        "photo.jpg".to_string(),
+        4,
+        "hash".to_string(),
+    );
+    state.db.save_transfer(&transfer).await.unwrap();
+
+    for _ in 0..2 {
+        let error = tcp::send_transfer(addr, &device.id, b"wrong token", &transfer.id, b"data", 4)
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("authentication failed"),
+            "{}",
+            error
+        );
+    }
+
+    // Even the right key is refused until the lockout expires
+    let error = tcp::send_transfer(
+        addr,
+        &device.id,
+        TOKEN,
+        &transfer.id,
+        b"data",
+        4,
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        error.to_string().contains("too many failed attempts"),
+        "{}",
+        error
+    );
+
+    let ip = addr.ip();
+    assert!(state.rate_limiter.lockout(ip).is_some());
+
+    // The lockout covers the HTTP API as well
+    let app = server::router(state.clone());
+    let response = send(&app, pair_request("127.0.0.1:50000")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("retry-after"));
+
+    std::fs::remove_dir_all(&state.config.storage.upload_dir).ok();
+}
+
+#[tokio::test]
+async fn test_device_budgets_follow_the_signing_device() {
+    let rate_limit = RateLimitConfig {
+        api: RateBudget {
+            per_minute: 1,
+            burst: 2,
+        },
+        ..Default::default()
+    };
+    let state = test_state(memory_db().await, rate_limit);
+    let app = server::device_router(state.clone());
+    let device = Device::new(
+        Uuid::new_v4().to_string(),
+        "Phone".to_string(),
+        DeviceType::Mobile,
+        vec![9; 32],
+    )
+    .with_pairing_token(TOKEN, chrono::Utc::now() + chrono::Duration::minutes(5));
+    state.db.save_device(&device).await.unwrap();
+    let heartbeat = format!("/api/v1/devices/{}/heartbeat", device.id);
+    let request = |from: &str, signed: bool| {
+        let mut builder = Request::post(&heartbeat);
+        if signed {
+            builder = builder.header(
+                "authorization",
+                auth::authorization(&device.id, TOKEN, &Method::POST, &heartbeat, chrono::Utc::now()),
+            );
+        }
+        let mut request = builder.body(Body::empty()).unwrap();
+        let addr: SocketAddr = from.parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(addr));
+        request
+    };
+
+    // Unsigned requests naming the device only spend their own IP's budget
+    for host in 30..34 {
+        let from = format!("192.168.1.{}:50000", host);
+        assert_eq!(
+            send(&app, request(&from, false)).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    // The device's own budget follows it across addresses
+    assert_eq!(
+        send(&app, request("192.168.1.20:50000", true)).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&app, request("192.168.1.21:50000", true)).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&app, request("192.168.1.22:50000", true)).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
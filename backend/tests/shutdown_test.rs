@@ -0,0 +1,186 @@
+//! Graceful shutdown and resuming interrupted transfers
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use serde_json::Value;
+use std::time::Duration;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use bridgex_backend::db::models::{Device, DeviceType, Transfer};
+use bridgex_backend::server::{self, shutdown, upload};
+use bridgex_backend::{AppState, Config, Database, ServerCertificate};
+use bridgex_backend::util::sha256_hash;
+
+const BOUNDARY: &str = "bridgex-test-boundary";
+
+async fn test_state() -> (AppState, Transfer) {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+
+    let device = Device::new(
+        Uuid::new_v4().to_string(),
+        "Phone".to_string(),
+        DeviceType::Mobile,
+        vec![1; 32],
+    );
+    db.save_device(&device).await.unwrap();
+    let transfer = Transfer::new(
+        Uuid::new_v4().to_string(),
+        device.id.clone(),
+        "notes.txt".to_string(),
+        8,
+        format!("sha256:{}", sha256_hash(b"halfdone")),
+    );
+    db.save_transfer(&transfer).await.unwrap();
+
+    let mut config = Config::default();
+    config.storage.upload_dir =
+        std::env::temp_dir().join(format!("bridgex-shutdown-{}", Uuid::new_v4()));
+    let state = AppState::with_config(db, ServerCertificate::generate().unwrap(), config);
+    (state, transfer)
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+fn chunk_request(transfer_id: &str, offset: u64, chunk: &[u8]) -> Request<Body> {
+    let mut body = Vec::new();
+    let offset = offset.to_string();
+    for (name, value) in [
+        ("transfer_id", transfer_id.as_bytes()),
+        ("offset", offset.as_bytes()),
+        ("chunk", chunk),
+    ] {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                BOUNDARY, name
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(value);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+
+    Request::post("/api/v1/transfer/upload")
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        )
+        .body(Body::from(body))
+        .unwrap()
+}
+
+async fn upload_status(app: &Router, transfer_id: &str) -> Value {
+    let request = Request::get(format!("/api/v1/transfer/{}/status", transfer_id))
+        .body(Body::empty())
+        .unwrap();
+    let (status, body) = send(app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    body
+}
+
+#[tokio::test]
+async fn test_interrupted_upload_resumes() {
+    let (state, transfer) = test_state().await;
+    let app = server::router(state.clone());
+
+    let (status, _) = send(&app, chunk_request(&transfer.id, 0, b"half")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        upload_status(&app, &transfer.id).await["status"],
+        "uploading"
+    );
+
+    state.shutdown.trigger();
+    let interrupted = shutdown::drain(&state, Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_eq!(interrupted, 1);
+
+    let body = upload_status(&app, &transfer.id).await;
+    assert_eq!(body["status"], "interrupted");
+    assert_eq!(body["resumable"], true);
+    assert_eq!(body["chunks_received"], 1);
+
+    // After a restart the client sends the missing chunk and finalizes
+    let (status, _) = send(&app, chunk_request(&transfer.id, 4, b"done")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        upload_status(&app, &transfer.id).await["status"],
+        "uploading"
+    );
+
+    let request = Request::post("/api/v1/transfer/finalize")
+        .header("content-type", "application/json")
+        .body(Body::from(format!(
+            r#"{{"transfer_id":"{}"}}"#,
+            transfer.id
+        )))
+        .unwrap();
+    let (status, body) = send(&app, request).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["total_bytes"], 8);
+
+    let path = upload::transfer_dir(&state.config.storage.upload_dir, &transfer.id).join("file");
+    assert_eq!(std::fs::read(path).unwrap(), b"halfdone");
+    std::fs::remove_dir_all(&state.config.storage.upload_dir).ok();
+}
+
+#[tokio::test]
+async fn test_drain_waits_for_active_transfers() {
+    let (state, transfer) = test_state().await;
+    state
+        .db
+        .update_transfer_status(&transfer.id, "uploading", None)
+        .await
+        .unwrap();
+
+    let guard = state.shutdown.begin(&transfer.id);
+    let drain = tokio::spawn({
+        let state = state.clone();
+        async move { shutdown::drain(&state, Duration::from_secs(5)).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!drain.is_finished());
+
+    // The session finishes within the drain period and completes the transfer
+    state
+        .db
+        .update_transfer_status(&transfer.id, "completed", Some(chrono::Utc::now()))
+        .await
+        .unwrap();
+    drop(guard);
+
+    assert_eq!(drain.await.unwrap().unwrap(), 0);
+    let transfer = state.db.get_transfer(&transfer.id).await.unwrap().unwrap();
+    assert_eq!(transfer.status, "completed");
+}
+
+#[tokio::test]
+async fn test_listeners_stop_accepting_on_shutdown() {
+    let (state, _) = test_state().await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp = tokio::spawn(server::tcp::serve(listener, state.clone()));
+
+    state.shutdown.trigger();
+    tokio::time::timeout(Duration::from_secs(1), tcp)
+        .await
+        .expect("TCP listener stops on shutdown")
+        .unwrap()
+        .unwrap();
+}
@@ -0,0 +1,151 @@
+//! WebRTC signaling relay tests
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use bridgex_backend::db::models::{Device, DeviceType};
+use bridgex_backend::{server, AppState, Database, ServerCertificate};
+
+const OFFER_SDP: &str = "v=0\r\no=- 1 1 IN IP4 0.0.0.0\r\ns=-\r\nt=0 0\r\nm=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\na=setup:actpass\r\n";
+const ANSWER_SDP: &str = "v=0\r\no=- 2 1 IN IP4 0.0.0.0\r\ns=-\r\nt=0 0\r\nm=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\na=setup:active\r\n";
+
+async fn test_app() -> (Router, Device) {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+
+    let device = Device::new(
+        Uuid::new_v4().to_string(),
+        "Test Phone".to_string(),
+        DeviceType::Mobile,
+        vec![1; 32],
+    );
+    db.save_device(&device).await.unwrap();
+
+    let state = AppState::new(db, ServerCertificate::generate().unwrap());
+    (server::router(state), device)
+}
+
+async fn call(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(match body {
+            Some(body) => Body::from(body.to_string()),
+            None => Body::empty(),
+        })
+        .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+    (status, value)
+}
+
+#[tokio::test]
+async fn test_offer_answer_and_candidate_exchange() {
+    let (app, device) = test_app().await;
+
+    let (status, body) = call(
+        &app,
+        "POST",
+        "/api/v1/signaling/offer",
+        Some(json!({
+            "device_id": device.id,
+            "offer": { "type": "offer", "sdp": OFFER_SDP },
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let session_id = body["session_id"].as_str().unwrap().to_string();
+    let base = format!("/api/v1/signaling/{}", session_id);
+
+    // Answerer fetches the offer and answers it
+    let (status, session) = call(&app, "GET", &base, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(session["offer"]["sdp"], OFFER_SDP);
+    assert!(session["answer"].is_null());
+
+    let answer = json!({ "type": "answer", "sdp": ANSWER_SDP });
+    let (status, _) = call(
+        &app,
+        "POST",
+        &format!("{}/answer", base),
+        Some(answer.clone()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = call(&app, "POST", &format!("{}/answer", base), Some(answer)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // Both sides trickle candidates
+    for (role, port) in [("offerer", 50000), ("answerer", 50001), ("answerer", 50002)] {
+        let (status, _) = call(
+            &app,
+            "POST",
+            &format!("{}/candidates", base),
+            Some(json!({
+                "role": role,
+                "candidate": format!("candidate:1 1 UDP 2122252543 192.168.1.10 {} typ host", port),
+                "sdpMid": "0",
+                "sdpMLineIndex": 0,
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+
+    let (status, body) = call(
+        &app,
+        "GET",
+        &format!("{}/candidates?role=answerer&since=1", base),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["candidates"].as_array().unwrap().len(), 1);
+    assert_eq!(body["next"], 2);
+
+    let (status, _) = call(&app, "DELETE", &base, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = call(&app, "GET", &base, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_offer_validation() {
+    let (app, device) = test_app().await;
+
+    let (status, _) = call(
+        &app,
+        "POST",
+        "/api/v1/signaling/offer",
+        Some(json!({
+            "device_id": device.id,
+            "offer": { "type": "offer", "sdp": "webrtc-offer-placeholder" },
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = call(
+        &app,
+        "POST",
+        "/api/v1/signaling/offer",
+        Some(json!({
+            "device_id": "unknown",
+            "offer": { "type": "offer", "sdp": OFFER_SDP },
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,248 @@
+//! Direct TCP transfer channel tests
+
+use uuid::Uuid;
+
+use bridgex_backend::db::models::{Device, DeviceType, Transfer};
+use bridgex_backend::server::api::create_pairing;
+use bridgex_backend::server::audit::Actor;
+use bridgex_backend::server::tcp::{self, ProtocolError};
+use bridgex_backend::server::PairRequest;
+use bridgex_backend::server::upload;
+use bridgex_backend::{AppState, Database, ServerCertificate};
+use bridgex_backend::util::sha256_hash;
+
+/// Pairing token of the test device
+const TOKEN: &[u8] = b"test pairing token";
+
+async fn start_server() -> (AppState, std::net::SocketAddr) {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+    let state = AppState::new(db, ServerCertificate::generate().unwrap());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve(listener, state.clone()));
+    (state, addr)
+}
+
+async fn create_transfer(state: &AppState, data: &[u8]) -> (Device, Transfer) {
+    let device = Device::new(
+        Uuid::new_v4().to_string(),
+        "Test Phone".to_string(),
+        DeviceType::Mobile,
+        vec![9; 32],
+    )
+    .with_pairing_token(TOKEN, chrono::Utc::now() + chrono::Duration::minutes(5));
+    state.db.save_device(&device).await.unwrap();
+
+    let transfer = Transfer::new(
+        Uuid::new_v4().to_string(),
+        device.id.clone(),
+        "photo.jpg".to_string(),
+        data.len() as i64,
+        format!("sha256:{}", sha256_hash(data)),
+    );
+    state.db.save_transfer(&transfer).await.unwrap();
+    (device, transfer)
+}
+
+#[tokio::test]
+async fn test_tcp_transfer_completes() {
+    let (state, addr) = start_server().await;
+    let data: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+    let (device, transfer) = create_transfer(&state, &data).await;
+
+    let total = tcp::send_transfer(
+        addr,
+        &device.id,
+        TOKEN,
+        &transfer.id,
+        &data,
+        4096,
+    )
+    .await
+    .unwrap();
+    assert_eq!(total, data.len() as u64);
+
+    let dir = upload::transfer_dir(&state.config.storage.upload_dir, &transfer.id);
+    let assembled = std::fs::read(dir.join("file")).unwrap();
+    assert_eq!(assembled, data);
+
+    let stored = state.db.get_transfer(&transfer.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, "completed");
+    assert!(stored.completed_at.is_some());
+    assert_eq!(state.connections.connection_count().await, 0);
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn test_tcp_hello_rejects_bad_auth() {
+    let (state, addr) = start_server().await;
+    let (device, transfer) = create_transfer(&state, b"data").await;
+
+    let result = tcp::send_transfer(addr, &device.id, b"wrong token", &transfer.id, b"data", 4).await;
+    assert!(matches!(result, Err(ProtocolError::Peer(_))));
+
+    let stored = state.db.get_transfer(&transfer.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, "pending");
+}
+
+async fn save_transfer(state: &AppState, device: &Device) -> Transfer {
+    state.db.save_device(device).await.unwrap();
+    let transfer = Transfer::new(
+        Uuid::new_v4().to_string(),
+        device.id.clone(),
+        "notes.txt".to_string(),
+        4,
+        format!("sha256:{}", sha256_hash(b"data")),
+    );
+    state.db.save_transfer(&transfer).await.unwrap();
+    transfer
+}
+
+#[tokio::test]
+async fn test_pairing_token_authenticates_and_is_confirmed() {
+    let (state, addr) = start_server().await;
+    let (device, pairing) = create_pairing(
+        &state,
+        PairRequest {
+            device_name: "Phone".to_string(),
+            device_type: DeviceType::Mobile,
+            platform: None,
+            os_version: None,
+            app_version: None,
+        },
+        &Actor::Cli,
+    )
+    .await
+    .unwrap();
+    assert!(device.confirmed_at.is_none());
+    assert_ne!(device.token_hash.as_deref(), Some(pairing.token.as_slice()));
+    let transfer = save_transfer(&state, &device).await;
+
+    tcp::send_transfer(addr, &device.id, &pairing.token, &transfer.id, b"data", 4)
+        .await
+        .unwrap();
+
+    let stored = state.db.get_device(&device.id).await.unwrap().unwrap();
+    assert!(stored.confirmed_at.is_some());
+    std::fs::remove_dir_all(upload::transfer_dir(
+        &state.config.storage.upload_dir,
+        &transfer.id,
+    ))
+    .ok();
+}
+
+#[tokio::test]
+async fn test_unused_token_expires_with_the_pairing_window() {
+    let (state, addr) = start_server().await;
+    let expired = chrono::Utc::now() - chrono::Duration::minutes(1);
+
+    let unconfirmed = Device::new(
+        Uuid::new_v4().to_string(),
+        "Phone".to_string(),
+        DeviceType::Mobile,
+        vec![9; 32],
+    )
+    .with_pairing_token(TOKEN, expired);
+    let transfer = save_transfer(&state, &unconfirmed).await;
+    let result = tcp::send_transfer(addr, &unconfirmed.id, TOKEN, &transfer.id, b"data", 4).await;
+    assert!(matches!(result, Err(ProtocolError::Peer(_))));
+
+    // A device that authenticated in time keeps its token
+    let mut confirmed = Device::new(
+        Uuid::new_v4().to_string(),
+        "Tablet".to_string(),
+        DeviceType::Tablet,
+        vec![9; 32],
+    )
+    .with_pairing_token(TOKEN, expired);
+    confirmed.confirmed_at = Some(expired);
+    let transfer = save_transfer(&state, &confirmed).await;
+    tcp::send_transfer(addr, &confirmed.id, TOKEN, &transfer.id, b"data", 4)
+        .await
+        .unwrap();
+    std::fs::remove_dir_all(upload::transfer_dir(
+        &state.config.storage.upload_dir,
+        &transfer.id,
+    ))
+    .ok();
+}
+
+#[tokio::test]
+async fn test_hello_for_another_challenge_is_rejected() {
+    use bridgex_backend::db::models::token_hash;
+    use tcp::Frame;
+
+    let (state, addr) = start_server().await;
+    let (device, transfer) = create_transfer(&state, b"data").await;
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    tcp::write_frame(&mut stream, &Frame::Open).await.unwrap();
+    let nonce = match tcp::read_frame(&mut stream).await.unwrap() {
+        Some(Frame::Challenge { nonce }) => nonce,
+        other => panic!("expected challenge, got {:?}", other),
+    };
+
+    // A tag recorded from an earlier session does not answer this challenge
+    let mut replayed = nonce;
+    replayed[0] ^= 1;
+    let hello = Frame::Hello {
+        device_id: device.id.clone(),
+        transfer_id: transfer.id.clone(),
+        auth: tcp::hello_auth(&token_hash(TOKEN), &replayed, &device.id, &transfer.id),
+    };
+    tcp::write_frame(&mut stream, &hello).await.unwrap();
+    assert!(matches!(
+        tcp::read_frame(&mut stream).await.unwrap(),
+        Some(Frame::Error { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_tcp_rejects_chunks_past_the_file_size() {
+    let (state, addr) = start_server().await;
+    let (device, transfer) = create_transfer(&state, b"data").await;
+
+    let result = tcp::send_transfer(addr, &device.id, TOKEN, &transfer.id, b"too long", 8).await;
+    assert!(matches!(result, Err(ProtocolError::Peer(_))));
+
+    let dir = upload::transfer_dir(&state.config.storage.upload_dir, &transfer.id);
+    assert!(!dir.join("chunk_00000000000000000000").exists());
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn test_tcp_refuses_completed_transfers() {
+    let (state, addr) = start_server().await;
+    let (device, transfer) = create_transfer(&state, b"data").await;
+    state
+        .db
+        .update_transfer_status(&transfer.id, "completed", Some(chrono::Utc::now()))
+        .await
+        .unwrap();
+
+    let result = tcp::send_transfer(addr, &device.id, TOKEN, &transfer.id, b"data", 4).await;
+    assert!(matches!(result, Err(ProtocolError::Peer(_))));
+
+    let stored = state.db.get_transfer(&transfer.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, "completed");
+}
+
+#[tokio::test]
+async fn test_tcp_fails_transfers_whose_file_does_not_match() {
+    let (state, addr) = start_server().await;
+    let (device, transfer) = create_transfer(&state, b"data").await;
+
+    let result = tcp::send_transfer(addr, &device.id, TOKEN, &transfer.id, b"DATA", 4).await;
+    assert!(matches!(result, Err(ProtocolError::Peer(_))));
+
+    let stored = state.db.get_transfer(&transfer.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, "failed");
+    std::fs::remove_dir_all(upload::transfer_dir(
+        &state.config.storage.upload_dir,
+        &transfer.id,
+    ))
+    .ok();
+}
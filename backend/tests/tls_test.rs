@@ -0,0 +1,133 @@
+//! HTTPS listener with the pinned server certificate
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use hyper_util::rt::TokioIo;
+use rustls::pki_types::ServerName;
+use serde_json::Value;
+use tokio_rustls::TlsConnector;
+
+use bridgex_backend::config::TlsMode;
+use bridgex_backend::crypto::cert::CERT_SUBJECT;
+use bridgex_backend::pairing::parse_pairing_uri;
+use bridgex_backend::server::{self, tls};
+use bridgex_backend::{AppState, Config, Database, ServerCertificate};
+
+async fn start_server() -> (
+    AppState,
+    SocketAddr,
+    tokio::task::JoinHandle<std::io::Result<()>>,
+) {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+    let mut config = Config::default();
+    config.server.tls = TlsMode::On;
+    let state = AppState::with_config(db, ServerCertificate::generate().unwrap(), config);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    state
+        .discovery
+        .set_local_addrs(vec!["192.168.1.5:8080".parse().unwrap()])
+        .await;
+    let server = tokio::spawn(tls::serve(
+        listener,
+        tls::acceptor(&state.certificate).unwrap(),
+        server::router(state.clone()),
+        state.shutdown.clone(),
+    ));
+    (state, addr, server)
+}
+
+/// Send one request over a TLS connection pinned to `fingerprint`
+async fn send(
+    addr: SocketAddr,
+    fingerprint: &str,
+    request: Request<Body>,
+) -> std::io::Result<(StatusCode, Vec<u8>)> {
+    let connector = TlsConnector::from(Arc::new(tls::client_config(fingerprint).unwrap()));
+    let stream = tokio::net::TcpStream::connect(addr).await?;
+    let stream = connector
+        .connect(ServerName::try_from(CERT_SUBJECT).unwrap(), stream)
+        .await?;
+
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .unwrap();
+    tokio::spawn(connection);
+    let response = sender.send_request(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(Body::new(response.into_body()), usize::MAX)
+        .await
+        .unwrap();
+    Ok((status, body.to_vec()))
+}
+
+fn get(uri: &str) -> Request<Body> {
+    Request::get(uri)
+        .header("host", "localhost")
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_api_is_served_over_tls() {
+    let (state, addr, server) = start_server().await;
+    let fingerprint = state.certificate.fingerprint();
+
+    let (status, _) = send(addr, &fingerprint, get("/api/v1/health"))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    // The QR code carries everything needed to pin the certificate
+    let request = Request::post("/api/v1/pair")
+        .header("host", "localhost")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            r#"{"device_name":"Phone","device_type":"mobile"}"#,
+        ))
+        .unwrap();
+    let (status, body) = send(addr, &fingerprint, request).await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    let payload = parse_pairing_uri(body["pairing_uri"].as_str().unwrap()).unwrap();
+    assert!(payload.tls);
+    assert_eq!(payload.server_fingerprint, fingerprint);
+    assert_eq!(
+        payload.api_url(payload.addresses[0]),
+        "https://192.168.1.5:8080"
+    );
+
+    state.shutdown.trigger();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_other_certificates_are_refused() {
+    let (_, addr, _) = start_server().await;
+    let impostor = ServerCertificate::generate().unwrap().fingerprint();
+
+    let error = send(addr, &impostor, get("/api/v1/health"))
+        .await
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("invalid peer certificate"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn test_plain_http_is_not_served() {
+    let (_, addr, _) = start_server().await;
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .unwrap();
+    tokio::spawn(connection);
+    assert!(sender.send_request(get("/api/v1/health")).await.is_err());
+}
@@ -0,0 +1,172 @@
+//! Transfer history API tests
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use chrono::{Duration, TimeZone, Utc};
+use serde_json::Value;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use bridgex_backend::db::models::{Device, DeviceType, Transfer};
+use bridgex_backend::{server, AppState, Database, ServerCertificate};
+
+struct Fixture {
+    app: Router,
+    phone: Device,
+    laptop: Device,
+}
+
+/// 10 transfers one hour apart, alternating between two devices
+async fn fixture() -> Fixture {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+
+    let phone = Device::new(
+        Uuid::new_v4().to_string(),
+        "Phone".to_string(),
+        DeviceType::Mobile,
+        vec![1; 32],
+    );
+    let laptop = Device::new(
+        Uuid::new_v4().to_string(),
+        "Laptop".to_string(),
+        DeviceType::Desktop,
+        vec![2; 32],
+    );
+    db.save_device(&phone).await.unwrap();
+    db.save_device(&laptop).await.unwrap();
+
+    let start = Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap();
+    for i in 0..10 {
+        let device = if i % 2 == 0 { &phone } else { &laptop };
+        let mut transfer = Transfer::new(
+            format!("transfer-{}", i),
+            device.id.clone(),
+            format!("photo_{}{}.jpg", i, if i == 3 { "%" } else { "" }),
+            1000 + i,
+            "hash".to_string(),
+        );
+        transfer.created_at = start + Duration::hours(i);
+        if i % 3 == 0 {
+            transfer.status = "completed".to_string();
+        }
+        db.save_transfer(&transfer).await.unwrap();
+    }
+
+    let state = AppState::new(db, ServerCertificate::generate().unwrap());
+    Fixture {
+        app: server::router(state),
+        phone,
+        laptop,
+    }
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, Value) {
+    let response = app
+        .clone()
+        .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+fn ids(page: &Value) -> Vec<String> {
+    page["transfers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_cursor_pagination_visits_every_transfer_once() {
+    let f = fixture().await;
+
+    let mut seen = Vec::new();
+    let mut uri = "/api/v1/transfers?limit=3".to_string();
+    loop {
+        let (status, page) = get(&f.app, &uri).await;
+        assert_eq!(status, StatusCode::OK);
+        seen.extend(ids(&page));
+        match page["next_cursor"].as_str() {
+            Some(cursor) => uri = format!("/api/v1/transfers?limit=3&cursor={}", cursor),
+            None => break,
+        }
+    }
+
+    let expected: Vec<String> = (0..10).rev().map(|i| format!("transfer-{}", i)).collect();
+    assert_eq!(seen, expected);
+}
+
+#[tokio::test]
+async fn test_filters_and_sort() {
+    let f = fixture().await;
+
+    let (_, page) = get(
+        &f.app,
+        &format!("/api/v1/transfers?device_id={}&sort=asc", f.phone.id),
+    )
+    .await;
+    assert_eq!(
+        ids(&page),
+        ["transfer-0", "transfer-2", "transfer-4", "transfer-6", "transfer-8"]
+    );
+
+    let (_, page) = get(
+        &f.app,
+        &format!(
+            "/api/v1/transfers?device_id={}&status=completed",
+            f.laptop.id
+        ),
+    )
+    .await;
+    assert_eq!(ids(&page), ["transfer-9", "transfer-3"]);
+
+    let (_, page) = get(
+        &f.app,
+        "/api/v1/transfers?from=2025-03-01T02:00:00Z&to=2025-03-01T05:00:00Z&sort=asc",
+    )
+    .await;
+    assert_eq!(ids(&page), ["transfer-2", "transfer-3", "transfer-4"]);
+
+    // LIKE wildcards in the search are matched literally
+    let (_, page) = get(&f.app, "/api/v1/transfers?q=PHOTO_3%25").await;
+    assert_eq!(ids(&page), ["transfer-3"]);
+}
+
+#[tokio::test]
+async fn test_invalid_parameters() {
+    let f = fixture().await;
+
+    for query in [
+        "status=lost",
+        "sort=sideways",
+        "limit=0",
+        "cursor=garbage",
+        "from=yesterday",
+    ] {
+        let (status, _) = get(&f.app, &format!("/api/v1/transfers?{}", query)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn test_transfer_detail() {
+    let f = fixture().await;
+
+    let (status, transfer) = get(&f.app, "/api/v1/transfers/transfer-4").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(transfer["file_name"], "photo_4.jpg");
+    assert_eq!(transfer["device_name"], "Phone");
+
+    let (status, _) = get(&f.app, "/api/v1/transfers/missing").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
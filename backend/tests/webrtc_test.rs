@@ -0,0 +1,158 @@
+//! WebRTC data channel tests
+
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use bridgex_backend::db::models::{Device, DeviceType, Transfer};
+use bridgex_backend::server::signaling::{Role, SessionDescription};
+use bridgex_backend::server::webrtc::Peer;
+use bridgex_backend::server::{self, auth, p2p};
+use bridgex_backend::util::sha256_hash;
+use bridgex_backend::{AppState, Config, Database, ServerCertificate, SignalingHub};
+
+/// Pairing token of the test device
+const TOKEN: &[u8] = b"test pairing token";
+
+async fn signed(
+    app: &Router,
+    device: &Device,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method.clone())
+        .uri(uri)
+        .header("content-type", "application/json")
+        .header(
+            "authorization",
+            auth::authorization(&device.id, TOKEN, &method, uri, chrono::Utc::now()),
+        )
+        .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_data_channel_between_two_peers() {
+    let hub = SignalingHub::new();
+    let (offerer, offerer_candidates) = Peer::new(Role::Offerer).await.unwrap();
+    let (answerer, answerer_candidates) = Peer::new(Role::Answerer).await.unwrap();
+
+    let offer = offerer.local_description().await;
+    let session = hub.create_session("device-1", offer.clone()).await.unwrap();
+    let answer = answerer.local_description().await;
+    hub.set_answer(&session.id, answer.clone()).await.unwrap();
+
+    let (offerer_connection, answerer_connection) = tokio::join!(
+        p2p::connect_through_hub(&hub, &session.id, &offerer, offerer_candidates, &answer),
+        p2p::connect_through_hub(&hub, &session.id, &answerer, answerer_candidates, &offer),
+    );
+    let (offerer_connection, answerer_connection) =
+        (offerer_connection.unwrap(), answerer_connection.unwrap());
+
+    // Messages larger than one data channel message arrive whole and in order
+    let message: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let mut opened = offerer_connection.open_channel().await.unwrap();
+    opened.write_all(&message).await.unwrap();
+    opened.flush().await.unwrap();
+
+    let mut accepted = answerer_connection.accept_channel().await.unwrap();
+    assert_eq!(accepted.label(), "bridgex-transfer");
+    let mut received = vec![0u8; message.len()];
+    accepted.read_exact(&mut received).await.unwrap();
+    assert_eq!(received, message);
+
+    accepted.write_all(b"ack").await.unwrap();
+    accepted.flush().await.unwrap();
+    let mut reply = [0u8; 3];
+    opened.read_exact(&mut reply).await.unwrap();
+    assert_eq!(&reply, b"ack");
+
+    offerer_connection.close().await;
+    answerer_connection.close().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_server_peer_receives_a_file() {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.init_schema().await.unwrap();
+    let mut config = Config::default();
+    config.storage.upload_dir =
+        std::env::temp_dir().join(format!("bridgex-webrtc-{}", Uuid::new_v4()));
+    let state = AppState::with_config(db, ServerCertificate::generate().unwrap(), config);
+    let app = server::device_router(state.clone());
+
+    let device = Device::new(
+        Uuid::new_v4().to_string(),
+        "Test Phone".to_string(),
+        DeviceType::Mobile,
+        vec![5; 32],
+    )
+    .with_pairing_token(TOKEN, chrono::Utc::now() + chrono::Duration::minutes(5));
+    state.db.save_device(&device).await.unwrap();
+    let data: Vec<u8> = (0..50_000u32).map(|i| (i % 13) as u8).collect();
+    let transfer = Transfer::new(
+        Uuid::new_v4().to_string(),
+        device.id.clone(),
+        "video.mp4".to_string(),
+        data.len() as i64,
+        format!("sha256:{}", sha256_hash(&data)),
+    );
+    state.db.save_transfer(&transfer).await.unwrap();
+
+    // The device offers and asks the server to answer
+    let (peer, candidates) = Peer::new(Role::Offerer).await.unwrap();
+    let offer = peer.local_description().await;
+    let (status, body) = signed(
+        &app,
+        &device,
+        Method::POST,
+        "/api/v1/signaling/offer",
+        Some(json!({ "device_id": device.id, "offer": offer })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let session_id = body["session_id"].as_str().unwrap().to_string();
+
+    let accept = format!("/api/v1/signaling/{}/accept", session_id);
+    let (status, body) = signed(&app, &device, Method::POST, &accept, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let answer: SessionDescription = serde_json::from_value(body).unwrap();
+
+    // A session is only answered once
+    let (status, _) = signed(&app, &device, Method::POST, &accept, None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let connection =
+        p2p::connect_through_hub(&state.signaling, &session_id, &peer, candidates, &answer)
+            .await
+            .unwrap();
+    let total = p2p::send_file(&connection, &device.id, TOKEN, &transfer.id, &data, 20_000)
+        .await
+        .unwrap();
+    assert_eq!(total, data.len() as u64);
+
+    let stored = state.db.get_transfer(&transfer.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, "completed");
+    let connected = state.connections.get_connection(&device.id).await.unwrap();
+    assert_eq!(connected.connection_type, p2p::ConnectionType::WebRTC);
+
+    connection.close().await;
+    std::fs::remove_dir_all(&state.config.storage.upload_dir).ok();
+}
@@ -0,0 +1,208 @@
+//! Client for the backend's admin API
+//!
+//! On Unix the backend serves the admin API on a socket only the current
+//! user can open, so the app talks plain HTTP over it. Elsewhere it falls
+//! back to the loopback admin port over HTTPS pinned to the backend's
+//! certificate.
+
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How the admin API is reached
+#[derive(Debug, Clone)]
+pub enum AdminClient {
+    /// Unix socket at this path
+    #[cfg(unix)]
+    Socket(PathBuf),
+    /// Loopback TCP port, with a client pinned to the backend certificate
+    Tcp { client: reqwest::Client, port: u16 },
+}
+
+/// Status and body of an admin API response
+#[derive(Debug)]
+pub struct AdminResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl AdminResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn text(self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn json(&self) -> Result<serde_json::Value, String> {
+        serde_json::from_slice(&self.body).map_err(|e| e.to_string())
+    }
+}
+
+impl AdminClient {
+    pub async fn get(&self, path: &str) -> Result<AdminResponse, String> {
+        self.send("GET", path, None, Vec::new()).await
+    }
+
+    pub async fn post_json(
+        &self,
+        path: &str,
+        body: &serde_json::Value,
+    ) -> Result<AdminResponse, String> {
+        let body = serde_json::to_vec(body).map_err(|e| e.to_string())?;
+        self.send("POST", path, Some("application/json".to_string()), body)
+            .await
+    }
+
+    pub async fn post_multipart(
+        &self,
+        path: &str,
+        form: Multipart,
+    ) -> Result<AdminResponse, String> {
+        let content_type = form.content_type();
+        self.send("POST", path, Some(content_type), form.finish())
+            .await
+    }
+
+    async fn send(
+        &self,
+        method: &str,
+        path: &str,
+        content_type: Option<String>,
+        body: Vec<u8>,
+    ) -> Result<AdminResponse, String> {
+        match self {
+            #[cfg(unix)]
+            AdminClient::Socket(socket) => {
+                send_over_socket(socket, method, path, content_type, body).await
+            }
+            AdminClient::Tcp { client, port } => {
+                let method = reqwest::Method::from_bytes(method.as_bytes())
+                    .map_err(|e| e.to_string())?;
+                let mut request = client
+                    .request(method, format!("https://127.0.0.1:{}{}", port, path))
+                    .body(body);
+                if let Some(content_type) = content_type {
+                    request = request.header("content-type", content_type);
+                }
+                let response = request
+                    .send()
+                    .await
+                    .map_err(|e| format!("Request failed: {}", e))?;
+                let status = response.status().as_u16();
+                let body = response
+                    .bytes()
+                    .await
+                    .map_err(|e| format!("Request failed: {}", e))?;
+                Ok(AdminResponse {
+                    status,
+                    body: body.to_vec(),
+                })
+            }
+        }
+    }
+}
+
+/// Send one HTTP/1.1 request over a fresh connection to the socket
+#[cfg(unix)]
+async fn send_over_socket(
+    socket: &std::path::Path,
+    method: &str,
+    path: &str,
+    content_type: Option<String>,
+    body: Vec<u8>,
+) -> Result<AdminResponse, String> {
+    let stream = tokio::net::UnixStream::connect(socket)
+        .await
+        .map_err(|e| format!("Failed to connect to {}: {}", socket.display(), e))?;
+    let (mut sender, connection) = hyper::client::conn::handshake(stream)
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    tokio::spawn(connection);
+
+    let mut request = hyper::Request::builder()
+        .method(method)
+        .uri(path)
+        .header("host", "localhost");
+    if let Some(content_type) = content_type {
+        request = request.header("content-type", content_type);
+    }
+    let request = request
+        .body(hyper::Body::from(body))
+        .map_err(|e| e.to_string())?;
+
+    let response = sender
+        .send_request(request)
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    let status = response.status().as_u16();
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    Ok(AdminResponse {
+        status,
+        body: body.to_vec(),
+    })
+}
+
+/// A `multipart/form-data` body
+pub struct Multipart {
+    boundary: String,
+    body: Vec<u8>,
+}
+
+impl Default for Multipart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Multipart {
+    pub fn new() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or_default();
+        Self {
+            boundary: format!("bridgex-{:x}", nanos),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn text(self, name: &str, value: &str) -> Self {
+        self.part(name, None, value.as_bytes())
+    }
+
+    pub fn bytes(self, name: &str, data: &[u8]) -> Self {
+        self.part(name, Some("application/octet-stream"), data)
+    }
+
+    fn part(mut self, name: &str, content_type: Option<&str>, data: &[u8]) -> Self {
+        self.body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n",
+                self.boundary, name
+            )
+            .as_bytes(),
+        );
+        if let Some(content_type) = content_type {
+            self.body
+                .extend_from_slice(format!("Content-Type: {}\r\n", content_type).as_bytes());
+        }
+        self.body.extend_from_slice(b"\r\n");
+        self.body.extend_from_slice(data);
+        self.body.extend_from_slice(b"\r\n");
+        self
+    }
+
+    fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.body
+            .extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        self.body
+    }
+}
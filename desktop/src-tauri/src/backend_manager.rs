@@ -1,21 +1,127 @@
-use std::process::{Child, Command};
-use std::sync::{Arc, Mutex};
+use std::io::{BufRead, BufReader};
+use std::net::{Ipv4Addr, TcpListener};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdout, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::admin_client::AdminClient;
+#[cfg(not(unix))]
+use crate::pinned_tls::pinned_client;
+use crate::supervisor::{BackendStatus, LogBuffer, LogStream, STATUS_EVENT};
+
+/// Drain period the backend grants active uploads when asked to stop
+const SHUTDOWN_GRACE_SECS: u64 = 5;
+
+/// How long to wait for a graceful exit before killing the backend
+const STOP_TIMEOUT: Duration = Duration::from_secs(SHUTDOWN_GRACE_SECS + 5);
+
+/// Prefix of the line the backend prints on stdout once it is listening
+const READY_PREFIX: &str = "BRIDGEX_READY ";
+
+/// Ports the running backend listens on, from its handshake line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendPorts {
+    /// Device API port, reachable by phones on the LAN
+    pub port: u16,
+    /// Admin API port on loopback; off on Unix, where the app uses the socket
+    #[serde(default)]
+    pub admin_port: Option<u16>,
+    pub tcp_port: u16,
+    pub quic_port: u16,
+    /// Unix socket serving the admin API
+    #[serde(default)]
+    pub admin_socket: Option<PathBuf>,
+    pub pid: u32,
+}
+
+impl BackendPorts {
+    fn parse_handshake(line: &str) -> Option<Self> {
+        let json = line.trim_end().strip_prefix(READY_PREFIX)?;
+        serde_json::from_str(json).ok()
+    }
+}
+
 #[derive(Debug)]
 pub struct BackendManager {
     process: Arc<Mutex<Option<Child>>>,
-    port: u16,
+    /// Device API port phones are paired with; another one is picked when taken
+    preferred_port: u16,
+    /// Unix socket serving the admin API, used by the app itself
+    #[cfg(unix)]
+    admin_socket: PathBuf,
+    /// Ports reported by the running backend
+    ports: Arc<Mutex<Option<BackendPorts>>>,
+    /// Admin API client shared by every command, known once the backend is listening
+    admin: Arc<Mutex<Option<AdminClient>>>,
+    /// Whether the backend should be running; an exit while set is a crash
+    wanted: AtomicBool,
+    status: Mutex<BackendStatus>,
+    /// Recent stdout and stderr lines of the backend
+    logs: Arc<LogBuffer>,
+    /// Receives `backend-status` events once the app is set up
+    app: OnceLock<AppHandle>,
 }
 
 impl BackendManager {
-    pub fn new(port: u16) -> Self {
+    pub fn new(preferred_port: u16) -> Self {
         Self {
             process: Arc::new(Mutex::new(None)),
-            port,
+            preferred_port,
+            #[cfg(unix)]
+            admin_socket: default_admin_socket(),
+            ports: Arc::new(Mutex::new(None)),
+            admin: Arc::new(Mutex::new(None)),
+            wanted: AtomicBool::new(false),
+            status: Mutex::new(BackendStatus::Stopped),
+            logs: Arc::new(LogBuffer::default()),
+            app: OnceLock::new(),
         }
     }
 
+    /// Send status changes to the frontend of `app`
+    pub fn attach(&self, app: AppHandle) {
+        let _ = self.app.set(app);
+    }
+
+    /// Current lifecycle state of the backend
+    pub fn status(&self) -> BackendStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Record a status change and emit it as a `backend-status` event
+    pub fn set_status(&self, status: BackendStatus) {
+        *self.status.lock().unwrap() = status.clone();
+        if let Some(app) = self.app.get() {
+            if let Err(e) = app.emit(STATUS_EVENT, status) {
+                eprintln!("[Backend] Failed to emit status: {}", e);
+            }
+        }
+    }
+
+    /// Recent output of the backend
+    pub fn logs(&self) -> &LogBuffer {
+        &self.logs
+    }
+
+    /// Client for the backend's admin API
+    pub fn admin(&self) -> Result<AdminClient, String> {
+        self.admin
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| "Backend is not running".to_string())
+    }
+
+    /// Ports of the running backend
+    pub fn ports(&self) -> Option<BackendPorts> {
+        self.ports.lock().unwrap().clone()
+    }
+
     /// Start backend server automatically
     pub fn start(&self) -> Result<(), String> {
         let mut proc = self.process.lock().unwrap();
@@ -29,38 +135,140 @@ impl BackendManager {
 
         println!("[Backend] Starting server at {}", backend_path);
 
-        // Start backend process
-        let child = Command::new(&backend_path)
-            .env("BRIDGEX_PORT", self.port.to_string())
+        // Keep the paired port when it is free; the other ports only the app uses
+        let port = pick_port(self.preferred_port);
+        let mut command = Command::new(&backend_path);
+        command
+            .env("BRIDGEX_PORT", port.to_string())
+            .env("BRIDGEX_TCP_PORT", "0")
+            .env("BRIDGEX_QUIC_PORT", "0")
+            .env("BRIDGEX_TLS", "on")
             .env("BRIDGEX_AUTO_START", "1")
+            .env("BRIDGEX_SHUTDOWN_GRACE_SECS", SHUTDOWN_GRACE_SECS.to_string())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        // Only this user can open the socket, unlike the loopback port, which
+        // is turned off so other local users cannot reach the admin API
+        #[cfg(unix)]
+        let connect = {
+            command
+                .env("BRIDGEX_ADMIN_PORT", "off")
+                .env("BRIDGEX_ADMIN_SOCKET", &self.admin_socket);
+            let admin_socket = self.admin_socket.clone();
+            move |ports: &BackendPorts| {
+                let socket = ports.admin_socket.clone().unwrap_or_else(|| admin_socket.clone());
+                Ok(AdminClient::Socket(socket))
+            }
+        };
+        // The certificate is created on first use and kept across restarts
+        #[cfg(not(unix))]
+        let connect = {
+            command.env("BRIDGEX_ADMIN_PORT", "0");
+            let client = pinned_client(&read_fingerprint(&backend_path)?)?;
+            move |ports: &BackendPorts| {
+                Ok(AdminClient::Tcp {
+                    client: client.clone(),
+                    port: ports.admin_port.ok_or("Backend has no admin port")?,
+                })
+            }
+        };
+
+        *self.ports.lock().unwrap() = None;
+        *self.admin.lock().unwrap() = None;
+
+        // Start backend process
+        let mut child = command
             .spawn()
             .map_err(|e| format!("Failed to start backend: {}", e))?;
 
+        let stdout = child.stdout.take().ok_or("Backend stdout is not captured")?;
+        let ports = self.ports.clone();
+        let admin = self.admin.clone();
+        let logs = self.logs.clone();
+        std::thread::spawn(move || read_stdout(stdout, logs, ports, admin, connect));
+
+        if let Some(stderr) = child.stderr.take() {
+            let logs = self.logs.clone();
+            std::thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    logs.push(LogStream::Stderr, line);
+                }
+            });
+        }
+
         *proc = Some(child);
-        println!("[Backend] Server started on port {}", self.port);
+        self.wanted.store(true, Ordering::SeqCst);
+        self.set_status(BackendStatus::Starting);
+        println!("[Backend] Server process started, waiting for its ports");
 
         Ok(())
     }
 
     /// Stop backend server gracefully
+    ///
+    /// Asks the backend to shut down so it can drain active uploads and mark
+    /// unfinished transfers resumable, and only kills it if it has not
+    /// exited within `STOP_TIMEOUT`.
     pub fn stop(&self) -> Result<(), String> {
+        self.wanted.store(false, Ordering::SeqCst);
         let mut proc = self.process.lock().unwrap();
         
         if let Some(mut child) = proc.take() {
             println!("[Backend] Stopping server...");
+
+            if request_shutdown(&child) && wait_for_exit(&mut child, STOP_TIMEOUT) {
+                println!("[Backend] Server stopped");
+                self.set_status(BackendStatus::Stopped);
+                return Ok(());
+            }
+
+            println!("[Backend] Server did not stop in time, killing it");
             child.kill().map_err(|e| format!("Failed to kill backend: {}", e))?;
-            println!("[Backend] Server stopped");
+            let _ = child.wait();
+            println!("[Backend] Server killed");
+            self.set_status(BackendStatus::Stopped);
         }
 
         Ok(())
     }
 
+    /// Kill a backend that stopped responding
+    ///
+    /// Unlike [`stop`](Self::stop) this counts as an unexpected exit, so the
+    /// supervisor restarts the backend.
+    pub fn kill_unresponsive(&self) {
+        if let Some(child) = self.process.lock().unwrap().as_mut() {
+            println!("[Backend] Killing unresponsive server");
+            if let Err(e) = child.kill() {
+                eprintln!("[Backend] Failed to kill backend: {}", e);
+            }
+        }
+    }
+
+    /// Exit status of a backend that exited without being stopped
+    ///
+    /// The exited process is released, so `start` can run it again.
+    pub fn take_unexpected_exit(&self) -> Option<ExitStatus> {
+        if !self.wanted.load(Ordering::SeqCst) {
+            return None;
+        }
+        let mut proc = self.process.lock().unwrap();
+        let status = proc.as_mut()?.try_wait().ok()??;
+        proc.take();
+        self.wanted.store(false, Ordering::SeqCst);
+        Some(status)
+    }
+
     /// Check if backend is running and healthy
     pub async fn is_healthy(&self) -> bool {
-        let url = format!("http://127.0.0.1:{}/api/v1/health", self.port);  // Fixed: added /api/v1
-        
-        match reqwest::get(&url).await {
-            Ok(resp) => resp.status().is_success(),
+        let admin = match self.admin() {
+            Ok(admin) => admin,
+            Err(_) => return false,
+        };
+
+        match admin.get("/api/v1/health").await {
+            Ok(resp) => resp.is_success(),
             Err(_) => false,
         }
     }
@@ -72,18 +280,39 @@ impl BackendManager {
 
         loop {
             if self.is_healthy().await {
-                println!("[Backend] Server is ready!");
+                let ports = self.ports();
+                if let Some(ports) = &ports {
+                    println!("[Backend] Server is ready on port {}", ports.port);
+                }
+                self.set_status(BackendStatus::Running { ports });
                 return Ok(());
             }
 
+            // The supervisor reports the exit and restarts it
+            if self.has_exited() {
+                return Err("Backend exited during startup".to_string());
+            }
+
             if start.elapsed() > timeout {
-                return Err("Backend startup timeout".to_string());
+                let reason = "Backend startup timeout".to_string();
+                self.set_status(BackendStatus::Failed {
+                    reason: reason.clone(),
+                });
+                return Err(reason);
             }
 
             tokio::time::sleep(Duration::from_millis(200)).await;
         }
     }
 
+    /// Whether the backend process was started and has exited since
+    fn has_exited(&self) -> bool {
+        match self.process.lock().unwrap().as_mut() {
+            Some(child) => !matches!(child.try_wait(), Ok(None)),
+            None => true,
+        }
+    }
+
     /// Get backend executable path (platform-specific)
     fn get_backend_path(&self) -> Result<String, String> {
         #[cfg(target_os = "windows")]
@@ -127,6 +356,114 @@ impl BackendManager {
     }
 }
 
+/// `preferred` when it can be bound, else 0 so the backend picks a free port
+fn pick_port(preferred: u16) -> u16 {
+    match TcpListener::bind((Ipv4Addr::UNSPECIFIED, preferred)) {
+        Ok(_) => preferred,
+        Err(_) => {
+            println!("[Backend] Port {} is in use, letting the backend pick one", preferred);
+            0
+        }
+    }
+}
+
+/// Keep the backend's stdout in `logs`, picking up the ports from its handshake line
+///
+/// Reads until the backend exits, so it never blocks on a full pipe.
+fn read_stdout(
+    stdout: ChildStdout,
+    logs: Arc<LogBuffer>,
+    ports: Arc<Mutex<Option<BackendPorts>>>,
+    admin: Arc<Mutex<Option<AdminClient>>>,
+    connect: impl Fn(&BackendPorts) -> Result<AdminClient, String>,
+) {
+    let mut pid = None;
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else { break };
+        if let Some(reported) = BackendPorts::parse_handshake(&line) {
+            pid = Some(reported.pid);
+            println!("[Backend] Listening on port {}", reported.port);
+            match connect(&reported) {
+                Ok(client) => *admin.lock().unwrap() = Some(client),
+                Err(e) => eprintln!("[Backend] {}", e),
+            }
+            *ports.lock().unwrap() = Some(reported);
+        } else {
+            logs.push(LogStream::Stdout, line);
+        }
+    }
+
+    // A restarted backend may already have reported its own ports
+    let mut ports = ports.lock().unwrap();
+    if pid.is_some() && ports.as_ref().map(|ports| ports.pid) == pid {
+        *ports = None;
+        *admin.lock().unwrap() = None;
+    }
+}
+
+/// Socket path of the admin API: `bridgex/admin.sock` in the user runtime
+/// directory, or `bridgex-<uid>/admin.sock` in the shared temporary directory
+#[cfg(unix)]
+fn default_admin_socket() -> PathBuf {
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        Some(runtime_dir) => runtime_dir.join("bridgex"),
+        // SAFETY: geteuid has no preconditions and cannot fail
+        None => std::env::temp_dir().join(format!("bridgex-{}", unsafe { libc::geteuid() })),
+    };
+    dir.join("admin.sock")
+}
+
+/// Ask the backend binary for the fingerprint of its certificate
+#[cfg(not(unix))]
+fn read_fingerprint(backend_path: &str) -> Result<String, String> {
+    let output = Command::new(backend_path)
+        .args(["keys", "show-fingerprint"])
+        .output()
+        .map_err(|e| format!("Failed to read backend certificate: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to read backend certificate: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Send SIGTERM to the backend, which starts its graceful shutdown
+///
+/// Returns false when no graceful stop could be requested.
+#[cfg(unix)]
+fn request_shutdown(child: &Child) -> bool {
+    Command::new("kill")
+        .arg("-TERM")
+        .arg(child.id().to_string())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Windows has no SIGTERM for console-less children; the backend is killed
+#[cfg(not(unix))]
+fn request_shutdown(_child: &Child) -> bool {
+    false
+}
+
+/// Wait up to `timeout` for the backend to exit
+fn wait_for_exit(child: &mut Child, timeout: Duration) -> bool {
+    let start = std::time::Instant::now();
+    while start.elapsed() < timeout {
+        match child.try_wait() {
+            Ok(Some(_)) => return true,
+            Ok(None) => std::thread::sleep(Duration::from_millis(100)),
+            Err(_) => return false,
+        }
+    }
+    false
+}
+
 impl Drop for BackendManager {
     fn drop(&mut self) {
         let _ = self.stop();
@@ -141,6 +478,14 @@ pub async fn check_backend_status(
     Ok(backend.is_healthy().await)
 }
 
+/// Tauri command returning the ports of the running backend
+#[tauri::command]
+pub async fn get_backend_ports(
+    backend: tauri::State<'_, Arc<BackendManager>>,
+) -> Result<Option<BackendPorts>, String> {
+    Ok(backend.ports())
+}
+
 /// Tauri command to restart backend
 #[tauri::command]
 pub async fn restart_backend(
@@ -1,52 +1,57 @@
 // Prevents additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod admin_client;
 mod backend_manager;
 mod file_picker;
+#[cfg(not(unix))]
+mod pinned_tls;
+mod supervisor;
 
 use std::sync::Arc;
-use backend_manager::{BackendManager, check_backend_status, restart_backend};
+use admin_client::Multipart;
+use backend_manager::{BackendManager, check_backend_status, get_backend_ports, restart_backend};
 use file_picker::{pick_file, pick_files, pick_folder, get_file_info, read_file_base64};
+use supervisor::{get_backend_logs, get_backend_status};
 use tauri::Manager;
 
 /// Request device pairing
 #[tauri::command]
-async fn pair_device(device_name: String) -> Result<String, String> {
-    let client = reqwest::Client::new();
+async fn pair_device(
+    backend: tauri::State<'_, Arc<BackendManager>>,
+    device_name: String,
+) -> Result<String, String> {
     let payload = serde_json::json!({
         "device_name": device_name
     });
 
-    match client
-        .post("http://127.0.0.1:8080/api/v1/pair")
-        .json(&payload)
-        .send()
-        .await
-    {
-        Ok(resp) if resp.status().is_success() => {
-            resp.text().await.map_err(|e| e.to_string())
-        }
-        Ok(resp) => Err(format!("Pairing failed: {}", resp.status())),
-        Err(e) => Err(format!("Request failed: {}", e)),
+    match backend.admin()?.post_json("/api/v1/pair", &payload).await {
+        Ok(resp) if resp.is_success() => Ok(resp.text()),
+        Ok(resp) => Err(format!("Pairing failed: {}", resp.status)),
+        Err(e) => Err(e),
     }
 }
 
 /// Get paired devices list
 #[tauri::command]
-async fn get_devices() -> Result<String, String> {
-    match reqwest::get("http://127.0.0.1:8080/api/v1/devices").await {
-        Ok(resp) if resp.status().is_success() => {
-            resp.text().await.map_err(|e| e.to_string())
-        }
-        Ok(resp) => Err(format!("Failed to get devices: {}", resp.status())),
-        Err(e) => Err(format!("Request failed: {}", e)),
+async fn get_devices(
+    backend: tauri::State<'_, Arc<BackendManager>>,
+) -> Result<String, String> {
+    match backend.admin()?.get("/api/v1/devices").await {
+        Ok(resp) if resp.is_success() => Ok(resp.text()),
+        Ok(resp) => Err(format!("Failed to get devices: {}", resp.status)),
+        Err(e) => Err(e),
     }
 }
 
 /// Initialize file transfer
 #[tauri::command]
-async fn send_file(device_id: String, file_path: String) -> Result<String, String> {
-    let client = reqwest::Client::new();
+async fn send_file(
+    backend: tauri::State<'_, Arc<BackendManager>>,
+    device_id: String,
+    file_path: String,
+) -> Result<String, String> {
+    let admin = backend.admin()?;
     
     // Read file metadata
     let metadata = tokio::fs::metadata(&file_path)
@@ -66,20 +71,13 @@ async fn send_file(device_id: String, file_path: String) -> Result<String, Strin
         "file_size": file_size,
     });
     
-    let init_resp = client
-        .post("http://127.0.0.1:8080/api/v1/transfer/init")
-        .json(&init_payload)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+    let init_resp = admin.post_json("/api/v1/transfer/init", &init_payload).await?;
     
-    if !init_resp.status().is_success() {
-        return Err(format!("Transfer init failed: {}", init_resp.status()));
+    if !init_resp.is_success() {
+        return Err(format!("Transfer init failed: {}", init_resp.status));
     }
     
-    let init_data: serde_json::Value = init_resp.json()
-        .await
-        .map_err(|e| e.to_string())?;
+    let init_data = init_resp.json()?;
     
     let transfer_id = init_data["transfer_id"]
         .as_str()
@@ -97,20 +95,15 @@ async fn send_file(device_id: String, file_path: String) -> Result<String, Strin
         let end = std::cmp::min(offset + chunk_size, file_data.len());
         let chunk = &file_data[offset..end];
         
-        let form = reqwest::multipart::Form::new()
-            .text("transfer_id", transfer_id.to_string())
-            .text("offset", offset.to_string())
-            .part("chunk", reqwest::multipart::Part::bytes(chunk.to_vec()));
+        let form = Multipart::new()
+            .text("transfer_id", transfer_id)
+            .text("offset", &offset.to_string())
+            .bytes("chunk", chunk);
         
-        let upload_resp = client
-            .post("http://127.0.0.1:8080/api/v1/transfer/upload")
-            .multipart(form)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
+        let upload_resp = admin.post_multipart("/api/v1/transfer/upload", form).await?;
         
-        if !upload_resp.status().is_success() {
-            return Err(format!("Chunk upload failed at offset {}: {}", offset, upload_resp.status()));
+        if !upload_resp.is_success() {
+            return Err(format!("Chunk upload failed at offset {}: {}", offset, upload_resp.status));
         }
         
         offset = end;
@@ -121,15 +114,10 @@ async fn send_file(device_id: String, file_path: String) -> Result<String, Strin
         "transfer_id": transfer_id,
     });
     
-    let finalize_resp = client
-        .post("http://127.0.0.1:8080/api/v1/transfer/finalize")
-        .json(&finalize_payload)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+    let finalize_resp = admin.post_json("/api/v1/transfer/finalize", &finalize_payload).await?;
     
-    if !finalize_resp.status().is_success() {
-        return Err(format!("Transfer finalize failed: {}", finalize_resp.status()));
+    if !finalize_resp.is_success() {
+        return Err(format!("Transfer finalize failed: {}", finalize_resp.status));
     }
     
     Ok(format!("File '{}' transferred successfully to device {}", file_name, device_id))
@@ -146,6 +134,9 @@ async fn main() {
         .manage(backend_arc.clone())
         .invoke_handler(tauri::generate_handler![
             check_backend_status,
+            get_backend_ports,
+            get_backend_logs,
+            get_backend_status,
             restart_backend,
             pick_file,
             pick_files,
@@ -158,26 +149,11 @@ async fn main() {
         ])
         .setup(move |app| {
             let backend_clone = backend_arc.clone();
+            backend_clone.attach(app.handle().clone());
             
-            // Start backend in async task
-            tauri::async_runtime::spawn(async move {
-                println!("🚀 Starting BridgeX backend...");
-                
-                if let Err(e) = backend_clone.start() {
-                    eprintln!("❌ Failed to start backend: {}", e);
-                    eprintln!("   Please ensure the backend binary is available");
-                    return;
-                }
-                
-                println!("⏳ Waiting for backend to be ready...");
-                match backend_clone.wait_ready(15).await {
-                    Ok(_) => println!("✅ Backend is ready and healthy!"),
-                    Err(e) => {
-                        eprintln!("⚠️  Backend health check failed: {}", e);
-                        eprintln!("   The app will continue but may not function correctly");
-                    }
-                }
-            });
+            // Start the backend and restart it if it crashes
+            println!("🚀 Starting BridgeX backend...");
+            tauri::async_runtime::spawn(supervisor::supervise(backend_clone));
             
             Ok(())
         })
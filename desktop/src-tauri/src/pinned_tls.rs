@@ -0,0 +1,52 @@
+//! HTTPS client for the backend, pinned to its self-signed certificate
+//!
+//! The backend presents a self-signed certificate whose SHA-256 fingerprint
+//! `bridgex-server keys show-fingerprint` prints. Instead of WebPKI
+//! validation the client accepts exactly that certificate.
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, CertificateError, ClientConfig, Error, ServerName};
+use sha2::{Digest, Sha256};
+
+/// Certificate verifier accepting only the certificate with a pinned fingerprint
+struct PinnedCertVerifier {
+    fingerprint: String,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, Error> {
+        if hex::encode(Sha256::digest(&end_entity.0)) == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+}
+
+/// HTTP client trusting only the certificate with `fingerprint` (hex SHA-256)
+pub fn pinned_client(fingerprint: &str) -> Result<reqwest::Client, String> {
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier {
+            fingerprint: fingerprint.trim().to_ascii_lowercase(),
+        }))
+        .with_no_client_auth();
+
+    reqwest::Client::builder()
+        .use_preconfigured_tls(config)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
@@ -0,0 +1,220 @@
+//! Backend process supervision
+//!
+//! [`supervise`] starts the backend and keeps it running: when the process
+//! exits without being asked to, or is not healthy within
+//! `STARTUP_TIMEOUT_SECS`, it is restarted after a delay that doubles with
+//! each crash in a row, up to `MAX_BACKOFF`. Status changes are sent to
+//! the frontend as `backend-status` events ([`BackendStatus`]), and the
+//! backend's stdout and stderr are kept in a [`LogBuffer`] the frontend can
+//! read with the `get_backend_logs` command.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::backend_manager::{BackendManager, BackendPorts};
+
+/// Name of the event carrying [`BackendStatus`] changes
+pub const STATUS_EVENT: &str = "backend-status";
+
+/// Lines of backend output kept for the frontend
+const LOG_CAPACITY: usize = 2000;
+
+/// Longer lines are cut, so one runaway line cannot fill the buffer
+const MAX_LINE_LEN: usize = 4096;
+
+/// How long the backend gets to become healthy after a (re)start
+const STARTUP_TIMEOUT_SECS: u64 = 15;
+
+/// How often the backend process is checked for an exit
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Delay before the first restart after a crash
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Longest delay between restarts
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A backend that ran this long is considered stable again
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+/// Lifecycle state of the backend, as sent to the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum BackendStatus {
+    /// Process spawned, not answering health checks yet
+    Starting,
+    /// Healthy and listening on `ports`
+    Running { ports: Option<BackendPorts> },
+    /// Exited on its own; restarted after `restart_in_secs`
+    Crashed {
+        exit_code: Option<i32>,
+        restart_in_secs: u64,
+    },
+    /// Could not be started or did not become healthy
+    Failed { reason: String },
+    /// Stopped by the app
+    Stopped,
+}
+
+/// Stream a backend output line was written to
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+/// One line of backend output
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    pub stream: LogStream,
+    pub line: String,
+}
+
+/// Ring buffer of the most recent backend output lines
+#[derive(Debug)]
+pub struct LogBuffer {
+    lines: Mutex<VecDeque<LogLine>>,
+    capacity: usize,
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        Self::new(LOG_CAPACITY)
+    }
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    /// Append a line, dropping the oldest one when full
+    pub fn push(&self, stream: LogStream, mut line: String) {
+        if line.len() > MAX_LINE_LEN {
+            let mut end = MAX_LINE_LEN;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            line.truncate(end);
+        }
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(LogLine {
+            timestamp_ms,
+            stream,
+            line,
+        });
+    }
+
+    /// The last `limit` lines, oldest first
+    pub fn tail(&self, limit: usize) -> Vec<LogLine> {
+        let lines = self.lines.lock().unwrap();
+        let skip = lines.len().saturating_sub(limit);
+        lines.iter().skip(skip).cloned().collect()
+    }
+}
+
+/// Restart delays doubling from `INITIAL_BACKOFF` up to `MAX_BACKOFF`
+#[derive(Debug)]
+struct Backoff {
+    next: Duration,
+}
+
+impl Backoff {
+    fn new() -> Self {
+        Self {
+            next: INITIAL_BACKOFF,
+        }
+    }
+
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(MAX_BACKOFF);
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.next = INITIAL_BACKOFF;
+    }
+}
+
+/// Start the backend and restart it whenever it exits unexpectedly
+///
+/// Runs for the lifetime of the app. Stopping the backend through
+/// [`BackendManager::stop`] is not a crash and does not trigger a restart.
+pub async fn supervise(backend: Arc<BackendManager>) {
+    let mut backoff = Backoff::new();
+    loop {
+        let started = Instant::now();
+        match backend.start() {
+            Ok(()) => {
+                if let Err(e) = backend.wait_ready(STARTUP_TIMEOUT_SECS).await {
+                    eprintln!("[Backend] {}", e);
+                    // A backend that never became healthy is restarted like a crashed one
+                    backend.kill_unresponsive();
+                }
+            }
+            Err(e) => {
+                eprintln!("[Backend] {}", e);
+                backend.set_status(BackendStatus::Failed { reason: e });
+                tokio::time::sleep(backoff.next_delay()).await;
+                continue;
+            }
+        }
+
+        let exit = loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            if let Some(exit) = backend.take_unexpected_exit() {
+                break exit;
+            }
+        };
+
+        if started.elapsed() >= STABLE_AFTER {
+            backoff.reset();
+        }
+        let delay = backoff.next_delay();
+        eprintln!(
+            "[Backend] Server exited unexpectedly ({}), restarting in {}s",
+            exit,
+            delay.as_secs()
+        );
+        backend.set_status(BackendStatus::Crashed {
+            exit_code: exit.code(),
+            restart_in_secs: delay.as_secs(),
+        });
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Tauri command returning the most recent backend output
+#[tauri::command]
+pub async fn get_backend_logs(
+    backend: tauri::State<'_, Arc<BackendManager>>,
+    limit: Option<usize>,
+) -> Result<Vec<LogLine>, String> {
+    Ok(backend.logs().tail(limit.unwrap_or(LOG_CAPACITY)))
+}
+
+/// Tauri command returning the current backend status
+#[tauri::command]
+pub async fn get_backend_status(
+    backend: tauri::State<'_, Arc<BackendManager>>,
+) -> Result<BackendStatus, String> {
+    Ok(backend.status())
+}
@@ -1,17 +1,24 @@
 import 'dart:convert';
 import 'dart:io';
+import 'dart:typed_data';
+import 'package:crypto/crypto.dart';
 import 'package:http/http.dart' as http;
 import 'package:flutter_secure_storage/flutter_secure_storage.dart';
+import 'pinned_http_client.dart';
 
 class ApiService {
   String _baseUrl = 'http://192.168.1.100:8080'; // Default, will be updated
   final _storage = const FlutterSecureStorage();
   String? _deviceId;
   String? _sessionToken;
+  // Certificate fingerprint of an HTTPS server, pinned instead of CA validation
+  String? _serverFingerprint;
+  http.Client _client = http.Client();
 
   static const String _baseUrlKey = 'bridge_base_url';
   static const String _deviceIdKey = 'bridge_device_id';
   static const String _sessionTokenKey = 'bridge_session_token';
+  static const String _serverFingerprintKey = 'bridge_server_fingerprint';
 
   ApiService() {
     _loadConfig();
@@ -21,6 +28,8 @@ class ApiService {
     _baseUrl = await _storage.read(key: _baseUrlKey) ?? _baseUrl;
     _deviceId = await _storage.read(key: _deviceIdKey);
     _sessionToken = await _storage.read(key: _sessionTokenKey);
+    _serverFingerprint = await _storage.read(key: _serverFingerprintKey);
+    _client = createPinnedClient(_serverFingerprint);
   }
 
   Future<void> setBaseUrl(String url) async {
@@ -28,6 +37,19 @@ class ApiService {
     await _storage.write(key: _baseUrlKey, value: url);
   }
 
+  /// Point the service at a server, pinning its certificate for https URLs
+  Future<void> setServer(String url, {String? fingerprint}) async {
+    await setBaseUrl(url);
+    _serverFingerprint = url.startsWith('https://') ? fingerprint : null;
+    if (_serverFingerprint != null) {
+      await _storage.write(key: _serverFingerprintKey, value: _serverFingerprint);
+    } else {
+      await _storage.delete(key: _serverFingerprintKey);
+    }
+    _client.close();
+    _client = createPinnedClient(_serverFingerprint);
+  }
+
   String get baseUrl => _baseUrl;
   String? get deviceId => _deviceId;
   bool get isPaired => _deviceId != null && _sessionToken != null;
@@ -35,7 +57,7 @@ class ApiService {
   // Health check
   Future<bool> checkHealth() async {
     try {
-      final response = await http
+      final response = await _client
           .get(Uri.parse('$_baseUrl/api/v1/health'))
           .timeout(const Duration(seconds: 5));
       return response.statusCode == 200;
@@ -50,23 +72,53 @@ class ApiService {
     required String pairingData,
   }) async {
     try {
-      // Decode pairing data (contains server URL + public key)
-      final decodedData = utf8.decode(base64.decode(pairingData));
-      final pairingInfo = jsonDecode(decodedData);
+      final Map<String, dynamic> pairingInfo;
+      if (pairingData.startsWith('bridgex://')) {
+        // bridgex://pair?...&addr={host:port}&fp={fingerprint}[&tls=1]
+        final uri = Uri.parse(pairingData);
+        final addresses = uri.queryParametersAll['addr'] ?? const [];
+        final scheme = uri.queryParameters['tls'] == '1' ? 'https' : 'http';
+        pairingInfo = {
+          'public_key': uri.queryParameters['key'],
+          if (addresses.isNotEmpty) 'server_url': '$scheme://${addresses.first}',
+          'server_fingerprint': uri.queryParameters['fp'],
+          'device_id': uri.queryParameters['id'],
+          'session_token': uri.queryParameters['token'],
+        };
+      } else {
+        // Legacy format: base64 JSON with the server URL and public key
+        final decodedData = utf8.decode(base64.decode(pairingData));
+        pairingInfo = jsonDecode(decodedData);
+      }
 
       // Extract server URL and update
       if (pairingInfo['server_url'] != null) {
-        await setBaseUrl(pairingInfo['server_url']);
+        await setServer(
+          pairingInfo['server_url'],
+          fingerprint: pairingInfo['server_fingerprint'],
+        );
       }
 
-      // Send pairing request
-      final response = await http.post(
+      // The QR code of a current server already registered this device;
+      // pairing is only available on the server's admin API
+      if (pairingInfo['device_id'] != null) {
+        _deviceId = pairingInfo['device_id'];
+        _sessionToken = pairingInfo['session_token'];
+        await _storage.write(key: _deviceIdKey, value: _deviceId);
+        await _storage.write(key: _sessionTokenKey, value: _sessionToken);
+        return;
+      }
+
+      // Legacy servers: send pairing request
+      final response = await _client.post(
         Uri.parse('$_baseUrl/api/v1/pair'),
         headers: {'Content-Type': 'application/json'},
         body: jsonEncode({
           'device_name': deviceName,
+          'device_type': 'mobile',
           'public_key': pairingInfo['public_key'],
           'platform': Platform.operatingSystem,
+          'os_version': Platform.operatingSystemVersion,
         }),
       ).timeout(const Duration(seconds: 10));
 
@@ -90,9 +142,9 @@ class ApiService {
   // Get paired devices list
   Future<List<Map<String, dynamic>>> getDevices() async {
     try {
-      final response = await http.get(
+      final response = await _client.get(
         Uri.parse('$_baseUrl/api/v1/devices'),
-        headers: _getAuthHeaders(),
+        headers: _getAuthHeaders('GET', '$_baseUrl/api/v1/devices'),
       ).timeout(const Duration(seconds: 10));
 
       if (response.statusCode != 200) {
@@ -122,9 +174,9 @@ class ApiService {
       final fileName = file.path.split('/').last;
 
       // 1. Initialize transfer
-      final initResponse = await http.post(
+      final initResponse = await _client.post(
         Uri.parse('$_baseUrl/api/v1/transfer/init'),
-        headers: _getAuthHeaders(),
+        headers: _getAuthHeaders('POST', '$_baseUrl/api/v1/transfer/init'),
         body: jsonEncode({
           'device_id': deviceId,
           'file_name': fileName,
@@ -155,14 +207,14 @@ class ApiService {
           Uri.parse('$_baseUrl/api/v1/transfer/upload'),
         );
 
-        request.headers.addAll(_getAuthHeaders());
+        request.headers.addAll(_getAuthHeaders('POST', request.url.toString()));
         request.fields['transfer_id'] = transferId;
         request.fields['offset'] = offset.toString();
         request.files.add(
           http.MultipartFile.fromBytes('chunk', chunk, filename: 'chunk'),
         );
 
-        final streamedResponse = await request.send()
+        final streamedResponse = await _client.send(request)
             .timeout(const Duration(seconds: 30));
         final uploadResponse = await http.Response.fromStream(streamedResponse);
 
@@ -179,9 +231,9 @@ class ApiService {
       }
 
       // 3. Finalize transfer
-      final finalizeResponse = await http.post(
+      final finalizeResponse = await _client.post(
         Uri.parse('$_baseUrl/api/v1/transfer/finalize'),
-        headers: _getAuthHeaders(),
+        headers: _getAuthHeaders('POST', '$_baseUrl/api/v1/transfer/finalize'),
         body: jsonEncode({'transfer_id': transferId}),
       ).timeout(const Duration(seconds: 10));
 
@@ -196,9 +248,9 @@ class ApiService {
   // Delete paired device
   Future<void> unpairDevice(String targetDeviceId) async {
     try {
-      final response = await http.delete(
+      final response = await _client.delete(
         Uri.parse('$_baseUrl/api/v1/devices/$targetDeviceId'),
-        headers: _getAuthHeaders(),
+        headers: _getAuthHeaders('DELETE', '$_baseUrl/api/v1/devices/$targetDeviceId'),
       ).timeout(const Duration(seconds: 10));
 
       if (response.statusCode != 200) {
@@ -209,23 +261,70 @@ class ApiService {
     }
   }
 
+  // Report that this device is still reachable (call every 15 seconds)
+  Future<void> sendHeartbeat() async {
+    if (_deviceId == null) return;
+    try {
+      final response = await _client.post(
+        Uri.parse('$_baseUrl/api/v1/devices/$_deviceId/heartbeat'),
+        headers: _getAuthHeaders('POST', '$_baseUrl/api/v1/devices/$_deviceId/heartbeat'),
+      ).timeout(const Duration(seconds: 5));
+
+      if (response.statusCode != 200) {
+        throw Exception('Heartbeat failed: ${response.statusCode}');
+      }
+    } catch (e) {
+      throw Exception('Heartbeat error: $e');
+    }
+  }
+
   // Clear local pairing data (logout)
   Future<void> clearPairingData() async {
     await _storage.delete(key: _deviceIdKey);
     await _storage.delete(key: _sessionTokenKey);
+    await _storage.delete(key: _serverFingerprintKey);
     _deviceId = null;
     _sessionToken = null;
+    _serverFingerprint = null;
+    _client.close();
+    _client = http.Client();
   }
 
   // Get auth headers
-  Map<String, String> _getAuthHeaders() {
+  // Sign a request with the pairing token, which itself is never sent:
+  // Authorization: Device {device_id}:{unix seconds}:{tag}
+  Map<String, String> _getAuthHeaders(String method, String url) {
     final headers = {'Content-Type': 'application/json'};
-    if (_sessionToken != null) {
-      headers['Authorization'] = 'Bearer $_sessionToken';
+    if (_deviceId != null && _sessionToken != null) {
+      final timestamp = DateTime.now().millisecondsSinceEpoch ~/ 1000;
+      final tag = _requestTag(timestamp, method, Uri.parse(url).path);
+      headers['Authorization'] = 'Device $_deviceId:$timestamp:$tag';
     }
     return headers;
   }
 
+  // HKDF-SHA256 keyed with the token hash over the time, method and path,
+  // as checked by the server
+  String _requestTag(int timestamp, String method, String path) {
+    final token = base64Url.decode(base64Url.normalize(_sessionToken!));
+    final tokenHash = sha256.convert(token).bytes;
+    final info = BytesBuilder()
+      ..add(utf8.encode('bridgex-http-request'))
+      ..add(_uint64(timestamp));
+    for (final field in [_deviceId!, method, path]) {
+      final bytes = utf8.encode(field);
+      info
+        ..add(_uint64(bytes.length))
+        ..add(bytes);
+    }
+    final prk = Hmac(sha256, List.filled(32, 0)).convert(tokenHash).bytes;
+    return Hmac(sha256, prk).convert([...info.toBytes(), 1]).toString();
+  }
+
+  static List<int> _uint64(int value) {
+    return (ByteData(8)..setInt64(0, value)).buffer.asUint8List();
+  }
+
   // Discover devices on local network
   Future<List<String>> discoverDevices() async {
     final discovered = <String>[];
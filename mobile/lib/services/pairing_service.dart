@@ -10,10 +10,11 @@ class PairingService extends ChangeNotifier {
 
   /// Process pairing QR code
   ///
-  /// Format: bridgex://pair?id={device_id}&key={public_key_base64}
+  /// Format: bridgex://pair?v=1&id={device_id}&key={public_key_base64url}&token=...&addr=...&exp=...&fp=...
   Future<bool> processPairingQR(String qrData) async {
     try {
-      debugPrint('Processing QR code: $qrData');
+      // The QR code carries the pairing token, so it is never printed
+      debugPrint('Processing pairing QR code');
 
       // Parse URI
       final uri = Uri.parse(qrData);
@@ -56,7 +57,8 @@ class PairingService extends ChangeNotifier {
       debugPrint('Device paired successfully');
       return true;
     } catch (e) {
-      debugPrint('Error processing QR code: $e');
+      // Parse errors quote their input, which holds the token
+      debugPrint('Error processing QR code: ${e.runtimeType}');
       return false;
     }
   }
@@ -0,0 +1,22 @@
+import 'dart:io';
+import 'package:crypto/crypto.dart';
+import 'package:http/http.dart' as http;
+import 'package:http/io_client.dart';
+
+/// HTTP client for a BridgeX server presenting its self-signed certificate
+///
+/// No CA is trusted: a TLS connection only succeeds when the server presents
+/// the certificate whose SHA-256 fingerprint (hex) was received at pairing.
+/// Without a fingerprint a plain client is returned, for servers without TLS.
+http.Client createPinnedClient(String? fingerprint) {
+  if (fingerprint == null || fingerprint.isEmpty) {
+    return http.Client();
+  }
+
+  final pinned = fingerprint.toLowerCase();
+  final httpClient =
+      HttpClient(context: SecurityContext(withTrustedRoots: false))
+        ..badCertificateCallback = (X509Certificate cert, String host, int port) =>
+            sha256.convert(cert.der).toString() == pinned;
+  return IOClient(httpClient);
+}
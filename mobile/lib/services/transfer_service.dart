@@ -3,14 +3,19 @@ import 'dart:convert';
 import 'package:flutter/foundation.dart';
 import 'package:http/http.dart' as http;
 import 'package:crypto/crypto.dart';
+import 'pinned_http_client.dart';
 
 /// Service for handling file transfers
 class TransferService extends ChangeNotifier {
   static const String _defaultApiUrl = 'http://192.168.1.100:8080';
   String _apiUrl = _defaultApiUrl;
+  http.Client _client = http.Client();
 
-  void setApiUrl(String url) {
+  /// Set the server URL; https servers are pinned to `fingerprint`
+  void setApiUrl(String url, {String? fingerprint}) {
     _apiUrl = url;
+    _client.close();
+    _client = createPinnedClient(url.startsWith('https://') ? fingerprint : null);
     notifyListeners();
   }
 
@@ -75,7 +80,7 @@ class TransferService extends ChangeNotifier {
     required String fileHash,
   }) async {
     try {
-      final response = await http.post(
+      final response = await _client.post(
         Uri.parse('$_apiUrl/api/v1/transfer'),
         headers: {'Content-Type': 'application/json'},
         body: json.encode({
@@ -114,7 +119,7 @@ class TransferService extends ChangeNotifier {
 
     await for (var chunk in fileStream) {
       // Upload chunk
-      final response = await http.post(
+      final response = await _client.post(
         Uri.parse('$_apiUrl/api/v1/transfer/$transferId/upload'),
         headers: {
           'Content-Type': 'application/octet-stream',